@@ -0,0 +1,642 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+    future::Future,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt, channel::mpsc, stream};
+use k8s_openapi::{
+    api::core::v1::Namespace,
+    chrono::{DateTime, Utc},
+    serde::de::DeserializeOwned,
+};
+use kube::{
+    Api, Client, Resource, ResourceExt,
+    runtime::{
+        WatchStreamExt,
+        reflector::{self, ObjectRef, Store, store::Writer},
+        watcher,
+    },
+};
+use kubizone_common::{FullyQualifiedDomainName, SuffixTrie};
+use kubizone_crds::v1alpha1::{ClusterZone, DomainExt, Record, RecordSet, Zone, ZoneKind, ZoneRef};
+use tracing::*;
+
+use crate::{AnyZone, ZoneResource, health::Health};
+
+/// In-memory caches of all zones, cluster zones, records, record sets and namespaces in
+/// the cluster, shared by the controllers instead of listing them on every reconcile.
+///
+/// Zones and cluster zones are additionally indexed by their fully qualified domain name,
+/// so the zones containing a given domain name can be looked up without scanning all of them.
+///
+/// The parent zones of zones, records and record sets are tracked as well, so a
+/// zone can be reconciled once a resource it previously adopted has moved elsewhere.
+///
+/// Controllers are triggered by [subscribing](Cache::subscribe) to the cache, rather
+/// than running watches of their own, so each kind of resource is only watched once.
+#[derive(Clone)]
+pub struct Cache {
+    zones: Shared<Zone>,
+    cluster_zones: Shared<ClusterZone>,
+    records: Shared<Record>,
+    record_sets: Shared<RecordSet>,
+    namespaces: Shared<Namespace>,
+    index: Arc<RwLock<ZoneIndex>>,
+    parents: Arc<RwLock<ParentIndex>>,
+}
+
+/// Number of changes to a kind of resource buffered for subscribers, before
+/// the cache waits for all of them to catch up.
+const SUBSCRIPTION_BUFFER: usize = 1024;
+
+/// Store of all resources of one kind, along with the subscribers to their changes.
+#[derive(Clone)]
+pub struct Shared<K: Resource<DynamicType = ()> + Clone + 'static> {
+    store: Store<K>,
+    /// Writer of the store, until the cache is started.
+    writer: Arc<Mutex<Option<Writer<K>>>>,
+    /// Subscribers to deleted resources, which the writer does not dispatch.
+    deleted: Arc<Mutex<Vec<mpsc::UnboundedSender<Arc<K>>>>>,
+}
+
+impl<K> Shared<K>
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + Sync + 'static,
+{
+    fn new() -> Self {
+        let (store, writer) = reflector::store_shared(SUBSCRIPTION_BUFFER);
+
+        Shared {
+            store,
+            writer: Arc::new(Mutex::new(Some(writer))),
+            deleted: Arc::default(),
+        }
+    }
+
+    fn subscribe(&self) -> impl Stream<Item = Arc<K>> + Send + use<K> {
+        // Unwrap safety: Writers created by `store_shared` can always be subscribed to.
+        let applied = self
+            .writer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .expect("subscribed to the cache after it was started")
+            .subscribe()
+            .unwrap();
+
+        let (subscriber, deleted) = mpsc::unbounded();
+        self.deleted.lock().unwrap().push(subscriber);
+
+        stream::select(applied, deleted)
+    }
+
+    /// Watch all resources of kind `K`, keeping the store up to date and
+    /// notifying subscribers of the changes.
+    fn reflect(
+        &self,
+        client: Client,
+    ) -> impl Stream<Item = Result<watcher::Event<K>, watcher::Error>> + use<K> {
+        let writer = self
+            .writer
+            .lock()
+            .unwrap()
+            .take()
+            .expect("cache was started twice");
+        let deleted = self.deleted.clone();
+
+        watcher(Api::<K>::all(client), watcher::Config::default())
+            .default_backoff()
+            .reflect_shared(writer)
+            .inspect_ok(move |event| {
+                if let watcher::Event::Delete(resource) = event {
+                    let resource = Arc::new(resource.clone());
+
+                    deleted
+                        .lock()
+                        .unwrap()
+                        .retain(|subscriber| subscriber.unbounded_send(resource.clone()).is_ok());
+                }
+            })
+    }
+}
+
+/// Kinds of resources kept in the [`Cache`].
+pub trait Cached:
+    Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + Sync + 'static
+{
+    fn shared(cache: &Cache) -> &Shared<Self>;
+}
+
+impl Cached for Zone {
+    fn shared(cache: &Cache) -> &Shared<Self> {
+        &cache.zones
+    }
+}
+
+impl Cached for ClusterZone {
+    fn shared(cache: &Cache) -> &Shared<Self> {
+        &cache.cluster_zones
+    }
+}
+
+impl Cached for Record {
+    fn shared(cache: &Cache) -> &Shared<Self> {
+        &cache.records
+    }
+}
+
+impl Cached for RecordSet {
+    fn shared(cache: &Cache) -> &Shared<Self> {
+        &cache.record_sets
+    }
+}
+
+impl Cached for Namespace {
+    fn shared(cache: &Cache) -> &Shared<Self> {
+        &cache.namespaces
+    }
+}
+
+/// Fully qualified domain names of zones, and the zones which have them.
+///
+/// Zones are kept in the trie ordered by creation timestamp, then namespace
+/// and name, so when several zones claim the same fully qualified domain
+/// name, the one created first takes precedence.
+#[derive(Default)]
+struct ZoneIndex {
+    zones: SuffixTrie<ZoneKey>,
+    keys: HashMap<ZoneRef, (FullyQualifiedDomainName, ZoneKey)>,
+}
+
+impl ZoneIndex {
+    /// Index `zone` by its fqdn, if it has one.
+    ///
+    /// Zones being deleted are dropped from the index, so records
+    /// and zones detached from them are not adopted by them again.
+    fn apply<Z: ZoneResource>(&mut self, zone: &Z) {
+        let zone_ref = zone.zone_ref();
+        self.delete(&zone_ref);
+
+        if zone.meta().deletion_timestamp.is_some() {
+            return;
+        }
+
+        if let Some(fqdn) = zone.fqdn() {
+            let key = ZoneKey::new(zone);
+            self.zones.insert(fqdn, key.clone());
+            self.keys.insert(zone_ref, (fqdn.clone(), key));
+        }
+    }
+
+    fn delete(&mut self, zone_ref: &ZoneRef) {
+        let Some((fqdn, key)) = self.keys.remove(zone_ref) else {
+            return;
+        };
+
+        self.zones.remove(&fqdn, &key);
+    }
+
+    /// Keep the index up to date with the events of a watch on zones of kind `Z`.
+    ///
+    /// A relist keeps the zones which still exist, and drops
+    /// those deleted in the meantime.
+    fn track<Z: ZoneResource>(index: Arc<RwLock<Self>>) -> impl FnMut(&watcher::Event<Z>) {
+        let mut relisted = HashSet::new();
+
+        move |event| match event {
+            watcher::Event::Apply(zone) => index.write().unwrap().apply(zone),
+            watcher::Event::Delete(zone) => index.write().unwrap().delete(&zone.zone_ref()),
+            watcher::Event::Init => relisted.clear(),
+            watcher::Event::InitApply(zone) => {
+                relisted.insert(zone.zone_ref());
+                index.write().unwrap().apply(zone);
+            }
+            watcher::Event::InitDone => {
+                let relisted = std::mem::take(&mut relisted);
+                let mut index = index.write().unwrap();
+
+                let deleted: Vec<ZoneRef> = index
+                    .keys
+                    .keys()
+                    .filter(|zone_ref| zone_ref.kind == Z::KIND && !relisted.contains(zone_ref))
+                    .cloned()
+                    .collect();
+
+                for zone_ref in deleted {
+                    index.delete(&zone_ref);
+                }
+            }
+        }
+    }
+}
+
+/// Creation timestamp, namespace and name of a zone, determining
+/// which of several zones with the same fqdn takes precedence.
+///
+/// Cluster zones have no namespace, and therefore precede
+/// namespaced zones created at the same time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct ZoneKey {
+    created: Option<DateTime<Utc>>,
+    namespace: Option<String>,
+    name: String,
+}
+
+impl ZoneKey {
+    fn new<Z: ResourceExt>(zone: &Z) -> Self {
+        ZoneKey {
+            created: zone.creation_timestamp().map(|timestamp| timestamp.0),
+            namespace: zone.namespace(),
+            name: zone.name_any(),
+        }
+    }
+}
+
+impl From<&AnyZone> for ZoneKey {
+    fn from(zone: &AnyZone) -> Self {
+        match zone {
+            AnyZone::Zone(zone) => ZoneKey::new(zone.as_ref()),
+            AnyZone::ClusterZone(zone) => ZoneKey::new(zone.as_ref()),
+        }
+    }
+}
+
+/// Kind, namespace and name of a resource.
+type ResourceKey = (String, String, String);
+
+fn resource_key<K: Resource<DynamicType = ()>>(resource: &K) -> ResourceKey {
+    (
+        K::kind(&()).to_string(),
+        resource.namespace().unwrap_or_default(),
+        resource.name_any(),
+    )
+}
+
+/// Current and previous parent zones of resources, as given by their parent zone label.
+#[derive(Default)]
+struct ParentIndex {
+    parents: HashMap<ResourceKey, Parents>,
+}
+
+#[derive(Default)]
+struct Parents {
+    current: Option<ZoneRef>,
+    previous: Option<ZoneRef>,
+}
+
+impl ParentIndex {
+    fn apply<K: Resource<DynamicType = ()> + DomainExt>(&mut self, resource: &K) {
+        let parents = self.parents.entry(resource_key(resource)).or_default();
+        let parent = resource.parent();
+
+        if parents.current != parent {
+            parents.previous = std::mem::replace(&mut parents.current, parent);
+        }
+    }
+
+    fn delete<K: Resource<DynamicType = ()>>(&mut self, resource: &K) {
+        self.parents.remove(&resource_key(resource));
+    }
+
+    /// Keep the index up to date with the events of a watch on `K`.
+    ///
+    /// A relist keeps the parents of resources which still exist,
+    /// and drops those of resources deleted in the meantime.
+    fn track<K>(index: Arc<RwLock<Self>>) -> impl FnMut(&watcher::Event<K>)
+    where
+        K: Resource<DynamicType = ()> + DomainExt,
+    {
+        let mut relisted = HashSet::new();
+
+        move |event| match event {
+            watcher::Event::Apply(resource) => index.write().unwrap().apply(resource),
+            watcher::Event::Delete(resource) => index.write().unwrap().delete(resource),
+            watcher::Event::Init => relisted.clear(),
+            watcher::Event::InitApply(resource) => {
+                relisted.insert(resource_key(resource));
+                index.write().unwrap().apply(resource);
+            }
+            watcher::Event::InitDone => {
+                let relisted = std::mem::take(&mut relisted);
+                index
+                    .write()
+                    .unwrap()
+                    .parents
+                    .retain(|key, _| key.0 != K::kind(&()) || relisted.contains(key));
+            }
+        }
+    }
+}
+
+impl Cache {
+    /// Construct the cache, along with the future which keeps it up to date.
+    ///
+    /// The cache remains empty until the future is polled, and will not be
+    /// updated after it has been dropped. Subscribers must subscribe before
+    /// the future is first polled.
+    pub fn new(client: Client) -> (Self, impl Future<Output = ()>) {
+        let cache = Cache::empty();
+
+        let shared = cache.clone();
+        let future = async move {
+            // Zones are indexed once the store has been updated.
+            let zones = shared
+                .zones
+                .reflect(client.clone())
+                .inspect_ok(ZoneIndex::track(shared.index.clone()))
+                .inspect_ok(ParentIndex::track(shared.parents.clone()));
+
+            let cluster_zones = shared
+                .cluster_zones
+                .reflect(client.clone())
+                .inspect_ok(ZoneIndex::track(shared.index.clone()))
+                .inspect_ok(ParentIndex::track(shared.parents.clone()));
+
+            let records = shared
+                .records
+                .reflect(client.clone())
+                .inspect_ok(ParentIndex::track(shared.parents.clone()));
+
+            let record_sets = shared
+                .record_sets
+                .reflect(client.clone())
+                .inspect_ok(ParentIndex::track(shared.parents.clone()));
+
+            let namespaces = shared.namespaces.reflect(client);
+
+            futures::join!(
+                drive(zones),
+                drive(cluster_zones),
+                drive(records),
+                drive(record_sets),
+                drive(namespaces)
+            );
+        };
+
+        (cache, future)
+    }
+
+    fn empty() -> Self {
+        Cache {
+            zones: Shared::new(),
+            cluster_zones: Shared::new(),
+            records: Shared::new(),
+            record_sets: Shared::new(),
+            namespaces: Shared::new(),
+            index: Arc::default(),
+            parents: Arc::default(),
+        }
+    }
+
+    /// Cache holding only the given `zones` and `cluster_zones`, without watching anything.
+    #[cfg(test)]
+    pub(crate) fn with_zones(zones: Vec<Zone>, cluster_zones: Vec<ClusterZone>) -> Self {
+        fn fill<Z: ZoneResource + Cached>(cache: &Cache, zones: Vec<Z>) {
+            let mut writer = Z::shared(cache).writer.lock().unwrap().take().unwrap();
+
+            for zone in zones {
+                cache.index.write().unwrap().apply(&zone);
+                writer.apply_watcher_event(&watcher::Event::Apply(zone));
+            }
+        }
+
+        let cache = Cache::empty();
+        fill(&cache, zones);
+        fill(&cache, cluster_zones);
+        cache
+    }
+
+    /// Store of all resources of kind `K`.
+    pub fn store<K: Cached>(&self) -> Store<K> {
+        K::shared(self).store.clone()
+    }
+
+    /// Stream of resources of kind `K` as they are changed or deleted, used
+    /// for triggering controllers.
+    ///
+    /// Subscribers must keep consuming the stream, since the cache waits for
+    /// them once too many changes are buffered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache has already been started, since the changes
+    /// observed so far would be missed.
+    pub fn subscribe<K: Cached>(&self) -> impl Stream<Item = Arc<K>> + Send + use<K> {
+        K::shared(self).subscribe()
+    }
+
+    /// Register the watchers of the cache with `health`, and mark
+    /// each of them as ready once it has received its initial list.
+    pub async fn report_health(&self, health: &Health) {
+        const WATCHERS: [&str; 5] = [
+            "zone watcher",
+            "clusterzone watcher",
+            "record watcher",
+            "recordset watcher",
+            "namespace watcher",
+        ];
+
+        for watcher in WATCHERS {
+            health.register(watcher);
+        }
+
+        futures::join!(
+            health.ready_when_synced(WATCHERS[0], self.zones.store.clone()),
+            health.ready_when_synced(WATCHERS[1], self.cluster_zones.store.clone()),
+            health.ready_when_synced(WATCHERS[2], self.records.store.clone()),
+            health.ready_when_synced(WATCHERS[3], self.record_sets.store.clone()),
+            health.ready_when_synced(WATCHERS[4], self.namespaces.store.clone()),
+        );
+    }
+
+    /// Whether all caches have received their initial list of resources.
+    pub fn is_ready(&self) -> bool {
+        self.wait_until_ready().now_or_never().unwrap_or(false)
+    }
+
+    /// Wait for all caches to receive their initial list of resources.
+    ///
+    /// Returns false if the cache stopped being updated before then.
+    pub async fn wait_until_ready(&self) -> bool {
+        let (zones, cluster_zones, records, record_sets, namespaces) = futures::join!(
+            self.zones.store.wait_until_ready(),
+            self.cluster_zones.store.wait_until_ready(),
+            self.records.store.wait_until_ready(),
+            self.record_sets.store.wait_until_ready(),
+            self.namespaces.store.wait_until_ready(),
+        );
+
+        zones.is_ok()
+            && cluster_zones.is_ok()
+            && records.is_ok()
+            && record_sets.is_ok()
+            && namespaces.is_ok()
+    }
+
+    /// Zone referenced by `zone_ref`, relative to a resource in `namespace`, if any.
+    ///
+    /// Zones being deleted are treated as if they no longer exist.
+    pub fn zone(&self, zone_ref: &ZoneRef, namespace: Option<&str>) -> Option<AnyZone> {
+        let zone_ref = zone_ref.in_namespace(namespace);
+
+        let zone = match zone_ref.kind {
+            ZoneKind::Zone => self
+                .zones
+                .store
+                .get(&ObjectRef::new(&zone_ref.name).within(zone_ref.namespace.as_deref()?))
+                .map(AnyZone::Zone),
+            ZoneKind::ClusterZone => self
+                .cluster_zones
+                .store
+                .get(&ObjectRef::new(&zone_ref.name))
+                .map(AnyZone::ClusterZone),
+        }?;
+
+        zone.meta().deletion_timestamp.is_none().then_some(zone)
+    }
+
+    pub fn zones(&self) -> Vec<Arc<Zone>> {
+        self.zones.store.state()
+    }
+
+    pub fn cluster_zones(&self) -> Vec<Arc<ClusterZone>> {
+        self.cluster_zones.store.state()
+    }
+
+    pub fn records(&self) -> Vec<Arc<Record>> {
+        self.records.store.state()
+    }
+
+    pub fn record_sets(&self) -> Vec<Arc<RecordSet>> {
+        self.record_sets.store.state()
+    }
+
+    /// Records with an owner reference to the resource with the given `uid`.
+    pub fn owned_records(&self, uid: &str) -> Vec<Arc<Record>> {
+        self.records
+            .store
+            .state()
+            .into_iter()
+            .filter(|record| {
+                record
+                    .owner_references()
+                    .iter()
+                    .any(|owner| owner.uid == uid)
+            })
+            .collect()
+    }
+
+    /// Zones with exactly the fully qualified domain name `fqdn`,
+    /// ordered by precedence.
+    pub fn zones_at(&self, fqdn: &FullyQualifiedDomainName) -> Vec<AnyZone> {
+        self.index
+            .read()
+            .unwrap()
+            .zones
+            .get(fqdn)
+            .filter_map(|key| self.get_zone(key))
+            .collect()
+    }
+
+    /// Most immediate zone containing `fqdn`, being either the zone
+    /// with the longest fqdn which `fqdn` is a subdomain of, or a zone
+    /// with `fqdn` itself, if `apex` is true.
+    ///
+    /// If several zones have the same fqdn, the one which claimed it
+    /// first is picked, so the outcome does not depend on the order
+    /// in which the zones were observed.
+    pub fn parent_zone(&self, fqdn: &FullyQualifiedDomainName, apex: bool) -> Option<AnyZone> {
+        let index = self.index.read().unwrap();
+
+        let key = if apex {
+            index.zones.longest_match(fqdn)
+        } else {
+            index.zones.longest_parent_match(fqdn)
+        }?;
+
+        self.get_zone(key)
+    }
+
+    fn get_zone(&self, key: &ZoneKey) -> Option<AnyZone> {
+        match &key.namespace {
+            Some(namespace) => self
+                .zones
+                .store
+                .get(&ObjectRef::new(&key.name).within(namespace))
+                .map(AnyZone::Zone),
+            None => self
+                .cluster_zones
+                .store
+                .get(&ObjectRef::new(&key.name))
+                .map(AnyZone::ClusterZone),
+        }
+    }
+
+    /// Zone which has claimed `fqdn` before `zone` did, by being created first.
+    ///
+    /// Zones created at the same time are ordered by namespace and name, so
+    /// exactly one of several zones with the same fqdn is never in conflict.
+    pub fn claimed_by(&self, zone: &AnyZone, fqdn: &FullyQualifiedDomainName) -> Option<AnyZone> {
+        let key = ZoneKey::from(zone);
+
+        let index = self.index.read().unwrap();
+        let first = index.zones.get(fqdn).find(|other| **other != key)?;
+
+        if *first < key {
+            self.get_zone(first)
+        } else {
+            None
+        }
+    }
+
+    /// Zones which `resource` is, or was most recently, adopted by.
+    ///
+    /// The parent zone label of `resource` itself is included as well, since
+    /// the cache might not have observed this version of it yet.
+    pub fn parent_zones<K>(&self, resource: &K) -> Vec<ZoneRef>
+    where
+        K: Resource<DynamicType = ()> + DomainExt,
+    {
+        let parents = self.parents.read().unwrap();
+        let cached = parents.parents.get(&resource_key(resource));
+
+        let mut zone_refs: Vec<ZoneRef> = resource
+            .parent()
+            .into_iter()
+            .chain(cached.and_then(|parents| parents.current.clone()))
+            .chain(cached.and_then(|parents| parents.previous.clone()))
+            .collect();
+
+        zone_refs.sort();
+        zone_refs.dedup();
+        zone_refs
+    }
+
+    /// Labels of the given namespace, used for evaluating namespace selectors of delegations.
+    pub fn namespace_labels(&self, namespace: &str) -> BTreeMap<String, String> {
+        self.namespaces
+            .store
+            .get(&ObjectRef::new(namespace))
+            .map(|namespace| namespace.labels().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Consume the reflector stream, keeping its store up to date.
+async fn drive<K>(stream: impl Stream<Item = Result<watcher::Event<K>, watcher::Error>>)
+where
+    K: Resource<DynamicType = ()>,
+{
+    stream
+        .for_each(|event| async move {
+            if let Err(error) = event {
+                warn!(
+                    "{} cache watch failed: {error}",
+                    K::kind(&()).to_lowercase()
+                );
+            }
+        })
+        .await;
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use kube::ResourceExt;
+use kubizone_common::{Class, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::{
+    DomainExt as _, Explanation, RecordExt, ZoneEntry, ZoneExt as _, ZoneKind, ZoneRef,
+};
+
+use crate::{AnyZone, cache::Cache};
+
+/// Explain how the delegations of a zone apply to a record of `record_type`
+/// at `record_fqdn` in `namespace`, using the cluster objects in `cache`.
+///
+/// The record's `ttl` defaults to the one of the zone. Records and record sets
+/// in `namespace` already adopted by the zone count towards its quotas.
+///
+/// The zone is picked by [`find_zone`].
+pub fn explain(
+    cache: &Cache,
+    namespace: &str,
+    record_type: Type,
+    record_fqdn: &FullyQualifiedDomainName,
+    ttl: Option<u32>,
+    rdata: &str,
+    zone_ref: Option<&ZoneRef>,
+) -> Option<(AnyZone, Explanation)> {
+    let zone = find_zone(cache, namespace, record_fqdn, zone_ref)?;
+    let labels = cache.namespace_labels(namespace);
+
+    let mut admitted = admitted_entries(cache.records(), namespace, &zone);
+    admitted.extend(admitted_entries(cache.record_sets(), namespace, &zone));
+
+    let entry = ZoneEntry {
+        fqdn: record_fqdn.clone(),
+        type_: record_type,
+        class: Class::IN,
+        ttl: ttl.unwrap_or(zone.zone_spec().ttl),
+        rdata: rdata.to_string(),
+    };
+
+    let explanation = zone.explain_record(namespace, &labels, &entry, &admitted);
+
+    Some((zone, explanation))
+}
+
+/// Find the zone a record at `record_fqdn` in `namespace` would be evaluated against,
+/// the same way the record controller would pick it.
+///
+/// If no `zone_ref` is given, the most immediate parent zone of the record is used.
+/// Zones being deleted, or whose fqdn has been claimed by another zone, are never picked.
+pub fn find_zone(
+    cache: &Cache,
+    namespace: &str,
+    record_fqdn: &FullyQualifiedDomainName,
+    zone_ref: Option<&ZoneRef>,
+) -> Option<AnyZone> {
+    match zone_ref {
+        Some(zone_ref) => {
+            let zone = cache.zone(zone_ref, Some(namespace))?;
+            cache
+                .claimed_by(&zone, zone.fqdn()?)
+                .is_none()
+                .then_some(zone)
+        }
+        None => cache.parent_zone(record_fqdn, true),
+    }
+}
+
+/// Entries of all `resources` in `namespace` adopted by `zone`.
+fn admitted_entries<R: ResourceExt + RecordExt>(
+    resources: Vec<Arc<R>>,
+    namespace: &str,
+    zone: &AnyZone,
+) -> Vec<ZoneEntry> {
+    let zone_ref = zone.zone_ref();
+
+    resources
+        .into_iter()
+        .filter(|resource| resource.namespace().as_deref() == Some(namespace))
+        .filter(|resource| resource.parent().as_ref() == Some(&zone_ref))
+        .flat_map(|resource| {
+            resource
+                .fqdn()
+                .map(|fqdn| resource.entries(fqdn, zone.zone_spec().ttl))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Parse a zone reference of the form `name`, `namespace/name` or `ClusterZone/name`.
+pub fn parse_zone_ref(value: &str) -> Result<ZoneRef, String> {
+    Ok(match value.trim().split_once('/') {
+        Some(("ClusterZone", name)) => ZoneRef {
+            kind: ZoneKind::ClusterZone,
+            name: name.to_string(),
+            namespace: None,
+        },
+        Some((namespace, name)) => ZoneRef {
+            kind: ZoneKind::Zone,
+            name: name.to_string(),
+            namespace: Some(namespace.to_string()),
+        },
+        None => ZoneRef {
+            kind: ZoneKind::Zone,
+            name: value.trim().to_string(),
+            namespace: None,
+        },
+    })
+}
+
+/// Parse a record type, such as `A` or `cname`.
+pub fn parse_type(value: &str) -> Result<Type, String> {
+    k8s_openapi::serde_json::from_value(k8s_openapi::serde_json::Value::String(
+        value.to_uppercase(),
+    ))
+    .map_err(|_| format!("unknown record type {value}"))
+}
+
+/// Parse a fully qualified domain name, such as `www.example.org.`
+pub fn parse_fqdn(value: &str) -> Result<FullyQualifiedDomainName, String> {
+    FullyQualifiedDomainName::try_from(value).map_err(|error| error.to_string())
+}
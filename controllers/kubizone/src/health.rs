@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write as _},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode, body::Bytes, server::conn::http1, service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use kube::{Resource, runtime::reflector::Store};
+use tokio::net::TcpListener;
+use tracing::*;
+
+use crate::metrics::Metrics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the initial list of resources.
+    Starting,
+    /// Initial list of resources has been received.
+    Ready,
+    /// Stopped running, and will not recover.
+    Exited,
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Starting => f.write_str("starting"),
+            State::Ready => f.write_str("ready"),
+            State::Exited => f.write_str("exited"),
+        }
+    }
+}
+
+/// Tracks the state of each of the controllers and watchers, as reported
+/// by the `/healthz` and `/readyz` endpoints.
+///
+/// The process is healthy as long as none of them have exited, and ready once
+/// all of them have received their initial list of resources. Nothing is
+/// ready before at least one controller or watcher has been registered.
+#[derive(Default)]
+pub struct Health {
+    controllers: Mutex<BTreeMap<String, State>>,
+}
+
+impl Health {
+    /// Register `controller`, which is considered to be starting until marked as ready.
+    ///
+    /// Controllers must be registered before their first await point, so
+    /// readiness is never reported before all controllers have been registered.
+    pub fn register(&self, controller: &str) {
+        self.set(controller, State::Starting);
+    }
+
+    /// Wait for `store` to receive its initial list of resources,
+    /// then mark `controller` as ready.
+    pub async fn ready_when_synced<K>(&self, controller: &str, store: Store<K>)
+    where
+        K: Resource + Clone + 'static,
+        K::DynamicType: Eq + std::hash::Hash + Clone,
+    {
+        // The writer is only dropped if the controller exits, which is reported separately.
+        if store.wait_until_ready().await.is_ok() {
+            debug!("{controller} controller synced");
+            self.set(controller, State::Ready);
+        }
+    }
+
+    /// Mark `controller` as ready, for components which do not
+    /// wait for an initial list of resources.
+    pub fn ready(&self, controller: &str) {
+        self.set(controller, State::Ready);
+    }
+
+    /// Mark `controller` as having exited.
+    pub fn exited(&self, controller: &str) {
+        self.set(controller, State::Exited);
+    }
+
+    fn set(&self, controller: &str, state: State) {
+        let mut controllers = self.controllers.lock().unwrap();
+
+        // Exiting is final.
+        if controllers.get(controller) != Some(&State::Exited) {
+            controllers.insert(controller.to_string(), state);
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.controllers
+            .lock()
+            .unwrap()
+            .values()
+            .all(|state| *state != State::Exited)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        let controllers = self.controllers.lock().unwrap();
+
+        !controllers.is_empty() && controllers.values().all(|state| *state == State::Ready)
+    }
+
+    /// State of each controller, one per line.
+    fn report(&self) -> String {
+        let mut report = String::new();
+        for (controller, state) in self.controllers.lock().unwrap().iter() {
+            // Unwrap safety: Writing to a String cannot fail.
+            writeln!(report, "{controller}: {state}").unwrap();
+        }
+        report
+    }
+}
+
+/// Serve metrics at `/metrics` and the health of the controllers
+/// at `/healthz` and `/readyz` over plain HTTP on `address`.
+pub async fn serve(
+    address: SocketAddr,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+
+    info!("serving metrics and health probes on {address}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let metrics = metrics.clone();
+        let health = health.clone();
+
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let response = handle(request, &metrics, &health);
+                async move { Ok::<_, hyper::Error>(response) }
+            });
+
+            if let Err(error) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                warn!("connection with {peer} failed: {error}");
+            }
+        });
+    }
+}
+
+fn handle<B>(request: Request<B>, metrics: &Metrics, health: &Health) -> Response<Full<Bytes>> {
+    if request.method() != Method::GET {
+        return status(StatusCode::METHOD_NOT_ALLOWED, String::new());
+    }
+
+    match request.uri().path() {
+        "/metrics" => Response::builder()
+            .header(
+                "Content-Type",
+                "application/openmetrics-text; version=1.0.0; charset=utf-8",
+            )
+            .body(Full::new(Bytes::from(metrics.encode())))
+            .unwrap(),
+        "/healthz" if health.is_healthy() => status(StatusCode::OK, health.report()),
+        "/healthz" => status(StatusCode::SERVICE_UNAVAILABLE, health.report()),
+        "/readyz" if health.is_healthy() && health.is_ready() => {
+            status(StatusCode::OK, health.report())
+        }
+        "/readyz" => status(StatusCode::SERVICE_UNAVAILABLE, health.report()),
+        _ => status(StatusCode::NOT_FOUND, String::new()),
+    }
+}
+
+fn status(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Health;
+
+    #[test]
+    fn readiness() {
+        let health = Health::default();
+
+        // Nothing has been registered yet.
+        assert!(health.is_healthy());
+        assert!(!health.is_ready());
+
+        health.register("zone");
+        health.register("zone watcher");
+        assert!(!health.is_ready());
+
+        health.set("zone", super::State::Ready);
+        assert!(!health.is_ready());
+
+        health.set("zone watcher", super::State::Ready);
+        assert!(health.is_ready());
+
+        // Exiting is final.
+        health.exited("zone watcher");
+        health.register("zone watcher");
+        assert!(!health.is_healthy());
+        assert!(!health.is_ready());
+    }
+}
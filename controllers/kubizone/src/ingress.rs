@@ -1,163 +1,1076 @@
 use futures::StreamExt;
-use k8s_openapi::api::networking::v1::Ingress;
-use kubizone_common::{Class, DomainName, Type};
+use k8s_openapi::api::{core::v1::Namespace, networking::v1::Ingress};
+use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Type};
 use std::{net::IpAddr, str::FromStr, sync::Arc, time::Duration};
 
 use kube::{
     Api, Client, Resource, ResourceExt,
-    api::{ObjectMeta, PatchParams},
-    runtime::{Controller, controller::Action, watcher},
+    api::{DeleteParams, ObjectMeta, PatchParams},
+    core::{Expression, Selector, SelectorExt as _},
+    runtime::{
+        Controller,
+        controller::Action,
+        events::{Event, EventType, Recorder, Reporter},
+        reflector::{ObjectRef, Store},
+        watcher,
+    },
+};
+use kubizone_crds::{
+    ownership::Ownership,
+    v1alpha1::{ClusterZone, DomainExt as _, Record, RecordSpec, Zone, ZoneExt, ZoneKind, ZoneRef},
 };
-use kubizone_crds::v1alpha1::{Record, RecordSpec};
 use tracing::*;
 
+use crate::{cache::Cache, health::Health, metrics::Metrics, zone_changes};
+
 #[cfg(feature = "dev")]
 const CONTROLLER_NAME: &str = "dev.kubi.zone/ingress-resolver";
 #[cfg(not(feature = "dev"))]
 const CONTROLLER_NAME: &str = "kubi.zone/ingress-resolver";
 
-pub async fn controller(context: IngressControllerContext) {
-    let ingresses = Api::<Ingress>::all(context.client.clone());
-    let records = Api::<Record>::all(context.client.clone());
-
-    let ingress_controller = Controller::new(ingresses, watcher::Config::default())
-        .owns(records, watcher::Config::default())
-        .shutdown_on_signal()
-        .run(reconcile_ingresses, ingress_error_policy, Arc::new(context))
-        .for_each(|res| async move {
-            match res {
-                Ok((o, _)) => info!("reconciled {}.{}", o.name, o.namespace.unwrap_or_default()),
-                Err(e) => warn!("reconcile failed: {}", e),
+/// Annotation explicitly enabling (`"true"`) or disabling (`"false"`)
+/// record creation for an ingress.
+#[cfg(feature = "dev")]
+pub const ENABLED_ANNOTATION: &str = "dev.kubi.zone/enabled";
+#[cfg(not(feature = "dev"))]
+pub const ENABLED_ANNOTATION: &str = "kubi.zone/enabled";
+
+/// Annotation containing a comma-separated list of ingress hosts
+/// for which no records should be created.
+#[cfg(feature = "dev")]
+pub const EXCLUDE_HOSTS_ANNOTATION: &str = "dev.kubi.zone/exclude-hosts";
+#[cfg(not(feature = "dev"))]
+pub const EXCLUDE_HOSTS_ANNOTATION: &str = "kubi.zone/exclude-hosts";
+
+/// Annotation overriding the Time-to-Live of all records created for an ingress.
+#[cfg(feature = "dev")]
+pub const TTL_ANNOTATION: &str = "dev.kubi.zone/ttl";
+#[cfg(not(feature = "dev"))]
+pub const TTL_ANNOTATION: &str = "kubi.zone/ttl";
+
+/// Annotation referencing the zone which records created for an ingress
+/// should be placed in, either as `name`, `namespace/name`, or `ClusterZone/name`.
+#[cfg(feature = "dev")]
+pub const ZONE_REF_ANNOTATION: &str = "dev.kubi.zone/zone-ref";
+#[cfg(not(feature = "dev"))]
+pub const ZONE_REF_ANNOTATION: &str = "kubi.zone/zone-ref";
+
+/// Annotation containing a comma-separated list of addresses or hostnames,
+/// which replace the targets reported by the ingress' load balancer.
+#[cfg(feature = "dev")]
+pub const TARGETS_ANNOTATION: &str = "dev.kubi.zone/targets";
+#[cfg(not(feature = "dev"))]
+pub const TARGETS_ANNOTATION: &str = "kubi.zone/targets";
+
+/// Strategy used when an ingress host sits at the apex of a zone, but the
+/// load balancer only reports hostnames.
+///
+/// CNAME records cannot coexist with the SOA and NS records found at the
+/// apex of a zone, so a different kind of record must be produced instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ApexFallback {
+    /// Don't create any records for hosts at a zone apex.
+    #[default]
+    Skip,
+    /// Resolve the load balancer hostname and create A/AAAA records
+    /// pointing directly at the resulting addresses.
+    Resolve,
+}
+
+/// Run the controller creating [`Record`]s for ingresses.
+///
+/// The controller subscribes to the cache right away, so this
+/// must be called before the cache is started.
+pub fn controller(context: IngressControllerContext) -> impl Future<Output = ()> {
+    let records = context.cache.subscribe::<Record>();
+    let zones = context.cache.subscribe::<Zone>();
+    let cluster_zones = context.cache.subscribe::<ClusterZone>();
+
+    // Namespaces are only watched when their labels determine which ingresses are selected.
+    let namespaces = context
+        .namespace_selector
+        .is_some()
+        .then(|| context.cache.subscribe::<Namespace>());
+
+    async move {
+        let health = context.health.clone();
+        health.register("ingress");
+
+        if !context.cache.wait_until_ready().await {
+            health.exited("ingress");
+            warn!("ingress controller exited, since the cache stopped before synchronizing");
+            return;
+        }
+
+        let ingresses = Api::<Ingress>::all(context.client.clone());
+
+        let recorder = Recorder::new(context.client.clone(), Reporter::from(CONTROLLER_NAME));
+
+        let ingress_controller = Controller::new(ingresses, watcher::Config::default());
+        let store = ingress_controller.store();
+        let synced = ingress_controller.store();
+
+        let ingress_controller = ingress_controller
+            .owns_shared_stream(records)
+            .watches_shared_stream(zone_changes(zones), with_zone(store.clone()))
+            .watches_shared_stream(zone_changes(cluster_zones), with_zone(store.clone()));
+
+        let ingress_controller = match namespaces {
+            Some(namespaces) => {
+                ingress_controller.watches_shared_stream(namespaces, with_namespace(store))
             }
-        });
+            None => ingress_controller,
+        };
 
-    ingress_controller.await;
-    warn!("ingress controller exited");
+        let ingress_controller = ingress_controller
+            .shutdown_on_signal()
+            .run(
+                |ingress, ctx| {
+                    let metrics = ctx.metrics.clone();
+                    let recorder = recorder.clone();
+                    async move {
+                        metrics
+                            .measure("ingress", reconcile_ingresses(ingress, ctx, recorder))
+                            .await
+                    }
+                },
+                ingress_error_policy,
+                Arc::new(context),
+            )
+            .for_each(|res| async move {
+                match res {
+                    Ok((o, _)) => {
+                        info!("reconciled {}.{}", o.name, o.namespace.unwrap_or_default())
+                    }
+                    Err(e) => warn!("reconcile failed: {}", e),
+                }
+            });
+
+        futures::future::join(
+            ingress_controller,
+            health.ready_when_synced("ingress", synced),
+        )
+        .await;
+        health.exited("ingress");
+        warn!("ingress controller exited");
+    }
 }
 
 pub struct IngressControllerContext {
     pub client: Client,
     pub requeue_time: Duration,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<Health>,
+    pub cache: Cache,
+    pub apex_fallback: ApexFallback,
+    /// Only create records for ingresses which have explicitly
+    /// opted in using the [`ENABLED_ANNOTATION`].
+    pub opt_in: bool,
+    /// If not empty, only ingresses with one of these `ingressClassName`s are considered.
+    pub ingress_classes: Vec<String>,
+    /// If set, only ingresses in namespaces matching this label selector are considered.
+    pub namespace_selector: Option<Selector>,
+    /// If set, a companion TXT record identifying this cluster and the
+    /// owning ingress is created for every generated record.
+    pub registry_cluster_id: Option<String>,
+}
+
+/// Legacy annotation used for selecting the ingress class, before
+/// `ingressClassName` was introduced.
+const LEGACY_INGRESS_CLASS_ANNOTATION: &str = "kubernetes.io/ingress.class";
+
+/// Check if the ingress is selected by the ingress class and namespace
+/// filters configured for the controller.
+fn is_selected(ingress: &Ingress, ctx: &IngressControllerContext) -> bool {
+    if !ctx.ingress_classes.is_empty() {
+        let ingress_class = ingress
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.ingress_class_name.as_ref())
+            .or_else(|| ingress.annotations().get(LEGACY_INGRESS_CLASS_ANNOTATION));
+
+        if !ingress_class.is_some_and(|class| ctx.ingress_classes.contains(class)) {
+            debug!("ingress class {ingress_class:?} is not selected.");
+            return false;
+        }
+    }
+
+    if let Some(selector) = &ctx.namespace_selector {
+        let namespace = ingress.namespace().unwrap_or_default();
+
+        if !selector.matches(&ctx.cache.namespace_labels(&namespace)) {
+            debug!("namespace {namespace} does not match selector {selector}.");
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Parse a cluster id used for the TXT ownership registry.
+pub fn parse_cluster_id(value: &str) -> Result<String, String> {
+    Ownership::validate_cluster_id(value).map_err(|err| err.to_string())?;
+    Ok(value.to_string())
+}
+
+/// Parse a label selector in the format used by `kubectl --selector`,
+/// such as `dns=public,environment in (production, staging),!internal`.
+pub fn parse_label_selector(value: &str) -> Result<Selector, String> {
+    let mut terms = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                terms.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    terms.push(&value[start..]);
+
+    terms
+        .into_iter()
+        .map(str::trim)
+        .filter(|term| !term.is_empty())
+        .map(parse_label_expression)
+        .collect()
+}
+
+/// Parse a single requirement of a label selector.
+fn parse_label_expression(term: &str) -> Result<Expression, String> {
+    let key = |key: &str| {
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            Err(format!("invalid label selector requirement {term:?}"))
+        } else {
+            Ok(key.to_string())
+        }
+    };
+
+    if let Some((prefix, values)) = term.split_once('(') {
+        let values = values
+            .strip_suffix(')')
+            .ok_or_else(|| format!("unterminated value set in {term:?}"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect();
+
+        return match prefix.trim().rsplit_once(char::is_whitespace) {
+            Some((name, "in")) => Ok(Expression::In(key(name)?, values)),
+            Some((name, "notin")) => Ok(Expression::NotIn(key(name)?, values)),
+            _ => Err(format!("invalid label selector requirement {term:?}")),
+        };
+    }
+
+    if let Some(name) = term.strip_prefix('!') {
+        Ok(Expression::DoesNotExist(key(name)?))
+    } else if let Some((name, value)) = term.split_once("!=") {
+        Ok(Expression::NotEqual(key(name)?, value.trim().to_string()))
+    } else if let Some((name, value)) = term.split_once("==").or_else(|| term.split_once('=')) {
+        Ok(Expression::Equal(key(name)?, value.trim().to_string()))
+    } else {
+        Ok(Expression::Exists(key(term)?))
+    }
+}
+
+/// Per-ingress settings parsed from its annotations.
+///
+/// Invalid values are reported in `invalid`, and otherwise
+/// treated as if the annotation was not present.
+#[derive(Debug, Default)]
+struct IngressAnnotations {
+    enabled: Option<bool>,
+    exclude_hosts: Vec<FullyQualifiedDomainName>,
+    ttl: Option<u32>,
+    zone_ref: Option<ZoneRef>,
+    targets: Option<Targets>,
+    /// Descriptions of the invalid annotation values.
+    invalid: Vec<String>,
+}
+
+/// Addresses and hostnames which records created for an ingress point at.
+#[derive(Debug, Default, PartialEq)]
+struct Targets {
+    addresses: Vec<IpAddr>,
+    hostnames: Vec<FullyQualifiedDomainName>,
+}
+
+impl From<&Ingress> for IngressAnnotations {
+    fn from(ingress: &Ingress) -> Self {
+        let annotations = ingress.annotations();
+        let mut invalid = Vec::new();
+
+        let enabled = annotations.get(ENABLED_ANNOTATION).and_then(|value| {
+            bool::from_str(value.trim())
+                .inspect_err(|_| {
+                    invalid.push(format!(
+                        "{ENABLED_ANNOTATION} value {value:?} is not a boolean"
+                    ))
+                })
+                .ok()
+        });
+
+        let exclude_hosts = annotations
+            .get(EXCLUDE_HOSTS_ANNOTATION)
+            .map(|hosts| {
+                comma_separated(hosts)
+                    .filter_map(|host| {
+                        DomainName::try_from(host)
+                            .inspect_err(|err| {
+                                invalid.push(format!(
+                                    "{EXCLUDE_HOSTS_ANNOTATION} host {host:?} is invalid: {err}"
+                                ))
+                            })
+                            .ok()
+                    })
+                    .map(|host| host.to_fully_qualified())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let ttl = annotations.get(TTL_ANNOTATION).and_then(|value| {
+            u32::from_str(value.trim())
+                .inspect_err(|err| {
+                    invalid.push(format!(
+                        "{TTL_ANNOTATION} value {value:?} is invalid: {err}"
+                    ))
+                })
+                .ok()
+        });
+
+        let zone_ref = annotated_zone_ref(ingress);
+
+        let targets = annotations.get(TARGETS_ANNOTATION).map(|targets| {
+            let mut parsed = Targets::default();
+
+            for target in comma_separated(targets) {
+                if let Ok(address) = IpAddr::from_str(target) {
+                    parsed.addresses.push(address);
+                } else {
+                    match fully_qualified(target) {
+                        Ok(hostname) => parsed.hostnames.push(hostname),
+                        Err(err) => invalid.push(format!(
+                            "{TARGETS_ANNOTATION} target {target:?} is invalid: {err}"
+                        )),
+                    }
+                }
+            }
+
+            parsed
+        });
+
+        IngressAnnotations {
+            enabled,
+            exclude_hosts,
+            ttl,
+            zone_ref,
+            targets,
+            invalid,
+        }
+    }
+}
+
+/// Zone referenced by the [`ZONE_REF_ANNOTATION`] of the ingress, if any.
+///
+/// Zone references are written as `namespace/name`, or `ClusterZone/name`, matching
+/// the way they are displayed. The namespace defaults to that of the ingress itself.
+fn annotated_zone_ref(ingress: &Ingress) -> Option<ZoneRef> {
+    let value = ingress.annotations().get(ZONE_REF_ANNOTATION)?;
+
+    Some(match value.trim().split_once('/') {
+        Some(("ClusterZone", name)) => ZoneRef {
+            kind: ZoneKind::ClusterZone,
+            name: name.to_string(),
+            namespace: None,
+        },
+        Some((namespace, name)) => ZoneRef {
+            kind: ZoneKind::Zone,
+            name: name.to_string(),
+            namespace: Some(namespace.to_string()),
+        },
+        None => ZoneRef {
+            kind: ZoneKind::Zone,
+            name: value.trim().to_string(),
+            namespace: ingress.namespace(),
+        },
+    })
+}
+
+/// Maps a namespace to the ingresses within it, since their selection depends on its labels.
+fn with_namespace(store: Store<Ingress>) -> impl Fn(Arc<Namespace>) -> Vec<ObjectRef<Ingress>> {
+    move |namespace| {
+        store
+            .state()
+            .into_iter()
+            .filter(|ingress| ingress.namespace() == namespace.metadata.name)
+            .map(|ingress| ObjectRef::from_obj(&*ingress))
+            .collect()
+    }
+}
+
+/// Maps a zone to the ingresses referencing it through their [`ZONE_REF_ANNOTATION`],
+/// or which have a host at its apex, since these depend on its fqdn.
+fn with_zone<Z: ZoneExt>(store: Store<Ingress>) -> impl Fn(Arc<Z>) -> Vec<ObjectRef<Ingress>> {
+    move |zone| {
+        let zone_ref = zone.zone_ref();
+
+        store
+            .state()
+            .into_iter()
+            .filter(|ingress| {
+                annotated_zone_ref(ingress).as_ref() == Some(&zone_ref)
+                    || zone.fqdn().is_some_and(|origin| {
+                        ingress
+                            .spec
+                            .iter()
+                            .flat_map(|spec| spec.rules.iter().flatten())
+                            .filter_map(|rule| rule.host.as_deref())
+                            .any(|host| fully_qualified(host).is_ok_and(|host| &host == origin))
+                    })
+            })
+            .map(|ingress| ObjectRef::from_obj(&*ingress))
+            .collect()
+    }
+}
+
+fn comma_separated(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
 }
 
 #[tracing::instrument(name = "ingress", skip_all)]
 async fn reconcile_ingresses(
     ingress: Arc<Ingress>,
     ctx: Arc<IngressControllerContext>,
+    recorder: Recorder,
 ) -> Result<Action, kube::Error> {
-    let Some(spec) = ingress.spec.as_ref() else {
-        debug!("ingress has no spec (???), requeueing.");
+    let Some(DesiredRecords {
+        records: desired,
+        complete,
+    }) = desired_records(&ingress, &ctx, &recorder).await?
+    else {
         return Ok(Action::requeue(ctx.requeue_time));
     };
 
-    let Some(rules) = spec.rules.as_ref() else {
-        debug!("ingress contains no rules, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
-    };
+    let records =
+        Api::<Record>::namespaced(ctx.client.clone(), ingress.namespace().as_ref().unwrap());
+    for record in desired.iter() {
+        info!(
+            "creating record {:?}: {} {} {}",
+            record.metadata.name, record.spec.domain_name, record.spec.type_, record.spec.rdata
+        );
+        records
+            .patch(
+                record.metadata.name.as_deref().unwrap(),
+                &PatchParams::apply(CONTROLLER_NAME),
+                &kube::api::Patch::Apply(record),
+            )
+            .await?;
+    }
 
-    let Some(status) = ingress.status.as_ref() else {
-        debug!("ingress contains no status, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
+    // Records missing from an incomplete set might still be desired,
+    // so they are left alone until the set can be fully determined.
+    if !complete {
+        warn!(
+            "records of ingress could not be fully determined, skipping deletion of stale records."
+        );
+        return Ok(Action::requeue(INCOMPLETE_RETRY_TIME));
+    }
+
+    // Delete any records previously created for this ingress, which are no longer
+    // part of the desired set, either because a host was removed from the ingress,
+    // or because the address of the load balancer changed.
+    //
+    // Owned records are looked up in the cache, which might not have observed
+    // a deletion yet, so records which are already gone are skipped.
+    let owned = ingress
+        .uid()
+        .map(|uid| ctx.cache.owned_records(&uid))
+        .unwrap_or_default();
+
+    for stale in owned.into_iter().filter(|record| {
+        !desired
+            .iter()
+            .any(|desired| desired.metadata.name == record.metadata.name)
+    }) {
+        info!(
+            "deleting stale record {stale}: {} {} {}",
+            stale.spec.domain_name, stale.spec.type_, stale.spec.rdata
+        );
+        match records
+            .delete(&stale.name_any(), &DeleteParams::default())
+            .await
+        {
+            Err(kube::Error::Api(error)) if error.code == 404 => {}
+            result => {
+                result?;
+            }
+        }
+    }
+
+    Ok(Action::requeue(ctx.requeue_time))
+}
+
+/// Whether `hostname` is the apex of a zone or cluster zone, where CNAMEs are not allowed.
+fn is_zone_apex(cache: &Cache, hostname: &FullyQualifiedDomainName) -> bool {
+    !cache.zones_at(hostname).is_empty()
+}
+
+/// Time after which ingresses whose records could not be fully determined are retried.
+const INCOMPLETE_RETRY_TIME: Duration = Duration::from_secs(60);
+
+/// Records which should exist for an ingress.
+struct DesiredRecords {
+    records: Vec<Record>,
+    /// Whether `records` is the full set. Otherwise, records which could not be
+    /// determined are missing, for example because a hostname failed to resolve.
+    complete: bool,
+}
+
+impl DesiredRecords {
+    fn none() -> Option<Self> {
+        Some(DesiredRecords {
+            records: Vec::new(),
+            complete: true,
+        })
+    }
+}
+
+/// Compute the full set of records which should exist for the given ingress.
+///
+/// Ingresses without rules or load balancer status produce no records at all.
+/// Returns `None` if the desired set cannot be determined yet, in which case
+/// existing records should be left alone.
+async fn desired_records(
+    ingress: &Ingress,
+    ctx: &IngressControllerContext,
+    recorder: &Recorder,
+) -> Result<Option<DesiredRecords>, kube::Error> {
+    // Records of ingresses which are not selected are left alone, since they
+    // might be managed by another instance of the controller.
+    if !is_selected(ingress, ctx) {
+        return Ok(None);
+    }
+
+    let annotations = IngressAnnotations::from(ingress);
+    report_invalid_annotations(ingress, &annotations.invalid, recorder).await;
+
+    if !annotations.enabled.unwrap_or(!ctx.opt_in) {
+        debug!("record creation is not enabled for ingress.");
+        return Ok(DesiredRecords::none());
+    }
+
+    let Some(spec) = ingress.spec.as_ref() else {
+        debug!("ingress has no spec (???)");
+        return Ok(DesiredRecords::none());
     };
 
-    let Some(lb) = status.load_balancer.as_ref() else {
-        debug!("ingress status contains no loadBalancer segment, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
+    let Some(rules) = spec.rules.as_ref() else {
+        debug!("ingress contains no rules.");
+        return Ok(DesiredRecords::none());
     };
 
-    let Some(ingresses) = lb.ingress.as_ref() else {
-        debug!("ingress status load balancer contains no ingresses, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
+    let (addresses, load_balancer_hostnames) = if let Some(targets) = annotations.targets {
+        (targets.addresses, targets.hostnames)
+    } else {
+        let Some(status) = ingress.status.as_ref() else {
+            debug!("ingress contains no status.");
+            return Ok(DesiredRecords::none());
+        };
+
+        let Some(lb) = status.load_balancer.as_ref() else {
+            debug!("ingress status contains no loadBalancer segment.");
+            return Ok(DesiredRecords::none());
+        };
+
+        let Some(ingresses) = lb.ingress.as_ref() else {
+            debug!("ingress status load balancer contains no ingresses.");
+            return Ok(DesiredRecords::none());
+        };
+
+        let addresses: Vec<IpAddr> = ingresses
+            .iter()
+            .filter_map(|ingress| ingress.ip.as_ref())
+            .filter_map(|address| IpAddr::from_str(address).ok())
+            .collect();
+
+        // Some cloud providers (AWS, for example) only report a hostname for their
+        // load balancers, in which case we have to fall back to CNAME records.
+        let load_balancer_hostnames: Vec<FullyQualifiedDomainName> = ingresses
+            .iter()
+            .filter(|ingress| ingress.ip.is_none())
+            .filter_map(|ingress| ingress.hostname.as_deref())
+            .filter_map(|hostname| {
+                fully_qualified(hostname)
+                    .inspect_err(|err| {
+                        warn!("load balancer hostname {hostname:?} is invalid: {err}")
+                    })
+                    .ok()
+            })
+            .collect();
+
+        (addresses, load_balancer_hostnames)
     };
 
-    let (ipv4_addresses, ipv6_addresses): (Vec<IpAddr>, Vec<IpAddr>) = ingresses
-        .iter()
-        .filter_map(|ingress| ingress.ip.as_ref())
-        .filter_map(|address| IpAddr::from_str(&address).ok())
-        .partition(IpAddr::is_ipv4);
+    // Resolve the annotated zone reference up front, since the records'
+    // domain names must be made relative to the zone's fqdn.
+    let zone = if let Some(zone_ref) = &annotations.zone_ref {
+        let Some(zone) = ctx.cache.zone(zone_ref, ingress.namespace().as_deref()) else {
+            warn!("ingress {ZONE_REF_ANNOTATION} references unknown zone {zone_ref}");
+            return Ok(None);
+        };
+
+        let Some(zone_fqdn) = zone.fqdn().cloned() else {
+            info!("ingress zone {zone} missing fqdn, postponing record creation.");
+            return Ok(None);
+        };
 
-    let hostnames: Vec<_> = rules
+        Some((zone_ref, zone_fqdn))
+    } else {
+        None
+    };
+
+    let hostnames: Vec<FullyQualifiedDomainName> = rules
         .iter()
         .filter_map(|rule| rule.host.as_deref())
         .map(DomainName::try_from)
         .filter_map(Result::ok)
+        .map(|hostname| hostname.to_fully_qualified())
+        .filter(|hostname| {
+            if annotations.exclude_hosts.contains(hostname) {
+                debug!("{hostname} is excluded by {EXCLUDE_HOSTS_ANNOTATION}");
+                false
+            } else {
+                true
+            }
+        })
         .collect();
 
-    let records =
-        Api::<Record>::namespaced(ctx.client.clone(), ingress.namespace().as_ref().unwrap());
-    for hostname in hostnames.iter() {
-        let metadata = |addr: &IpAddr| ObjectMeta {
-            name: Some(format!(
-                "{}-{}-{}",
+    // CNAMEs are only used when no addresses are available, since
+    // CNAME records must be the only record for a given name.
+    let cname_target = if addresses.is_empty() {
+        if load_balancer_hostnames.len() > 1 {
+            warn!(
+                "ingress {} has multiple target hostnames, only the first one ({}) will be used.",
                 ingress.name_any(),
-                hostname.to_string().replace(".", "-"),
-                addr.to_canonical()
-                    .to_string()
-                    .replace(".", "-")
-                    .replace(":", "-")
-            )),
-            owner_references: Some(vec![ingress.owner_ref(&()).unwrap()]),
-            ..Default::default()
+                load_balancer_hostnames[0]
+            );
+        }
+
+        load_balancer_hostnames.first()
+    } else {
+        if !load_balancer_hostnames.is_empty() {
+            warn!(
+                "ingress {} has both target addresses and hostnames, the hostnames ({}) will be ignored.",
+                ingress.name_any(),
+                load_balancer_hostnames
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        None
+    };
+
+    let mut records = Vec::new();
+    let mut complete = true;
+    for hostname in hostnames.iter() {
+        let targets = match cname_target {
+            Some(target) if is_zone_apex(&ctx.cache, hostname) => match ctx.apex_fallback {
+                ApexFallback::Skip => {
+                    warn!(
+                        "{hostname} is the apex of a zone and cannot be a CNAME for {target}, skipping."
+                    );
+                    continue;
+                }
+                ApexFallback::Resolve => match resolve(target).await {
+                    Ok(addresses) => addresses.into_iter().map(address_target).collect(),
+                    Err(err) => {
+                        warn!("failed to resolve load balancer hostname {target}: {err}");
+                        complete = false;
+                        continue;
+                    }
+                },
+            },
+            Some(target) => vec![(Type::CNAME, target.to_string())],
+            None => addresses.iter().copied().map(address_target).collect(),
         };
 
-        for ipv4 in ipv4_addresses.iter() {
-            let metadata = metadata(ipv4);
-            info!("creating record {:?}: {hostname} -> {ipv4}", metadata.name);
-            records
-                .patch(
-                    metadata.name.as_deref().unwrap(),
-                    &PatchParams::apply(CONTROLLER_NAME),
-                    &kube::api::Patch::Apply(Record {
-                        metadata: metadata.clone(),
-                        spec: RecordSpec {
-                            domain_name: hostname.to_fully_qualified().into(),
-                            zone_ref: None,
-                            type_: Type::A,
-                            class: Class::IN,
-                            ttl: None,
-                            rdata: ipv4.to_string(),
-                        },
-                        status: None,
-                    }),
-                )
-                .await?;
+        let place = |fqdn: &FullyQualifiedDomainName| {
+            place(
+                fqdn,
+                zone.as_ref()
+                    .map(|(zone_ref, zone_fqdn)| (*zone_ref, zone_fqdn)),
+            )
+        };
+
+        let Some((domain_name, zone_ref)) = place(hostname) else {
+            continue;
+        };
+
+        // Track ownership of each distinct name/type pair in the TXT registry.
+        if let Some(cluster_id) = &ctx.registry_cluster_id {
+            let mut types: Vec<Type> = targets.iter().map(|(type_, _)| *type_).collect();
+            types.sort();
+            types.dedup();
+
+            for type_ in types {
+                let registry_fqdn = Ownership::registry_fqdn(hostname, type_);
+                let Some((domain_name, zone_ref)) = place(&registry_fqdn) else {
+                    continue;
+                };
+
+                let ownership = Ownership {
+                    cluster_id: cluster_id.clone(),
+                    kind: String::from("ingress"),
+                    namespace: ingress.namespace().unwrap_or_default(),
+                    name: ingress.name_any(),
+                };
+
+                records.push(Record {
+                    metadata: ingress_record_metadata(ingress, hostname, &format!("{type_}-owner")),
+                    spec: RecordSpec {
+                        domain_name,
+                        zone_ref,
+                        type_: Type::TXT,
+                        class: Class::IN,
+                        ttl: annotations.ttl,
+                        rdata: ownership.to_rdata(),
+                    },
+                    status: None,
+                });
+            }
+        }
+
+        records.extend(targets.into_iter().map(|(type_, rdata)| Record {
+            metadata: ingress_record_metadata(ingress, hostname, &rdata),
+            spec: RecordSpec {
+                domain_name: domain_name.clone(),
+                zone_ref: zone_ref.clone(),
+                type_,
+                class: Class::IN,
+                ttl: annotations.ttl,
+                rdata,
+            },
+            status: None,
+        }));
+    }
+
+    Ok(Some(DesiredRecords { records, complete }))
+}
+
+/// Domain name and zone reference of a record for `fqdn`, placed in `zone`, if any.
+///
+/// Records placed in a specific zone use a domain name relative to it. Names at
+/// the apex of the zone cannot be expressed as a relative name, and therefore
+/// keep their fully qualified domain name. Names outside of the zone are skipped.
+fn place(
+    fqdn: &FullyQualifiedDomainName,
+    zone: Option<(&ZoneRef, &FullyQualifiedDomainName)>,
+) -> Option<(DomainName, Option<ZoneRef>)> {
+    match zone {
+        Some((zone_ref, zone_fqdn)) if fqdn.is_subdomain_of(zone_fqdn) => Some((
+            DomainName::from((fqdn - zone_fqdn).unwrap()),
+            Some(zone_ref.clone()),
+        )),
+        Some((zone_ref, zone_fqdn)) if fqdn != zone_fqdn => {
+            warn!("{fqdn} is not within zone {zone_ref} ({zone_fqdn}), skipping.");
+            None
         }
+        _ => Some((DomainName::from(fqdn.clone()), None)),
+    }
+}
 
-        for ipv6 in ipv6_addresses.iter() {
-            let metadata = metadata(ipv6);
-            info!("creating record {:?}: {hostname} -> {ipv6}", metadata.name);
-            records
-                .patch(
-                    metadata.name.as_deref().unwrap(),
-                    &PatchParams::apply(CONTROLLER_NAME),
-                    &kube::api::Patch::Apply(Record {
-                        metadata: metadata.clone(),
-                        spec: RecordSpec {
-                            domain_name: hostname.to_fully_qualified().into(),
-                            zone_ref: None,
-                            type_: Type::AAAA,
-                            class: Class::IN,
-                            ttl: None,
-                            rdata: ipv6.to_string(),
-                        },
-                        status: None,
-                    }),
-                )
-                .await?;
+/// Log the invalid annotation values of `ingress`, and report them as warning events on it.
+async fn report_invalid_annotations(ingress: &Ingress, invalid: &[String], recorder: &Recorder) {
+    for note in invalid {
+        warn!("ingress {note}");
+
+        let event = Event {
+            type_: EventType::Warning,
+            reason: String::from("InvalidAnnotation"),
+            note: Some(note.clone()),
+            action: String::from("CreateRecords"),
+            secondary: None,
+        };
+
+        if let Err(err) = recorder.publish(&event, &ingress.object_ref(&())).await {
+            warn!("failed to publish event for ingress: {err}");
         }
     }
+}
 
-    Ok(Action::requeue(ctx.requeue_time))
+/// Parse a hostname as fully qualified, whether or not it has a trailing dot.
+fn fully_qualified(
+    hostname: &str,
+) -> Result<FullyQualifiedDomainName, kubizone_common::error::FullyQualifiedDomainNameError> {
+    FullyQualifiedDomainName::try_from(format!("{}.", hostname.trim_end_matches('.')))
+}
+
+/// Map an address to its corresponding record type and data.
+fn address_target(address: IpAddr) -> (Type, String) {
+    if address.is_ipv4() {
+        (Type::A, address.to_string())
+    } else {
+        (Type::AAAA, address.to_string())
+    }
+}
+
+/// Look up the addresses of the given load balancer hostname.
+async fn resolve(hostname: &FullyQualifiedDomainName) -> Result<Vec<IpAddr>, std::io::Error> {
+    Ok(tokio::net::lookup_host((hostname.to_string(), 0))
+        .await?
+        .map(|address| address.ip())
+        .collect())
+}
+
+/// Construct the metadata of a Record owned by `ingress`, pointing `hostname` at `rdata`.
+fn ingress_record_metadata(
+    ingress: &Ingress,
+    hostname: &FullyQualifiedDomainName,
+    rdata: &str,
+) -> ObjectMeta {
+    let target = match IpAddr::from_str(rdata) {
+        Ok(address) => address.to_canonical().to_string(),
+        Err(_) => rdata.trim_end_matches('.').to_lowercase(),
+    };
+
+    ObjectMeta {
+        name: Some(format!(
+            "{}-{}-{}",
+            ingress.name_any(),
+            hostname.to_string().trim_end_matches('.').replace(".", "-"),
+            target.replace(".", "-").replace(":", "-")
+        )),
+        owner_references: Some(vec![ingress.owner_ref(&()).unwrap()]),
+        ..Default::default()
+    }
 }
 
 fn ingress_error_policy(
     ingress: Arc<Ingress>,
     error: &kube::Error,
-    _ctx: Arc<IngressControllerContext>,
+    ctx: Arc<IngressControllerContext>,
 ) -> Action {
     error!(
         "ingress {} reconciliation encountered error: {error}",
         ingress.name_any()
     );
+    ctx.metrics.requeued("ingress");
     Action::requeue(Duration::from_secs(60))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, net::IpAddr};
+
+    use k8s_openapi::api::networking::v1::Ingress;
+    use kube::{
+        api::ObjectMeta,
+        core::{Expression, Selector},
+    };
+    use kubizone_common::{DomainName, FullyQualifiedDomainName};
+    use kubizone_crds::v1alpha1::{
+        ClusterZone, ClusterZoneSpec, Zone, ZoneKind, ZoneRef, ZoneSpec, ZoneStatus,
+    };
+
+    use super::{
+        IngressAnnotations, TARGETS_ANNOTATION, TTL_ANNOTATION, Targets, ZONE_REF_ANNOTATION,
+        is_zone_apex, parse_label_selector, place,
+    };
+    use crate::cache::Cache;
+
+    fn ingress(annotations: &[(&str, &str)]) -> Ingress {
+        Ingress {
+            metadata: ObjectMeta {
+                name: Some(String::from("example")),
+                namespace: Some(String::from("default")),
+                annotations: Some(BTreeMap::from_iter(
+                    annotations
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string())),
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn fqdn(fqdn: &str) -> FullyQualifiedDomainName {
+        FullyQualifiedDomainName::try_from(fqdn).unwrap()
+    }
+
+    #[test]
+    fn ttl_annotation() {
+        let annotations = IngressAnnotations::from(&ingress(&[(TTL_ANNOTATION, " 300 ")]));
+        assert_eq!(annotations.ttl, Some(300));
+        assert!(annotations.invalid.is_empty());
+
+        let annotations = IngressAnnotations::from(&ingress(&[(TTL_ANNOTATION, "-1")]));
+        assert_eq!(annotations.ttl, None);
+        assert_eq!(annotations.invalid.len(), 1);
+    }
+
+    #[test]
+    fn zone_ref_annotation() {
+        let zone_ref = |value| {
+            IngressAnnotations::from(&ingress(&[(ZONE_REF_ANNOTATION, value)]))
+                .zone_ref
+                .unwrap()
+        };
+
+        assert_eq!(
+            zone_ref("example-org"),
+            ZoneRef {
+                kind: ZoneKind::Zone,
+                name: String::from("example-org"),
+                namespace: Some(String::from("default")),
+            }
+        );
+
+        assert_eq!(
+            zone_ref("dns/example-org"),
+            ZoneRef {
+                kind: ZoneKind::Zone,
+                name: String::from("example-org"),
+                namespace: Some(String::from("dns")),
+            }
+        );
+
+        assert_eq!(
+            zone_ref("ClusterZone/example-org"),
+            ZoneRef {
+                kind: ZoneKind::ClusterZone,
+                name: String::from("example-org"),
+                namespace: None,
+            }
+        );
+    }
+
+    #[test]
+    fn targets_annotation() {
+        let annotations = IngressAnnotations::from(&ingress(&[(
+            TARGETS_ANNOTATION,
+            "127.0.0.1, lb.example.org, ::1, -invalid-",
+        )]));
+
+        assert_eq!(
+            annotations.targets,
+            Some(Targets {
+                addresses: vec![
+                    "127.0.0.1".parse::<IpAddr>().unwrap(),
+                    "::1".parse::<IpAddr>().unwrap()
+                ],
+                hostnames: vec![fqdn("lb.example.org.")],
+            })
+        );
+        assert_eq!(annotations.invalid.len(), 1);
+    }
+
+    #[test]
+    fn placement_at_zone_apex() {
+        let zone_ref = ZoneRef {
+            kind: ZoneKind::Zone,
+            name: String::from("example-org"),
+            namespace: Some(String::from("default")),
+        };
+        let origin = fqdn("example.org.");
+        let zone = Some((&zone_ref, &origin));
+
+        // Names within the zone are relative to it.
+        assert_eq!(
+            place(&fqdn("www.example.org."), zone),
+            Some((DomainName::try_from("www").unwrap(), Some(zone_ref.clone())))
+        );
+
+        // Names at the apex keep their fully qualified domain name.
+        assert_eq!(
+            place(&origin, zone),
+            Some((DomainName::from(origin.clone()), None))
+        );
+
+        // Names outside of the zone are skipped.
+        assert_eq!(place(&fqdn("www.example.com."), zone), None);
+
+        assert_eq!(
+            place(&fqdn("www.example.com."), None),
+            Some((DomainName::from(fqdn("www.example.com.")), None))
+        );
+    }
+
+    #[test]
+    fn zone_apex() {
+        let metadata = |name: &str, namespace: Option<&str>| ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: namespace.map(str::to_string),
+            ..Default::default()
+        };
+
+        let status = |fqdn: &str| {
+            Some(ZoneStatus {
+                fqdn: Some(self::fqdn(fqdn)),
+                ..Default::default()
+            })
+        };
+
+        let zone = Zone {
+            metadata: metadata("example-org", Some("default")),
+            spec: ZoneSpec {
+                domain_name: DomainName::try_from("example.org.").unwrap(),
+                ..Default::default()
+            },
+            status: status("example.org."),
+        };
+
+        let cluster_zone = ClusterZone {
+            metadata: metadata("example-com", None),
+            spec: ClusterZoneSpec(ZoneSpec {
+                domain_name: DomainName::try_from("example.com.").unwrap(),
+                ..Default::default()
+            }),
+            status: status("example.com."),
+        };
+
+        let cache = Cache::with_zones(vec![zone], vec![cluster_zone]);
+
+        assert!(is_zone_apex(&cache, &fqdn("example.org.")));
+        assert!(is_zone_apex(&cache, &fqdn("example.com.")));
+        assert!(!is_zone_apex(&cache, &fqdn("www.example.org.")));
+        assert!(!is_zone_apex(&cache, &fqdn("www.example.com.")));
+    }
+
+    #[test]
+    fn label_selector() {
+        assert_eq!(
+            parse_label_selector("dns=public, tier==frontend,environment in (production, staging),team notin (qa),!internal,managed,zone!=private").unwrap(),
+            Selector::from_iter([
+                Expression::Equal(String::from("dns"), String::from("public")),
+                Expression::Equal(String::from("tier"), String::from("frontend")),
+                Expression::In(
+                    String::from("environment"),
+                    [String::from("production"), String::from("staging")].into(),
+                ),
+                Expression::NotIn(String::from("team"), [String::from("qa")].into()),
+                Expression::DoesNotExist(String::from("internal")),
+                Expression::Exists(String::from("managed")),
+                Expression::NotEqual(String::from("zone"), String::from("private")),
+            ])
+        );
+
+        assert_eq!(parse_label_selector("").unwrap(), Selector::default());
+        assert!(parse_label_selector("environment in (production").is_err());
+        assert!(parse_label_selector("environment within (production)").is_err());
+        assert!(parse_label_selector("two words").is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use k8s_openapi::{
+    api::coordination::v1::Lease,
+    apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta},
+    chrono::Utc,
+};
+use kube::{Api, Client, api::PostParams};
+use tokio::time::sleep;
+use tracing::*;
+
+use crate::health::Health;
+
+/// Default name of the Lease used for electing a leader.
+#[cfg(feature = "dev")]
+pub const LEASE_NAME: &str = "dev.kubi.zone";
+#[cfg(not(feature = "dev"))]
+pub const LEASE_NAME: &str = "kubi.zone";
+
+/// Lease-based leader election, ensuring only a single replica of the
+/// controllers is running at any one time.
+///
+/// The leader renews the lease every third of its duration. Standbys
+/// periodically attempt to acquire it, which only succeeds once the lease
+/// has expired, or was released by the previous leader.
+///
+/// Clocks of replicas may be skewed, so the `renewTime` of a lease held by
+/// another replica is never compared to the local time. Instead, the lease
+/// expires once it has not changed for its duration, as measured locally.
+pub struct LeaderElection {
+    leases: Api<Lease>,
+    name: String,
+    identity: String,
+    lease_duration: Duration,
+    observed: Mutex<Option<Observation>>,
+}
+
+/// Renewal of a lease held by another replica, and when it was first observed locally.
+#[derive(Debug)]
+struct Observation {
+    renewal: Renewal,
+    at: Instant,
+}
+
+/// Holder, renew time and resource version of a lease, any of which
+/// change whenever its holder renews it.
+#[derive(Debug, PartialEq)]
+struct Renewal {
+    holder_identity: Option<String>,
+    renew_time: Option<MicroTime>,
+    resource_version: Option<String>,
+}
+
+impl Renewal {
+    fn of(lease: &Lease) -> Self {
+        let spec = lease.spec.as_ref();
+
+        Renewal {
+            holder_identity: spec.and_then(|spec| spec.holder_identity.clone()),
+            renew_time: spec.and_then(|spec| spec.renew_time.clone()),
+            resource_version: lease.metadata.resource_version.clone(),
+        }
+    }
+}
+
+impl LeaderElection {
+    pub fn new(
+        client: Client,
+        namespace: &str,
+        name: String,
+        identity: String,
+        lease_duration: Duration,
+    ) -> Self {
+        LeaderElection {
+            leases: Api::namespaced(client, namespace),
+            name,
+            identity,
+            lease_duration,
+            observed: Mutex::new(None),
+        }
+    }
+
+    fn retry_period(&self) -> Duration {
+        self.lease_duration / 3
+    }
+
+    /// Wait until this replica holds the lease.
+    ///
+    /// The replica is reported as ready while on standby, since the controllers are
+    /// only started, and register with `health`, once the lease has been acquired.
+    pub async fn acquire(&self, health: &Health) {
+        health.ready("leader election");
+
+        info!(
+            "waiting to acquire lease {} as {}",
+            self.name, self.identity
+        );
+
+        loop {
+            match self.try_acquire_or_renew().await {
+                Ok(true) => {
+                    info!("acquired lease {}, now leading", self.name);
+                    return;
+                }
+                Ok(false) => debug!("lease {} is held by another replica", self.name),
+                Err(error) => warn!("failed to acquire lease {}: {error}", self.name),
+            }
+
+            sleep(self.retry_period()).await;
+        }
+    }
+
+    /// Keep renewing the lease, returning once leadership has been lost.
+    ///
+    /// Leadership is considered lost if another replica has taken over the
+    /// lease, or if the lease could not be renewed for two thirds of its
+    /// duration, leaving standbys time to take over before it expires.
+    pub async fn hold(&self) {
+        let mut last_renewal = Instant::now();
+
+        loop {
+            sleep(self.retry_period()).await;
+
+            match self.try_acquire_or_renew().await {
+                Ok(true) => last_renewal = Instant::now(),
+                Ok(false) => {
+                    error!("lease {} was taken over by another replica", self.name);
+                    return;
+                }
+                Err(error) => {
+                    warn!("failed to renew lease {}: {error}", self.name);
+
+                    if last_renewal.elapsed() >= self.lease_duration * 2 / 3 {
+                        error!("unable to renew lease {} in time", self.name);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Release the lease if held by this replica, so a standby can take over immediately.
+    pub async fn release(&self) {
+        let Ok(Some(mut lease)) = self.leases.get_opt(&self.name).await else {
+            return;
+        };
+
+        let Some(spec) = lease.spec.as_mut() else {
+            return;
+        };
+
+        if spec.holder_identity.as_ref() != Some(&self.identity) {
+            return;
+        }
+
+        spec.holder_identity = None;
+        spec.acquire_time = None;
+        spec.renew_time = None;
+
+        match self
+            .leases
+            .replace(&self.name, &PostParams::default(), &lease)
+            .await
+        {
+            Ok(_) => info!("released lease {}", self.name),
+            Err(error) => warn!("failed to release lease {}: {error}", self.name),
+        }
+    }
+
+    /// Attempt to acquire the lease, or renew it if already held.
+    ///
+    /// Returns whether this replica holds the lease afterwards.
+    async fn try_acquire_or_renew(&self) -> Result<bool, kube::Error> {
+        let Some(mut lease) = self.leases.get_opt(&self.name).await? else {
+            let mut lease = Lease {
+                metadata: ObjectMeta {
+                    name: Some(self.name.clone()),
+                    ..Default::default()
+                },
+                spec: None,
+            };
+
+            self.claim(&mut lease, Instant::now());
+
+            return match self.leases.create(&PostParams::default(), &lease).await {
+                Ok(_) => Ok(true),
+                // Another replica created the lease first.
+                Err(kube::Error::Api(error)) if error.code == 409 => Ok(false),
+                Err(error) => Err(error),
+            };
+        };
+
+        if !self.claim(&mut lease, Instant::now()) {
+            return Ok(false);
+        }
+
+        // The replaced lease carries the resource version it was read at, so if
+        // another replica modified it in the meantime, the replacement is rejected.
+        match self
+            .leases
+            .replace(&self.name, &PostParams::default(), &lease)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(kube::Error::Api(error)) if error.code == 409 => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Update `lease`, as read at the local time `now`, to be held by this replica,
+    /// unless it is held by another replica and has not expired yet.
+    ///
+    /// Returns whether the lease was updated, and should be written back.
+    fn claim(&self, lease: &mut Lease, now: Instant) -> bool {
+        let renewal = Renewal::of(lease);
+        let spec = lease.spec.get_or_insert_with(Default::default);
+        let lease_duration_seconds = self.lease_duration.as_secs() as i32;
+
+        if spec.holder_identity.as_ref() != Some(&self.identity) {
+            let mut observed = self.observed.lock().unwrap();
+
+            // Any change to the lease means it has been renewed (or released)
+            // since last observed, so it is valid for its duration from now on.
+            if observed
+                .as_ref()
+                .is_none_or(|observed| observed.renewal != renewal)
+            {
+                *observed = Some(Observation { renewal, at: now });
+            }
+
+            let duration = spec
+                .lease_duration_seconds
+                .map(|seconds| Duration::from_secs(seconds.max(0) as u64))
+                .unwrap_or(self.lease_duration);
+
+            // Unwrap safety: Set above.
+            let unchanged_for = now.duration_since(observed.as_ref().unwrap().at);
+
+            // Released, or never held, leases can be taken over right away.
+            if spec.holder_identity.is_some() && unchanged_for < duration {
+                return false;
+            }
+
+            if let Some(holder_identity) = &spec.holder_identity {
+                info!(
+                    "lease {} held by {holder_identity} has expired, taking over",
+                    self.name
+                );
+            }
+
+            spec.holder_identity = Some(self.identity.clone());
+            spec.acquire_time = Some(MicroTime(Utc::now()));
+            // New leases start out without any transitions.
+            spec.lease_transitions = Some(
+                spec.lease_transitions
+                    .map_or(0, |transitions| transitions + 1),
+            );
+            *observed = None;
+        }
+
+        spec.lease_duration_seconds = Some(lease_duration_seconds);
+        spec.renew_time = Some(MicroTime(Utc::now()));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+    use kube::{Client, api::ObjectMeta};
+
+    use super::{Health, LeaderElection};
+
+    fn election(identity: &str) -> LeaderElection {
+        // The client is never used, since only claims are tested.
+        let config = kube::Config::new("http://localhost:0".parse().unwrap());
+        let client = Client::try_from(config).unwrap();
+
+        LeaderElection::new(
+            client,
+            "kubizone",
+            String::from("kubi.zone"),
+            identity.to_string(),
+            Duration::from_secs(15),
+        )
+    }
+
+    fn lease(holder_identity: Option<&str>, resource_version: &str) -> Lease {
+        Lease {
+            metadata: ObjectMeta {
+                name: Some(String::from("kubi.zone")),
+                resource_version: Some(resource_version.to_string()),
+                ..Default::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: holder_identity.map(str::to_string),
+                lease_duration_seconds: Some(15),
+                lease_transitions: Some(3),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn holder(lease: &Lease) -> Option<&str> {
+        lease.spec.as_ref()?.holder_identity.as_deref()
+    }
+
+    #[tokio::test]
+    async fn acquire() {
+        let election = election("a");
+
+        // Released leases can be acquired right away.
+        let mut released = lease(None, "1");
+        assert!(election.claim(&mut released, Instant::now()));
+        assert_eq!(holder(&released), Some("a"));
+        assert_eq!(released.spec.as_ref().unwrap().lease_transitions, Some(4));
+
+        // Leases held by other replicas cannot, however old their renewTime.
+        let mut held = lease(Some("b"), "1");
+        assert!(!election.claim(&mut held, Instant::now()));
+        assert_eq!(holder(&held), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn renew() {
+        let leader = election("a");
+        let start = Instant::now();
+
+        let mut held = lease(Some("a"), "1");
+        assert!(leader.claim(&mut held, start));
+        assert_eq!(holder(&held), Some("a"));
+        assert_eq!(held.spec.as_ref().unwrap().lease_transitions, Some(3));
+        assert!(held.spec.as_ref().unwrap().renew_time.is_some());
+
+        // Other replicas see each renewal as a change, which restarts the lease's duration.
+        let standby = election("b");
+        let mut observed = lease(Some("a"), "1");
+        assert!(!standby.claim(&mut observed, start));
+
+        let mut renewed = lease(Some("a"), "2");
+        assert!(!standby.claim(&mut renewed, start + Duration::from_secs(10)));
+
+        let mut renewed = lease(Some("a"), "2");
+        assert!(!standby.claim(&mut renewed, start + Duration::from_secs(20)));
+    }
+
+    #[tokio::test]
+    async fn expire() {
+        let standby = election("b");
+        let start = Instant::now();
+
+        let mut held = lease(Some("a"), "1");
+        assert!(!standby.claim(&mut held, start));
+
+        let mut held = lease(Some("a"), "1");
+        assert!(!standby.claim(&mut held, start + Duration::from_secs(14)));
+
+        // Unchanged for the lease's duration, as measured locally.
+        let mut expired = lease(Some("a"), "1");
+        assert!(standby.claim(&mut expired, start + Duration::from_secs(15)));
+        assert_eq!(holder(&expired), Some("b"));
+        assert_eq!(expired.spec.as_ref().unwrap().lease_transitions, Some(4));
+    }
+
+    #[tokio::test]
+    async fn standby() {
+        let health = Health::default();
+        let standby = election("b");
+
+        // The lease is never acquired, since there is no API server to acquire it from.
+        let acquired =
+            tokio::time::timeout(Duration::from_millis(100), standby.acquire(&health)).await;
+        assert!(acquired.is_err());
+
+        // Replicas on standby are ready to take over, without any controllers running.
+        assert!(health.is_healthy());
+        assert!(health.is_ready());
+
+        // Once leading, readiness depends on the controllers.
+        health.register("zone");
+        assert!(!health.is_ready());
+    }
+}
@@ -1,27 +1,48 @@
+pub mod cache;
+pub mod explain;
+pub mod health;
 pub mod ingress;
+pub mod leader;
+pub mod metrics;
+pub mod migrate;
 pub mod record;
+pub mod webhook;
 pub mod zone;
 
-use std::{fmt::Debug, hash::Hash, sync::Arc};
+use std::{
+    collections::{HashMap, hash_map::DefaultHasher},
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::{Stream, StreamExt};
 
-use json_patch::{PatchOperation, RemoveOperation};
 use k8s_openapi::{
     NamespaceResourceScope,
+    api::core::v1::Namespace,
+    apimachinery::pkg::apis::meta::v1::ObjectMeta,
     serde::{Serialize, de::DeserializeOwned},
     serde_json::json,
 };
 use kube::{
     Api, Client, Resource, ResourceExt,
     api::{Patch, PatchParams},
-    runtime::reflector::ObjectRef,
+    runtime::reflector::{ObjectRef, Store},
 };
-use kubizone_common::FullyQualifiedDomainName;
+use kubizone_common::{DomainName, FullyQualifiedDomainName};
 use kubizone_crds::{
-    PARENT_ZONE_LABEL,
-    v1alpha1::{DomainExt, ZoneRef},
+    PARENT_ZONE_ANNOTATION, PARENT_ZONE_LABEL,
+    v1alpha1::{
+        ClusterZone, DomainExt, Record, RecordExt as _, RecordSet, Zone, ZoneExt, ZoneKind,
+        ZoneRef, ZoneSpec, ZoneStatus,
+    },
 };
 use tracing::{debug, info};
 
+use crate::cache::{Cache, Cached};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Effect {
     None,
@@ -34,24 +55,315 @@ impl Effect {
     }
 }
 
-pub fn with_parent_zone<Parent, K>() -> impl Fn(K) -> Option<ObjectRef<Parent>>
+/// Where a zone, record or record set claims to belong: either within the
+/// zone referenced by its zoneRef, or wherever its domain name fits.
+pub trait Placement: DomainExt {
+    fn placement(&self) -> (Option<&ZoneRef>, &DomainName);
+}
+
+impl Placement for Zone {
+    fn placement(&self) -> (Option<&ZoneRef>, &DomainName) {
+        (self.spec.zone_ref.as_ref(), &self.spec.domain_name)
+    }
+}
+
+impl Placement for ClusterZone {
+    fn placement(&self) -> (Option<&ZoneRef>, &DomainName) {
+        (self.spec.zone_ref.as_ref(), &self.spec.domain_name)
+    }
+}
+
+impl Placement for Record {
+    fn placement(&self) -> (Option<&ZoneRef>, &DomainName) {
+        (self.zone_ref(), self.domain_name())
+    }
+}
+
+impl Placement for RecordSet {
+    fn placement(&self) -> (Option<&ZoneRef>, &DomainName) {
+        (self.zone_ref(), self.domain_name())
+    }
+}
+
+/// Api through which a resource is modified, which is scoped
+/// to the resource's namespace, unless it is cluster-scoped.
+pub trait ResourceApi: Resource<DynamicType = ()> + Sized {
+    fn api(client: Client, resource: &Self) -> Api<Self>;
+}
+
+fn namespaced_api<R>(client: Client, resource: &R) -> Api<R>
+where
+    R: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
+{
+    // Unwrap safety: Namespaced resources always have a namespace.
+    Api::namespaced(client, resource.namespace().as_deref().unwrap())
+}
+
+impl ResourceApi for Zone {
+    fn api(client: Client, zone: &Self) -> Api<Self> {
+        namespaced_api(client, zone)
+    }
+}
+
+impl ResourceApi for ClusterZone {
+    fn api(client: Client, _: &Self) -> Api<Self> {
+        Api::all(client)
+    }
+}
+
+impl ResourceApi for Record {
+    fn api(client: Client, record: &Self) -> Api<Self> {
+        namespaced_api(client, record)
+    }
+}
+
+impl ResourceApi for RecordSet {
+    fn api(client: Client, record_set: &Self) -> Api<Self> {
+        namespaced_api(client, record_set)
+    }
+}
+
+/// Kinds of zones managed by the zone controller, namely [`Zone`]s and [`ClusterZone`]s.
+pub trait ZoneResource:
+    ResourceApi
+    + Cached
+    + ZoneExt
+    + Placement
+    + Clone
+    + Debug
+    + DeserializeOwned
+    + Serialize
+    + Send
+    + Sync
+    + 'static
+{
+    const KIND: ZoneKind;
+
+    /// Wrap the zone, so it can be handled alongside zones of the other kind.
+    fn into_any(self: Arc<Self>) -> AnyZone;
+}
+
+impl ZoneResource for Zone {
+    const KIND: ZoneKind = ZoneKind::Zone;
+
+    fn into_any(self: Arc<Self>) -> AnyZone {
+        AnyZone::Zone(self)
+    }
+}
+
+impl ZoneResource for ClusterZone {
+    const KIND: ZoneKind = ZoneKind::ClusterZone;
+
+    fn into_any(self: Arc<Self>) -> AnyZone {
+        AnyZone::ClusterZone(self)
+    }
+}
+
+/// Either a [`Zone`] or a [`ClusterZone`], as looked up in the [`Cache`].
+#[derive(Clone, Debug)]
+pub enum AnyZone {
+    Zone(Arc<Zone>),
+    ClusterZone(Arc<ClusterZone>),
+}
+
+impl AnyZone {
+    pub fn meta(&self) -> &ObjectMeta {
+        match self {
+            AnyZone::Zone(zone) => zone.meta(),
+            AnyZone::ClusterZone(zone) => zone.meta(),
+        }
+    }
+}
+
+impl ZoneExt for AnyZone {
+    fn zone_spec(&self) -> &ZoneSpec {
+        match self {
+            AnyZone::Zone(zone) => zone.zone_spec(),
+            AnyZone::ClusterZone(zone) => zone.zone_spec(),
+        }
+    }
+
+    fn zone_status(&self) -> Option<&ZoneStatus> {
+        match self {
+            AnyZone::Zone(zone) => zone.zone_status(),
+            AnyZone::ClusterZone(zone) => zone.zone_status(),
+        }
+    }
+
+    fn zone_ref(&self) -> ZoneRef {
+        match self {
+            AnyZone::Zone(zone) => zone.zone_ref(),
+            AnyZone::ClusterZone(zone) => zone.zone_ref(),
+        }
+    }
+}
+
+impl DomainExt for AnyZone {
+    fn fqdn(&self) -> Option<&FullyQualifiedDomainName> {
+        match self {
+            AnyZone::Zone(zone) => zone.fqdn(),
+            AnyZone::ClusterZone(zone) => zone.fqdn(),
+        }
+    }
+
+    fn parent(&self) -> Option<ZoneRef> {
+        match self {
+            AnyZone::Zone(zone) => zone.parent(),
+            AnyZone::ClusterZone(zone) => zone.parent(),
+        }
+    }
+}
+
+impl Display for AnyZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnyZone::Zone(zone) => Display::fmt(zone, f),
+            AnyZone::ClusterZone(zone) => Display::fmt(zone, f),
+        }
+    }
+}
+
+/// Reference to the zone of kind `Z` referenced by `zone_ref`,
+/// if it is of that kind, and fully resolved.
+fn zone_object_ref<Z: ZoneResource>(zone_ref: &ZoneRef) -> Option<ObjectRef<Z>> {
+    match (zone_ref.kind, &zone_ref.namespace) {
+        (kind, _) if kind != Z::KIND => None,
+        (ZoneKind::Zone, Some(namespace)) => Some(ObjectRef::new(&zone_ref.name).within(namespace)),
+        (ZoneKind::Zone, None) => None,
+        (ZoneKind::ClusterZone, _) => Some(ObjectRef::new(&zone_ref.name)),
+    }
+}
+
+/// Maps a zone, record or record set to the zones which might adopt it, or
+/// have adopted it before: the zones it is, or was most recently, adopted by,
+/// the zone referenced by its zoneRef, and the most immediate parent zone of
+/// its fully qualified domain name.
+///
+/// Resources at the apex of a zone belong to that zone if `apex` is true,
+/// as is the case for records, but not for zones.
+///
+/// Only the zones of kind `Z` are produced.
+pub fn with_parent_zones<K, Z>(cache: Cache, apex: bool) -> impl Fn(Arc<K>) -> Vec<ObjectRef<Z>>
+where
+    K: Resource<DynamicType = ()> + Placement,
+    Z: ZoneResource,
+{
+    move |object| {
+        let mut zone_refs = cache.parent_zones(&*object);
+
+        match object.placement() {
+            (Some(zone_ref), _) => {
+                zone_refs.push(zone_ref.in_namespace(object.namespace().as_deref()))
+            }
+            (None, DomainName::Full(fqdn)) => {
+                zone_refs.extend(cache.parent_zone(fqdn, apex).map(|zone| zone.zone_ref()))
+            }
+            (None, DomainName::Partial(_)) => (),
+        }
+
+        zone_refs.iter().filter_map(zone_object_ref).collect()
+    }
+}
+
+/// Maps a zone to the resources in `store` which depend on it: the ones
+/// adopted by it, the ones referencing it through their zoneRef, the ones
+/// with the same fqdn, and the ones with a fully qualified domain name within
+/// it, which it might now be the most immediate parent zone of.
+///
+/// Changes to a zone's fully qualified domain name or delegations are
+/// thereby propagated to its dependents, without waiting for a resync.
+pub fn dependents_of_zone<Z, K>(store: Store<K>) -> impl Fn(Arc<Z>) -> Vec<ObjectRef<K>>
 where
-    K: ResourceExt,
-    Parent: Clone + Resource + DeserializeOwned + Debug + Send + 'static,
-    Parent::DynamicType: Default + Debug + Clone + Eq + Hash,
+    Z: ZoneExt,
+    K: Resource<DynamicType = ()> + Placement + Clone + 'static,
 {
-    |object| {
-        let parent = object.labels().get(PARENT_ZONE_LABEL)?;
+    move |zone| {
+        let zone_ref = zone.zone_ref();
 
-        let (name, namespace) = parent.split_once('.')?;
+        store
+            .state()
+            .into_iter()
+            .filter(|resource| {
+                // Zones with the same fqdn might have lost or gained the claim to it.
+                if resource.parent().as_ref() == Some(&zone_ref)
+                    || resource
+                        .fqdn()
+                        .is_some_and(|fqdn| Some(fqdn) == zone.fqdn())
+                {
+                    return true;
+                }
 
-        Some(ObjectRef::new(name).within(namespace))
+                match resource.placement() {
+                    (Some(reference), _) => {
+                        reference.in_namespace(resource.namespace().as_deref()) == zone_ref
+                    }
+                    (None, DomainName::Full(fqdn)) => zone
+                        .fqdn()
+                        .is_some_and(|origin| fqdn == origin || fqdn.is_subdomain_of(origin)),
+                    (None, DomainName::Partial(_)) => false,
+                }
+            })
+            .map(|resource| ObjectRef::from_obj(&*resource))
+            .collect()
     }
 }
 
-/// Configure the kubi.zone/parent-zone label for the resource.
+/// Filters a stream of zones down to the ones whose generation, labels, fqdn
+/// or deletion changed since they were last seen, which are the only changes
+/// affecting the resources depending on them.
 ///
-/// If `parent` is none, the label will be deleted instead.
+/// Status updates, such as the serial of a zone being bumped whenever one of
+/// its records changes, are thereby kept from triggering all of its dependents.
+pub fn zone_changes<Z>(zones: impl Stream<Item = Arc<Z>>) -> impl Stream<Item = Arc<Z>>
+where
+    Z: Resource<DynamicType = ()> + ZoneExt,
+{
+    let mut seen = HashMap::new();
+
+    zones.filter(move |zone| {
+        let mut hasher = DefaultHasher::new();
+        (
+            zone.meta().generation,
+            zone.labels(),
+            zone.fqdn(),
+            zone.meta().deletion_timestamp.is_some(),
+        )
+            .hash(&mut hasher);
+
+        let hash = hasher.finish();
+
+        let changed = seen.insert(ObjectRef::from_obj(zone.as_ref()), hash) != Some(hash);
+        futures::future::ready(changed)
+    })
+}
+
+/// Time after which resources waiting for the zone they reference to be
+/// created, or to have its fqdn set, are reconciled again.
+///
+/// They are reconciled as soon as the zone changes as well, so this only
+/// guards against the change being missed.
+pub const PENDING_ZONE_RETRY_TIME: Duration = Duration::from_secs(30);
+
+/// Maps a namespace to all resources within it, using the given store.
+pub fn within_namespace<K>(store: Store<K>) -> impl Fn(Arc<Namespace>) -> Vec<ObjectRef<K>>
+where
+    K: Resource + Clone + 'static,
+    K::DynamicType: Default + Eq + Hash + Clone,
+{
+    move |namespace| {
+        store
+            .state()
+            .into_iter()
+            .filter(|resource| resource.namespace() == namespace.metadata.name)
+            .map(|resource| ObjectRef::from_obj(&*resource))
+            .collect()
+    }
+}
+
+/// Configure the kubi.zone/parent-zone label and annotation for the resource.
+///
+/// If `parent` is none, both will be deleted instead. Resources which only have
+/// the legacy form of the label are migrated to the current one.
 pub async fn set_parent<R>(
     controller_name: &'static str,
     client: Client,
@@ -59,38 +371,44 @@ pub async fn set_parent<R>(
     parent: Option<ZoneRef>,
 ) -> Result<Effect, kube::Error>
 where
-    R: Resource + ResourceExt + Clone + Debug + DeserializeOwned + Serialize,
-    R: Resource<Scope = NamespaceResourceScope>,
-    <R as Resource>::DynamicType: Default,
+    R: ResourceApi + Clone + Debug + DeserializeOwned + Serialize,
 {
-    match (resource.labels().get(PARENT_ZONE_LABEL), parent) {
-        (None, None) => {
+    let current = (
+        resource.labels().get(PARENT_ZONE_LABEL),
+        resource.annotations().get(PARENT_ZONE_ANNOTATION),
+    );
+
+    match (current, parent) {
+        ((None, None), None) => {
             debug!("parent zone already null.");
             Ok(Effect::None)
         }
-        (Some(current), Some(desired)) if current == &desired.as_label() => {
+        ((Some(label), Some(annotation)), Some(desired))
+            if label == &desired.as_label() && annotation == &desired.as_annotation() =>
+        {
             debug!("parent zone already set to {desired}");
             Ok(Effect::None)
         }
         (_, None) => {
             info!(
                 "updating {} {}'s {PARENT_ZONE_LABEL}",
-                R::kind(&R::DynamicType::default()),
+                R::kind(&()),
                 resource.name_any()
             );
-            Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
+            R::api(client, resource)
                 .patch_metadata(
                     &resource.name_any(),
                     &PatchParams::apply(controller_name),
-                    &Patch::<R>::Json(json_patch::Patch(vec![PatchOperation::Remove(
-                        RemoveOperation {
-                            path: jsonptr::PointerBuf::from_tokens([
-                                "metadata",
-                                "labels",
-                                PARENT_ZONE_LABEL,
-                            ]),
-                        },
-                    )])),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "labels": {
+                                PARENT_ZONE_LABEL: null
+                            },
+                            "annotations": {
+                                PARENT_ZONE_ANNOTATION: null
+                            },
+                        }
+                    })),
                 )
                 .await?;
 
@@ -99,10 +417,10 @@ where
         (_, Some(desired)) => {
             info!(
                 "updating {} {}'s {PARENT_ZONE_LABEL} to {desired}",
-                R::kind(&R::DynamicType::default()),
+                R::kind(&()),
                 resource.name_any()
             );
-            Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
+            R::api(client, resource)
                 .patch_metadata(
                     &resource.name_any(),
                     &PatchParams::apply(controller_name),
@@ -111,6 +429,9 @@ where
                             "labels": {
                                 PARENT_ZONE_LABEL: desired.as_label()
                             },
+                            "annotations": {
+                                PARENT_ZONE_ANNOTATION: desired.as_annotation()
+                            },
                         }
                     })),
                 )
@@ -128,14 +449,12 @@ async fn set_fqdn<R>(
     fqdn: &FullyQualifiedDomainName,
 ) -> Result<Effect, kube::Error>
 where
-    R: Resource + DomainExt + DeserializeOwned,
-    R: Resource<Scope = NamespaceResourceScope>,
-    <R as Resource>::DynamicType: Default,
+    R: ResourceApi + DomainExt + DeserializeOwned,
 {
     if resource.fqdn() == Some(fqdn) {
         debug!(
             "not updating fqdn for {} {} {fqdn}, since it is already set.",
-            R::kind(&R::DynamicType::default()),
+            R::kind(&()),
             resource.name_any()
         );
 
@@ -144,11 +463,11 @@ where
 
     info!(
         "updating fqdn for {} {} to {}",
-        R::kind(&R::DynamicType::default()),
+        R::kind(&()),
         resource.name_any(),
         fqdn
     );
-    Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
+    R::api(client, resource)
         .patch_status(
             &resource.name_any(),
             &PatchParams::apply(controller_name),
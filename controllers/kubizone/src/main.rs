@@ -1,11 +1,23 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 
-use clap::{Parser, Subcommand, command};
-use futures::{Future, stream::FuturesUnordered};
-use ingress::IngressControllerContext;
+use cache::Cache;
+use clap::{Parser, Subcommand};
+use futures::{Future, future::Either, stream::FuturesUnordered};
+use health::Health;
+use ingress::{ApexFallback, IngressControllerContext};
 use kube::Client;
+use kube::core::Selector;
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::ZoneRef;
+use leader::LeaderElection;
+use metrics::Metrics;
 use record::RecordControllerContext;
+use tracing::{error, warn};
+use webhook::WebhookContext;
 use zone::ZoneControllerContext;
 
 pub use kubizone::*;
@@ -20,15 +32,122 @@ struct Args {
 #[derive(Debug, Subcommand)]
 enum Command {
     Reconcile {
-        /// Default time to wait between requeuing resources.
-        #[arg(env, long, default_value_t = 30)]
+        /// Time between periodic resyncs of resources, as a safety net for
+        /// missed changes. Resources are otherwise reconciled as soon as they,
+        /// or the zones and records they depend on, change.
+        #[arg(env, long, default_value_t = 3600)]
         requeue_time_secs: u64,
 
         /// If enabled, controller will create Records for all
         /// ingresses based on its hosts and loadBalancer settings.
         #[arg(env, long, default_value_t = false)]
         ingress_record_creation: bool,
+
+        /// Determines what happens when an ingress host is the apex of a zone,
+        /// and its load balancer only reports a hostname, which cannot be
+        /// used as a CNAME at a zone apex.
+        #[arg(env, long, value_enum, default_value_t = ApexFallback::Skip)]
+        ingress_apex_fallback: ApexFallback,
+
+        /// If enabled, records are only created for ingresses which
+        /// are explicitly annotated with `kubi.zone/enabled: "true"`.
+        #[arg(env, long, default_value_t = false)]
+        ingress_opt_in: bool,
+
+        /// Only create records for ingresses with one of these `ingressClassName`s.
+        /// If none are specified, ingresses of all classes are considered.
+        #[arg(env, long, value_delimiter = ',')]
+        ingress_class: Vec<String>,
+
+        /// Only create records for ingresses in namespaces matching this
+        /// label selector, e.g. `dns=public`.
+        #[arg(env, long, value_parser = ingress::parse_label_selector)]
+        ingress_namespace_selector: Option<Selector>,
+
+        /// If set, every generated record is accompanied by a TXT record
+        /// identifying this cluster and the resource it was generated for,
+        /// allowing providers to avoid touching records they don't own.
+        #[arg(env, long, value_parser = ingress::parse_cluster_id)]
+        registry_cluster_id: Option<String>,
+
+        /// Address to serve Prometheus metrics on at `/metrics`, as well
+        /// as the `/healthz` liveness and `/readyz` readiness probes.
+        #[arg(env, long, default_value = "0.0.0.0:9090")]
+        metrics_address: SocketAddr,
+
+        /// If enabled, the controllers only run while holding a Lease, so
+        /// multiple replicas can be deployed, with all but one on standby.
+        #[arg(env, long, default_value_t = false)]
+        leader_election: bool,
+
+        /// Namespace of the Lease used for leader election. Defaults to the
+        /// namespace of the pod.
+        #[arg(env, long)]
+        leader_election_namespace: Option<String>,
+
+        /// Name of the Lease used for leader election.
+        #[arg(env, long, default_value = leader::LEASE_NAME)]
+        leader_election_lease_name: String,
+
+        /// Identity of this replica when holding the Lease. Defaults to the
+        /// hostname, which within Kubernetes is the name of the pod.
+        #[arg(env, long)]
+        leader_election_identity: Option<String>,
+
+        /// Time after its last renewal at which the Lease is considered
+        /// expired, and can be taken over by a standby replica.
+        #[arg(env, long, default_value_t = 15)]
+        leader_election_lease_duration_secs: u64,
     },
+    /// Explain how the delegations of a zone apply to a record, without creating it.
+    Explain {
+        /// Namespace the record would be created in.
+        #[arg(long, short)]
+        namespace: String,
+
+        /// Type of the record, such as `A` or `CNAME`.
+        #[arg(long = "type", short, value_parser = explain::parse_type)]
+        type_: Type,
+
+        /// Time-to-Live of the record. Defaults to the one of the zone.
+        #[arg(long)]
+        ttl: Option<u32>,
+
+        /// Record data, such as the target of a `CNAME` record.
+        #[arg(long, default_value = "")]
+        rdata: String,
+
+        /// Zone to evaluate the record against, as `name`, `namespace/name` or `ClusterZone/name`.
+        /// Defaults to the most immediate parent zone of the record.
+        #[arg(long, value_parser = explain::parse_zone_ref)]
+        zone_ref: Option<ZoneRef>,
+
+        /// Fully qualified domain name of the record, such as `www.example.org.`
+        #[arg(value_parser = explain::parse_fqdn)]
+        fqdn: FullyQualifiedDomainName,
+    },
+    /// Serve the validating admission and conversion webhooks for records and zones.
+    Webhook {
+        /// Address to serve the webhook on.
+        #[arg(env, long, default_value = "0.0.0.0:8443")]
+        webhook_address: SocketAddr,
+
+        /// Path to the PEM-encoded TLS certificate chain to serve.
+        #[arg(env, long)]
+        tls_certificate: PathBuf,
+
+        /// Path to the PEM-encoded private key of the TLS certificate.
+        #[arg(env, long)]
+        tls_key: PathBuf,
+
+        /// If enabled, records which would not be adopted by any
+        /// zone, given the zones' delegations, are also rejected.
+        #[arg(env, long, default_value_t = false)]
+        reject_unadoptable: bool,
+    },
+    /// Rewrite all records and zones in the storage version of their custom
+    /// resource definitions, and drop all other versions from their `storedVersions`.
+    MigrateStorage,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -40,39 +159,214 @@ async fn main() {
         Command::Reconcile {
             requeue_time_secs,
             ingress_record_creation,
+            ingress_apex_fallback,
+            ingress_opt_in,
+            ingress_class,
+            ingress_namespace_selector,
+            registry_cluster_id,
+            metrics_address,
+            leader_election,
+            leader_election_namespace,
+            leader_election_lease_name,
+            leader_election_identity,
+            leader_election_lease_duration_secs,
         } => {
             let client = Client::try_default().await.unwrap();
+            let metrics = Arc::new(Metrics::default());
+            let health = Arc::new(Health::default());
 
-            let futures: FuturesUnordered<Pin<Box<dyn Future<Output = ()>>>> =
-                FuturesUnordered::new();
+            let leader = leader_election.then(|| {
+                let identity = leader_election_identity
+                    .or_else(|| std::env::var("HOSTNAME").ok())
+                    .expect("--leader-election-identity must be set when HOSTNAME is not");
 
-            futures.push(Box::pin(async {
-                zone::controller(ZoneControllerContext {
+                LeaderElection::new(
+                    client.clone(),
+                    leader_election_namespace
+                        .as_deref()
+                        .unwrap_or(client.default_namespace()),
+                    leader_election_lease_name,
+                    identity,
+                    Duration::from_secs(leader_election_lease_duration_secs),
+                )
+            });
+
+            let (cache, reflectors) = Cache::new(client.clone());
+
+            // The controllers subscribe to the cache as they are constructed,
+            // which must happen before the cache is started.
+            let zone_controller = zone::controller(ZoneControllerContext {
+                client: client.clone(),
+                requeue_time: Duration::from_secs(requeue_time_secs),
+                metrics: metrics.clone(),
+                health: health.clone(),
+                cache: cache.clone(),
+            });
+
+            let record_controller = record::controller(RecordControllerContext {
+                client: client.clone(),
+                requeue_time: Duration::from_secs(requeue_time_secs),
+                metrics: metrics.clone(),
+                health: health.clone(),
+                cache: cache.clone(),
+            });
+
+            let ingress_controller = ingress_record_creation.then(|| {
+                ingress::controller(IngressControllerContext {
                     client: client.clone(),
                     requeue_time: Duration::from_secs(requeue_time_secs),
+                    metrics: metrics.clone(),
+                    health: health.clone(),
+                    cache: cache.clone(),
+                    apex_fallback: ingress_apex_fallback,
+                    opt_in: ingress_opt_in,
+                    ingress_classes: ingress_class,
+                    namespace_selector: ingress_namespace_selector,
+                    registry_cluster_id,
                 })
-                .await;
+            });
+
+            // Each future resolves to the name of what it was running, so
+            // the reason for shutting down can be reported once one exits.
+            let futures: FuturesUnordered<Pin<Box<dyn Future<Output = &str>>>> =
+                FuturesUnordered::new();
+
+            futures.push(Box::pin(async {
+                futures::join!(reflectors, cache.report_health(&health));
+                "cache"
             }));
 
             futures.push(Box::pin(async {
-                record::controller(RecordControllerContext {
-                    client: client.clone(),
-                    requeue_time: Duration::from_secs(requeue_time_secs),
-                })
-                .await;
+                zone_controller.await;
+                "zone controller"
             }));
 
-            if ingress_record_creation {
+            futures.push(Box::pin(async {
+                record_controller.await;
+                "record controller"
+            }));
+
+            if let Some(ingress_controller) = ingress_controller {
                 futures.push(Box::pin(async {
-                    ingress::controller(IngressControllerContext {
-                        client: client.clone(),
-                        requeue_time: Duration::from_secs(requeue_time_secs),
-                    })
-                    .await;
+                    ingress_controller.await;
+                    "ingress controller"
                 }));
             }
 
-            futures::future::select_all(futures.into_iter()).await;
+            if let Some(leader) = &leader {
+                futures.push(Box::pin(async {
+                    leader.hold().await;
+                    "leader election"
+                }));
+            }
+
+            // Probes and metrics are served while waiting to become the leader as
+            // well, with standby replicas reported as healthy and ready.
+            let server = Box::pin(async {
+                if let Err(error) =
+                    health::serve(metrics_address, metrics.clone(), health.clone()).await
+                {
+                    error!("metrics and health probe server failed: {error}");
+                }
+                "metrics and health probe server"
+            });
+
+            let controllers = Box::pin(async {
+                if let Some(leader) = &leader {
+                    leader.acquire(&health).await;
+                }
+
+                let (exited, _, _) = futures::future::select_all(futures.into_iter()).await;
+                exited
+            });
+
+            let exited = futures::future::select(server, controllers)
+                .await
+                .factor_first()
+                .0;
+            warn!("{exited} exited, shutting down");
+
+            if let Some(leader) = &leader {
+                leader.release().await;
+            }
+        }
+        Command::Explain {
+            namespace,
+            type_,
+            ttl,
+            rdata,
+            zone_ref,
+            fqdn,
+        } => {
+            let client = match Client::try_default().await {
+                Ok(client) => client,
+                Err(error) => {
+                    eprintln!("failed to connect to the cluster: {error}");
+                    std::process::exit(1);
+                }
+            };
+
+            // Zones are looked up in the same cache as used by the controllers,
+            // so they are picked the same way.
+            let (cache, reflectors) = Cache::new(client);
+            let synchronized =
+                futures::future::select(Box::pin(reflectors), Box::pin(cache.wait_until_ready()))
+                    .await;
+
+            if !matches!(synchronized, Either::Right((true, _))) {
+                eprintln!("failed to explain {fqdn}: unable to list zones and records");
+                std::process::exit(1);
+            }
+
+            match explain::explain(
+                &cache,
+                &namespace,
+                type_,
+                &fqdn,
+                ttl,
+                &rdata,
+                zone_ref.as_ref(),
+            ) {
+                Some((zone, explanation)) => {
+                    println!(
+                        "{type_} record {fqdn} in namespace {namespace}, against zone {zone}:"
+                    );
+                    print!("{explanation}");
+                }
+                None => {
+                    eprintln!("no zone found for {fqdn}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Webhook {
+            webhook_address,
+            tls_certificate,
+            tls_key,
+            reject_unadoptable,
+        } => {
+            let client = Client::try_default().await.unwrap();
+
+            // Zones are only cached when they are needed for rejecting unadoptable records.
+            let adoption_cache = reject_unadoptable.then(|| {
+                let (cache, reflectors) = Cache::new(client);
+                tokio::spawn(reflectors);
+                cache
+            });
+
+            webhook::serve(
+                webhook_address,
+                tls_certificate,
+                tls_key,
+                WebhookContext { adoption_cache },
+            )
+            .await
+            .unwrap();
+        }
+        Command::MigrateStorage => {
+            let client = Client::try_default().await.unwrap();
+
+            migrate::migrate_storage(client).await.unwrap();
         }
     }
 }
@@ -0,0 +1,298 @@
+use std::{future::Future, time::Instant};
+
+use kube::runtime::controller::Action;
+use prometheus_client::{
+    encoding::{EncodeLabelSet, text::encode},
+    metrics::{counter::Counter, family::Family, gauge::Gauge, histogram::Histogram},
+    registry::Registry,
+};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ControllerLabels {
+    controller: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ZoneLabels {
+    namespace: String,
+    name: String,
+}
+
+/// Prometheus metrics shared by all controllers.
+pub struct Metrics {
+    registry: Registry,
+    reconciliations: Family<ControllerLabels, Counter>,
+    reconcile_duration: Family<ControllerLabels, Histogram>,
+    reconcile_errors: Family<ControllerLabels, Counter>,
+    requeues: Family<ControllerLabels, Counter>,
+    zone_entries: Family<ZoneLabels, Gauge>,
+    zone_serial: Family<ZoneLabels, Gauge>,
+    zone_rejected_records: Family<ZoneLabels, Gauge>,
+    zone_last_change: Family<ZoneLabels, Gauge>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let mut registry = Registry::with_prefix("kubizone");
+
+        let reconciliations = Family::default();
+        registry.register(
+            "reconciliations",
+            "Number of reconciliations performed by the controller",
+            reconciliations.clone(),
+        );
+
+        let reconcile_duration =
+            Family::<ControllerLabels, Histogram>::new_with_constructor(|| {
+                Histogram::new([0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0])
+            });
+        registry.register(
+            "reconcile_duration_seconds",
+            "Time spent reconciling a single resource",
+            reconcile_duration.clone(),
+        );
+
+        let reconcile_errors = Family::default();
+        registry.register(
+            "reconcile_errors",
+            "Number of reconciliations which failed with an error",
+            reconcile_errors.clone(),
+        );
+
+        let requeues = Family::default();
+        registry.register(
+            "error_requeues",
+            "Number of resources requeued by the controller's error policy, after failing to reconcile",
+            requeues.clone(),
+        );
+
+        let zone_entries = Family::default();
+        registry.register(
+            "zone_entries",
+            "Number of entries in the zone, including its SOA record",
+            zone_entries.clone(),
+        );
+
+        let zone_serial = Family::default();
+        registry.register(
+            "zone_serial",
+            "Current serial of the zone",
+            zone_serial.clone(),
+        );
+
+        let zone_rejected_records = Family::default();
+        registry.register(
+            "zone_rejected_records",
+            "Number of records claiming the zone as their parent, which its delegations deny",
+            zone_rejected_records.clone(),
+        );
+
+        let zone_last_change = Family::default();
+        registry.register(
+            "zone_last_change_timestamp_seconds",
+            "Unix timestamp of the last change to the zone's entries observed by the controller",
+            zone_last_change.clone(),
+        );
+
+        Metrics {
+            registry,
+            reconciliations,
+            reconcile_duration,
+            reconcile_errors,
+            requeues,
+            zone_entries,
+            zone_serial,
+            zone_rejected_records,
+            zone_last_change,
+        }
+    }
+}
+
+impl Metrics {
+    /// Run `reconciliation`, recording its outcome and duration as a
+    /// reconciliation performed by `controller`.
+    pub async fn measure<E>(
+        &self,
+        controller: &str,
+        reconciliation: impl Future<Output = Result<Action, E>>,
+    ) -> Result<Action, E> {
+        let labels = ControllerLabels {
+            controller: controller.to_string(),
+        };
+
+        let started = Instant::now();
+        let result = reconciliation.await;
+
+        self.reconcile_duration
+            .get_or_create(&labels)
+            .observe(started.elapsed().as_secs_f64());
+        self.reconciliations.get_or_create(&labels).inc();
+
+        if result.is_err() {
+            self.reconcile_errors.get_or_create(&labels).inc();
+        }
+
+        result
+    }
+
+    /// Record that the error policy of `controller` requeued a resource.
+    pub fn requeued(&self, controller: &str) {
+        self.requeues
+            .get_or_create(&ControllerLabels {
+                controller: controller.to_string(),
+            })
+            .inc();
+    }
+
+    /// Record the state of a zone, as of its latest reconciliation.
+    ///
+    /// Changes made before the controller started are not observed, so until the
+    /// zone changes again, its last change is taken to be the start of the day
+    /// encoded in its `serial`, which is bumped on every change.
+    pub fn observe_zone(
+        &self,
+        namespace: &str,
+        name: &str,
+        entries: usize,
+        serial: u32,
+        rejected_records: usize,
+        changed: bool,
+    ) {
+        let labels = ZoneLabels {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        };
+
+        self.zone_entries.get_or_create(&labels).set(entries as i64);
+        self.zone_serial.get_or_create(&labels).set(serial as i64);
+        self.zone_rejected_records
+            .get_or_create(&labels)
+            .set(rejected_records as i64);
+
+        if changed {
+            self.zone_last_change
+                .get_or_create(&labels)
+                .set(time::OffsetDateTime::now_utc().unix_timestamp());
+        } else if self.zone_last_change.get(&labels).is_none() {
+            if let Some(timestamp) = serial_timestamp(serial) {
+                self.zone_last_change.get_or_create(&labels).set(timestamp);
+            }
+        }
+    }
+
+    /// Drop all series of a deleted zone.
+    pub fn forget_zone(&self, namespace: &str, name: &str) {
+        let labels = ZoneLabels {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        };
+
+        self.zone_entries.remove(&labels);
+        self.zone_serial.remove(&labels);
+        self.zone_rejected_records.remove(&labels);
+        self.zone_last_change.remove(&labels);
+    }
+
+    /// Encode all metrics in the OpenMetrics text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        // Unwrap safety: Writing to a String cannot fail.
+        encode(&mut buffer, &self.registry).unwrap();
+        buffer
+    }
+}
+
+/// Unix timestamp of the start of the day encoded in a `YYYYMMDDnn` zone serial.
+fn serial_timestamp(serial: u32) -> Option<i64> {
+    let date = time::Date::from_calendar_date(
+        (serial / 1000000) as i32,
+        time::Month::try_from((serial / 10000 % 100) as u8).ok()?,
+        (serial / 100 % 100) as u8,
+    )
+    .ok()?;
+
+    Some(date.midnight().assume_utc().unix_timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use kube::runtime::controller::Action;
+
+    use super::Metrics;
+
+    fn sample<'a>(encoded: &'a str, series: &str) -> Option<&'a str> {
+        encoded
+            .lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+    }
+
+    #[tokio::test]
+    async fn reconciliations() {
+        let metrics = Metrics::default();
+
+        metrics
+            .measure("zone", async { Ok::<_, ()>(Action::await_change()) })
+            .await
+            .unwrap();
+        metrics
+            .measure("zone", async { Err::<Action, _>(()) })
+            .await
+            .unwrap_err();
+        metrics.requeued("zone");
+
+        let encoded = metrics.encode();
+        let labels = r#"{controller="zone"}"#;
+
+        assert_eq!(
+            sample(&encoded, &format!("kubizone_reconciliations_total{labels}")),
+            Some("2")
+        );
+        assert_eq!(
+            sample(
+                &encoded,
+                &format!("kubizone_reconcile_errors_total{labels}")
+            ),
+            Some("1")
+        );
+        assert_eq!(
+            sample(&encoded, &format!("kubizone_error_requeues_total{labels}")),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn zones() {
+        let metrics = Metrics::default();
+        let labels = r#"{namespace="dns",name="example-org"}"#;
+
+        // Unchanged zones observed for the first time report the day of their serial.
+        metrics.observe_zone("dns", "example-org", 3, 2024030105, 1, false);
+        let encoded = metrics.encode();
+
+        assert_eq!(
+            sample(&encoded, &format!("kubizone_zone_entries{labels}")),
+            Some("3")
+        );
+        assert_eq!(
+            sample(
+                &encoded,
+                &format!("kubizone_zone_last_change_timestamp_seconds{labels}")
+            ),
+            Some("1709251200")
+        );
+
+        metrics.observe_zone("dns", "example-org", 4, 2024030106, 1, true);
+        let encoded = metrics.encode();
+        let last_change: i64 = sample(
+            &encoded,
+            &format!("kubizone_zone_last_change_timestamp_seconds{labels}"),
+        )
+        .unwrap()
+        .parse()
+        .unwrap();
+        assert!(last_change > 1709251200);
+
+        metrics.forget_zone("dns", "example-org");
+        assert!(!metrics.encode().contains("example-org"));
+    }
+}
@@ -0,0 +1,102 @@
+use k8s_openapi::{
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
+    serde_json::json,
+};
+use kube::{
+    Api, Client, Resource, ResourceExt,
+    api::{ApiResource, DynamicObject, ListParams, Patch, PatchParams, PostParams},
+};
+use kubizone_crds::v1alpha1::{Record, Zone};
+use tracing::*;
+
+/// Rewrite all Records and Zones, so they are persisted in the storage version
+/// of their custom resource definitions, after which all other versions are
+/// dropped from the definitions' `status.storedVersions`.
+///
+/// Must be run after changing the storage version, and before removing
+/// a version from the custom resource definitions entirely.
+pub async fn migrate_storage(client: Client) -> Result<(), kube::Error> {
+    migrate::<Record>(client.clone()).await?;
+    migrate::<Zone>(client).await?;
+
+    Ok(())
+}
+
+async fn migrate<K>(client: Client) -> Result<(), kube::Error>
+where
+    K: Resource<DynamicType = ()>,
+{
+    let definitions = Api::<CustomResourceDefinition>::all(client.clone());
+    let name = format!("{}.{}", K::plural(&()), K::group(&()));
+
+    let definition = definitions.get(&name).await?;
+    let Some(storage_version) = definition
+        .spec
+        .versions
+        .iter()
+        .find(|version| version.storage)
+        .map(|version| version.name.clone())
+    else {
+        warn!("{name} has no storage version, skipping migration.");
+        return Ok(());
+    };
+
+    // Objects are read and written in the storage version, so they are not converted on the way.
+    let resource = ApiResource {
+        group: K::group(&()).to_string(),
+        version: storage_version.clone(),
+        api_version: format!("{}/{storage_version}", K::group(&())),
+        kind: K::kind(&()).to_string(),
+        plural: K::plural(&()).to_string(),
+    };
+
+    let objects = Api::<DynamicObject>::all_with(client.clone(), &resource)
+        .list(&ListParams::default())
+        .await?;
+
+    let mut migrated = 0;
+    for object in objects {
+        let namespace = object.namespace().unwrap_or_default();
+        let api = Api::<DynamicObject>::namespaced_with(client.clone(), &namespace, &resource);
+
+        // An unchanged update still causes the API server to encode the object in the storage version.
+        match api
+            .replace(&object.name_any(), &PostParams::default(), &object)
+            .await
+        {
+            Ok(_) => migrated += 1,
+            // Objects which were modified or deleted in the meantime have already been
+            // written in the storage version, or no longer need to be.
+            Err(kube::Error::Api(error)) if error.code == 404 || error.code == 409 => {
+                debug!(
+                    "{} {namespace}/{} changed during migration: {}",
+                    resource.kind,
+                    object.name_any(),
+                    error.message
+                );
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    info!(
+        "migrated {migrated} {} to {}",
+        resource.plural, resource.api_version
+    );
+
+    definitions
+        .patch_status(
+            &name,
+            &PatchParams::default(),
+            &Patch::Merge(json!({
+                "status": {
+                    "storedVersions": [storage_version]
+                }
+            })),
+        )
+        .await?;
+
+    info!("set stored versions of {name} to {storage_version}");
+
+    Ok(())
+}
@@ -1,94 +1,189 @@
-use futures::StreamExt;
-use std::{sync::Arc, time::Duration};
+use futures::{FutureExt, StreamExt};
+use k8s_openapi::{
+    NamespaceResourceScope,
+    api::core::v1::Namespace,
+    serde::{Serialize, de::DeserializeOwned},
+};
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
 
 use kube::{
-    Api, Client, ResourceExt,
-    api::ListParams,
-    runtime::{Controller, controller::Action, watcher},
+    Client, Resource, ResourceExt,
+    runtime::{Controller, controller::Action},
 };
 
 use kubizone_common::DomainName;
-use kubizone_crds::v1alpha1::{DomainExt as _, Record, Zone};
+use kubizone_crds::v1alpha1::{
+    ClusterZone, DomainExt as _, Record, RecordExt, RecordSet, Zone, ZoneExt as _,
+};
 use tracing::*;
 
-use crate::{set_fqdn, set_parent, with_parent_zone};
+use crate::{
+    PENDING_ZONE_RETRY_TIME, Placement, ResourceApi,
+    cache::{Cache, Cached},
+    dependents_of_zone,
+    health::Health,
+    metrics::Metrics,
+    set_fqdn, set_parent, within_namespace, zone_changes,
+};
 
 #[cfg(feature = "dev")]
 const CONTROLLER_NAME: &str = "dev.kubi.zone/record-resolver";
 #[cfg(not(feature = "dev"))]
 const CONTROLLER_NAME: &str = "kubi.zone/record-resolver";
 
-pub async fn controller(context: RecordControllerContext) {
-    let records = Api::<Record>::all(context.client.clone());
-
-    let record_controller = Controller::new(records, watcher::Config::default())
-        .watches(
-            Api::<Zone>::all(context.client.clone()),
-            watcher::Config::default(),
-            with_parent_zone(),
-        )
-        .shutdown_on_signal()
-        .run(reconcile_records, record_error_policy, Arc::new(context))
-        .for_each(|res| async move {
-            match res {
-                Ok(o) => info!("reconciled {:?}", o),
-                Err(e) => warn!("reconcile failed: {}", e),
-            }
-        });
+/// Run the controllers adopting [`Record`]s and [`RecordSet`]s into zones.
+///
+/// The controllers subscribe to the cache right away, so this
+/// must be called before the cache is started.
+pub fn controller(context: RecordControllerContext) -> impl Future<Output = ()> {
+    let context = Arc::new(context);
+
+    futures::future::join(run::<Record>(context.clone()), run::<RecordSet>(context)).map(|_| ())
+}
+
+fn run<R>(context: Arc<RecordControllerContext>) -> impl Future<Output = ()>
+where
+    R: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + ResourceApi
+        + Cached
+        + RecordExt
+        + Placement
+        + Clone
+        + Debug
+        + Display
+        + DeserializeOwned
+        + Serialize
+        + Send
+        + Sync
+        + 'static,
+{
+    let cache = &context.cache;
+    let triggers = cache.subscribe::<R>();
+    let zones = cache.subscribe::<Zone>();
+    let cluster_zones = cache.subscribe::<ClusterZone>();
+    let namespaces = cache.subscribe::<Namespace>();
+
+    async move {
+        let health = context.health.clone();
+        let name = R::kind(&()).to_lowercase();
+        health.register(&name);
+
+        if !context.cache.wait_until_ready().await {
+            health.exited(&name);
+            warn!("{name} controller exited, since the cache stopped before synchronizing");
+            return;
+        }
+
+        let store = context.cache.store::<R>();
+
+        let record_controller = Controller::for_shared_stream(triggers, store.clone())
+            // Zones and cluster zones changing their fqdn or delegations might change the fqdn
+            // or parent zone of the records referencing or within them.
+            .watches_shared_stream(zone_changes(zones), dependents_of_zone(store.clone()))
+            .watches_shared_stream(
+                zone_changes(cluster_zones),
+                dependents_of_zone(store.clone()),
+            )
+            // Namespace label changes might change which delegations cover the records within.
+            .watches_shared_stream(namespaces, within_namespace(store.clone()))
+            .shutdown_on_signal()
+            .run(
+                |record, ctx| {
+                    let metrics = ctx.metrics.clone();
+                    async move {
+                        metrics
+                            .measure(&R::kind(&()).to_lowercase(), reconcile_records(record, ctx))
+                            .await
+                    }
+                },
+                record_error_policy,
+                context,
+            )
+            .for_each(|res| async move {
+                match res {
+                    Ok(o) => info!("reconciled {:?}", o),
+                    Err(e) => warn!("reconcile failed: {}", e),
+                }
+            });
 
-    record_controller.await;
-    warn!("record controller exited");
+        futures::future::join(record_controller, health.ready_when_synced(&name, store)).await;
+        health.exited(&name);
+        warn!("{name} controller exited");
+    }
 }
 
 pub struct RecordControllerContext {
     pub client: Client,
     pub requeue_time: Duration,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<Health>,
+    pub cache: Cache,
 }
 
 #[tracing::instrument(name = "record", skip_all)]
-async fn reconcile_records(
-    record: Arc<Record>,
+async fn reconcile_records<R>(
+    record: Arc<R>,
     ctx: Arc<RecordControllerContext>,
-) -> Result<Action, kube::Error> {
-    match (record.spec.zone_ref.as_ref(), &record.spec.domain_name) {
+) -> Result<Action, kube::Error>
+where
+    R: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + ResourceApi
+        + RecordExt
+        + Clone
+        + Debug
+        + Display
+        + DeserializeOwned
+        + Serialize,
+{
+    let labels = ctx
+        .cache
+        .namespace_labels(record.namespace().as_deref().unwrap());
+
+    match (record.zone_ref(), record.domain_name()) {
         (Some(zone_ref), DomainName::Partial(partial_domain)) => {
-            // Follow the zoneRef to the supposed parent zone, if it exists
-            // or requeue later if it does not.
-            let Some(parent_zone) = Api::<Zone>::namespaced(
-                ctx.client.clone(),
-                &zone_ref
-                    .namespace
-                    .as_ref()
-                    .or(record.namespace().as_ref())
-                    .cloned()
-                    .unwrap(),
-            )
-            .get_opt(&zone_ref.name)
-            .await?
-            else {
+            // Follow the zoneRef to the supposed parent zone, if it exists.
+            // Otherwise, the record is reconciled again once the zone is created.
+            let Some(parent_zone) = ctx.cache.zone(zone_ref, record.namespace().as_deref()) else {
                 warn!("record {record} references unknown zone {zone_ref}");
-                return Ok(Action::requeue(ctx.requeue_time));
+                return Ok(Action::requeue(PENDING_ZONE_RETRY_TIME));
             };
 
             // If the parent does not have a fully qualified domain name defined
             // yet, we can't check if the delegations provided by it are valid.
-            // Postpone the reconcilliation until a later time, when the fqdn
-            // has (hopefully) been determined.
+            // The record is reconciled again once the parent's fqdn is set.
             let Some(parent_fqdn) = parent_zone.fqdn() else {
-                info!("parent zone {parent_zone} missing fqdn, requeuing.",);
-                return Ok(Action::requeue(Duration::from_secs(5)));
+                info!("parent zone {parent_zone} missing fqdn, postponing.");
+                return Ok(Action::requeue(PENDING_ZONE_RETRY_TIME));
             };
 
+            // Zones which lost the claim to their fqdn to another zone cannot adopt anything.
+            if let Some(owner) = ctx.cache.claimed_by(&parent_zone, parent_fqdn) {
+                warn!(
+                    "record {record} references zone {parent_zone}, but its fqdn {parent_fqdn} has already been claimed by zone {owner}"
+                );
+                set_parent(CONTROLLER_NAME, ctx.client.clone(), &record, None).await?;
+                return Ok(Action::requeue(ctx.requeue_time));
+            }
+
             // This is only "alleged", since we don't know yet if the referenced
             // zone's delegations allow the adoption.
             let alleged_fqdn = partial_domain.with_origin(parent_fqdn);
 
             trace!("record alleged fqdn: {partial_domain} + {parent_fqdn} = {alleged_fqdn}");
 
-            if parent_zone.spec.delegations.iter().any(|delegation| {
-                delegation.covers_namespace(record.namespace().as_deref().unwrap())
-                    && delegation.validate_record(parent_fqdn, record.spec.type_, &alleged_fqdn)
-            }) {
+            let verdict = parent_zone.evaluate_record(
+                record.namespace().as_deref().unwrap(),
+                &labels,
+                record.type_(),
+                &alleged_fqdn,
+            );
+
+            if verdict.is_allowed() {
                 set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &record, &alleged_fqdn).await?;
                 set_parent(
                     CONTROLLER_NAME,
@@ -99,8 +194,8 @@ async fn reconcile_records(
                 .await?;
             } else {
                 warn!(
-                    "parent zone {parent_zone} was found, but its delegations does not allow adoption of {record} with {alleged_fqdn} and type {}",
-                    record.spec.type_
+                    "parent zone {parent_zone} was found, but its delegations does not allow adoption of {record} with {alleged_fqdn} and type {}: {verdict}",
+                    record.type_()
                 );
                 return Ok(Action::requeue(ctx.requeue_time));
             }
@@ -110,27 +205,25 @@ async fn reconcile_records(
                 .await?
                 .changed()
             {
-                info!("record {record} fqdn changed to {record_fqdn}, requeuing.");
-                return Ok(Action::requeue(Duration::from_secs(1)));
+                // Updating the fqdn triggers another reconciliation by itself.
+                info!("record {record} fqdn changed to {record_fqdn}.");
+                return Ok(Action::await_change());
             }
 
-            // Fetch all zones from across the cluster and then filter down results to only parent
-            // zones which are valid parent zones for this one.
+            // Look up the most immediate parent zone of the record, and check whether
+            // its delegations allow the record's namespace, suffix and type.
             //
-            // This means filtering out parent zones without fqdns, as well as ones which do not
-            // have appropriate delegations for our `zone`'s namespace and suffix.
-            if let Some(longest_parent_zone) = Api::<Zone>::all(ctx.client.clone())
-                .list(&ListParams::default())
-                .await?
-                .into_iter()
-                .filter(|parent| {
-                    parent
-                        .fqdn()
-                        .is_some_and(|parent_fqdn| record_fqdn.is_subdomain_of(parent_fqdn))
-                })
-                .max_by_key(|parent| parent.fqdn().unwrap().as_ref().len())
-            {
-                if longest_parent_zone.validate_record(&record) {
+            // Records at the apex of a zone belong to that zone.
+            if let Some(longest_parent_zone) = ctx.cache.parent_zone(record_fqdn, true) {
+                if longest_parent_zone
+                    .evaluate_record(
+                        record.namespace().as_deref().unwrap(),
+                        &labels,
+                        record.type_(),
+                        record_fqdn,
+                    )
+                    .is_allowed()
+                {
                     set_parent(
                         CONTROLLER_NAME,
                         ctx.client.clone(),
@@ -147,7 +240,7 @@ async fn reconcile_records(
             } else {
                 warn!(
                     "record {record} ({}) does not fit into any found parent Zone",
-                    &record.spec.domain_name
+                    record.domain_name()
                 );
                 set_parent(CONTROLLER_NAME, ctx.client.clone(), &record, None).await?;
             };
@@ -169,14 +262,15 @@ async fn reconcile_records(
     Ok(Action::requeue(ctx.requeue_time))
 }
 
-fn record_error_policy(
-    record: Arc<Record>,
+fn record_error_policy<R: Resource<DynamicType = ()>>(
+    record: Arc<R>,
     error: &kube::Error,
-    _ctx: Arc<RecordControllerContext>,
+    ctx: Arc<RecordControllerContext>,
 ) -> Action {
     error!(
         "record {} reconciliation encountered error: {error}",
         record.name_any()
     );
+    ctx.metrics.requeued(&R::kind(&()).to_lowercase());
     Action::requeue(Duration::from_secs(60))
 }
@@ -0,0 +1,494 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use k8s_openapi::{
+    serde::{Serialize, de::DeserializeOwned},
+    serde_json,
+};
+use kube::{
+    Resource, ResourceExt,
+    core::{
+        Status,
+        admission::{AdmissionRequest, AdmissionResponse, AdmissionReview, Operation},
+        conversion::{ConversionRequest, ConversionResponse, ConversionReview},
+    },
+};
+use kubizone_common::DomainName;
+use kubizone_crds::{
+    conversion,
+    v1alpha1::{
+        ClusterZone, DomainExt as _, Record, RecordExt, RecordSet, SpecError, Zone, ZoneExt,
+    },
+};
+use tokio::net::TcpListener;
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+};
+use tracing::*;
+
+use crate::{cache::Cache, explain};
+
+pub struct WebhookContext {
+    /// If set, records which no zone would adopt are rejected, in addition to
+    /// records with invalid specifications, looking zones up in this cache.
+    pub adoption_cache: Option<Cache>,
+}
+
+/// Interval at which the TLS certificate and key files are checked for changes.
+const CERTIFICATE_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serve the validating admission webhook over TLS on `address`.
+///
+/// Records are validated at `/validate/records`, record sets at `/validate/recordsets`,
+/// zones at `/validate/zones` and cluster zones at `/validate/clusterzones`,
+/// while conversion between versions of the resources is served at `/convert`.
+///
+/// The `certificate` and `key` are reloaded whenever either of the files change,
+/// so certificates rotated by for example cert-manager are picked up without a restart.
+pub async fn serve(
+    address: SocketAddr,
+    certificate: PathBuf,
+    key: PathBuf,
+    context: WebhookContext,
+) -> std::io::Result<()> {
+    let acceptor = Arc::new(RwLock::new(load_acceptor(&certificate, &key)?));
+    tokio::spawn(reload_acceptor(acceptor.clone(), certificate, key));
+
+    let listener = TcpListener::bind(address).await?;
+    let context = Arc::new(context);
+
+    info!("serving admission webhook on {address}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let acceptor = acceptor.read().unwrap().clone();
+        let context = context.clone();
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(error) => {
+                    warn!("tls handshake with {peer} failed: {error}");
+                    return;
+                }
+            };
+
+            let service = service_fn(move |request| handle(request, context.clone()));
+
+            if let Err(error) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                warn!("connection with {peer} failed: {error}");
+            }
+        });
+    }
+}
+
+/// Build a TLS acceptor from the PEM-encoded `certificate` chain and private `key`.
+fn load_acceptor(certificate: &Path, key: &Path) -> std::io::Result<TlsAcceptor> {
+    let certificates = CertificateDer::pem_file_iter(certificate)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .map_err(std::io::Error::other)?;
+
+    let key = PrivateKeyDer::from_pem_file(key).map_err(std::io::Error::other)?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certificates, key)
+        .map_err(std::io::Error::other)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Latest modification time of the `certificate` and `key` files.
+fn last_modified(certificate: &Path, key: &Path) -> Option<SystemTime> {
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+
+    modified(certificate).max(modified(key))
+}
+
+/// Replace the `acceptor` whenever the `certificate` or `key` files change.
+///
+/// If the files cannot be loaded, for example while only one of them has
+/// been replaced, the previous acceptor is kept until the next change.
+async fn reload_acceptor(acceptor: Arc<RwLock<TlsAcceptor>>, certificate: PathBuf, key: PathBuf) {
+    let mut loaded = last_modified(&certificate, &key);
+    let mut interval = tokio::time::interval(CERTIFICATE_RELOAD_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let modified = last_modified(&certificate, &key);
+        if modified == loaded {
+            continue;
+        }
+
+        match load_acceptor(&certificate, &key) {
+            Ok(reloaded) => {
+                info!("reloaded tls certificate {}", certificate.display());
+                *acceptor.write().unwrap() = reloaded;
+                loaded = modified;
+            }
+            Err(error) => {
+                warn!(
+                    "failed to reload tls certificate {}: {error}",
+                    certificate.display()
+                );
+            }
+        }
+    }
+}
+
+async fn handle(
+    request: Request<Incoming>,
+    context: Arc<WebhookContext>,
+) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    if request.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let path = request.uri().path().to_string();
+    let body = request.into_body().collect().await?.to_bytes();
+
+    let response = match path.as_str() {
+        "/validate/records" => review(&body, |request| validate_record(&context, request))
+            .await
+            .and_then(|review| serde_json::to_vec(&review)),
+        "/validate/recordsets" => review(&body, |request| validate_record_set(&context, request))
+            .await
+            .and_then(|review| serde_json::to_vec(&review)),
+        "/validate/zones" => review(&body, |request| async { validate_zone::<Zone>(request) })
+            .await
+            .and_then(|review| serde_json::to_vec(&review)),
+        "/validate/clusterzones" => review(&body, |request| async {
+            validate_zone::<ClusterZone>(request)
+        })
+        .await
+        .and_then(|review| serde_json::to_vec(&review)),
+        "/convert" => convert(&body).and_then(|review| serde_json::to_vec(&review)),
+        _ => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+
+    Ok(match response {
+        Ok(body) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .unwrap(),
+        Err(error) => {
+            warn!("malformed review: {error}");
+            status(StatusCode::BAD_REQUEST)
+        }
+    })
+}
+
+/// Convert the objects of a conversion review from `body` to the desired version.
+fn convert(body: &[u8]) -> Result<ConversionReview, serde_json::Error> {
+    let review: ConversionReview = serde_json::from_slice(body)?;
+
+    let request = match ConversionRequest::from_review(review) {
+        Ok(request) => request,
+        Err(error) => {
+            return Ok(ConversionResponse::invalid(Status::failure(
+                &error.to_string(),
+                "BadRequest",
+            ))
+            .into_review());
+        }
+    };
+
+    let desired_api_version = request.desired_api_version.clone();
+    let objects = request.objects.clone();
+    let response = ConversionResponse::for_request(request);
+
+    let converted: Result<Vec<_>, _> = objects
+        .into_iter()
+        .map(|object| conversion::convert(object, &desired_api_version))
+        .collect();
+
+    Ok(match converted {
+        Ok(objects) => response.success(objects),
+        Err(error) => {
+            warn!("failed to convert objects to {desired_api_version}: {error}");
+            response.failure(Status::failure(&error.to_string(), "ConversionFailed"))
+        }
+    }
+    .into_review())
+}
+
+/// Parse an admission review from `body`, and respond to it using `validate`.
+async fn review<K, F, Fut>(
+    body: &[u8],
+    validate: F,
+) -> Result<AdmissionReview<kube::core::DynamicObject>, serde_json::Error>
+where
+    K: kube::Resource + DeserializeOwned,
+    F: FnOnce(AdmissionRequest<K>) -> Fut,
+    Fut: Future<Output = AdmissionResponse>,
+{
+    let review: AdmissionReview<K> = serde_json::from_slice(body)?;
+
+    let response = match review.try_into() {
+        Ok(request) => validate(request).await,
+        Err(error) => AdmissionResponse::invalid(error.to_string()),
+    };
+
+    Ok(response.into_review())
+}
+
+fn status(status: StatusCode) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .body(Full::default())
+        .unwrap()
+}
+
+/// Whether the request creates an object, or changes its spec.
+///
+/// Updates of only the metadata of an object, such as the labels, annotations and
+/// finalizers set by the controllers, must not be rejected, since objects which were
+/// valid before, or created before the webhook existed, could otherwise get stuck.
+fn changes_spec<K: Resource + Serialize>(request: &AdmissionRequest<K>) -> bool {
+    let spec = |object: &Option<K>| {
+        object
+            .as_ref()
+            .and_then(|object| serde_json::to_value(object).ok())
+            .map(|mut object| object["spec"].take())
+    };
+
+    match request.operation {
+        Operation::Create => true,
+        Operation::Update => spec(&request.object) != spec(&request.old_object),
+        Operation::Delete | Operation::Connect => false,
+    }
+}
+
+fn validate_zone<Z: Resource + ZoneExt + Serialize>(
+    request: AdmissionRequest<Z>,
+) -> AdmissionResponse {
+    let response = AdmissionResponse::from(&request);
+
+    let changes_spec = changes_spec(&request);
+
+    let Some(zone) = request.object.filter(|_| changes_spec) else {
+        return response;
+    };
+
+    match zone.zone_spec().validate() {
+        Ok(()) => response,
+        Err(error) => {
+            info!("rejecting zone {zone}: {error}");
+            response.deny(error)
+        }
+    }
+}
+
+async fn validate_record(
+    context: &WebhookContext,
+    request: AdmissionRequest<Record>,
+) -> AdmissionResponse {
+    validate_adoptable(context, request, |record| record.spec.validate()).await
+}
+
+async fn validate_record_set(
+    context: &WebhookContext,
+    request: AdmissionRequest<RecordSet>,
+) -> AdmissionResponse {
+    validate_adoptable(context, request, |record_set| record_set.spec.validate()).await
+}
+
+/// Validate the spec of a record or record set using `validate`, and optionally
+/// whether any zone would adopt it.
+async fn validate_adoptable<R>(
+    context: &WebhookContext,
+    request: AdmissionRequest<R>,
+    validate: impl Fn(&R) -> Result<(), SpecError>,
+) -> AdmissionResponse
+where
+    R: Resource<DynamicType = ()> + RecordExt + Clone + Serialize,
+{
+    let response = AdmissionResponse::from(&request);
+    let namespace = request.namespace.clone().unwrap_or_default();
+    let kind = R::kind(&()).to_lowercase();
+
+    let changes_spec = changes_spec(&request);
+
+    let Some(record) = request.object.filter(|_| changes_spec) else {
+        return response;
+    };
+
+    if let Err(error) = validate(&record) {
+        info!(
+            "rejecting {kind} {namespace}/{}: {error}",
+            record.name_any()
+        );
+        return response.deny(error);
+    }
+
+    let Some(cache) = &context.adoption_cache else {
+        return response;
+    };
+
+    // Failing closed here would block all record changes until the cache has
+    // synchronized, so only the spec validation is enforced until then.
+    if !cache.is_ready() {
+        warn!(
+            "unable to determine if {kind} {namespace}/{} would be adopted, since the cache has not synchronized yet",
+            record.name_any()
+        );
+        return response;
+    }
+
+    match adoption_denial(cache, &namespace, &record) {
+        None => response,
+        Some(reason) => {
+            info!(
+                "rejecting {kind} {namespace}/{}: {reason}",
+                record.name_any()
+            );
+            response.deny(reason)
+        }
+    }
+}
+
+/// Determine why no zone would adopt the record, if any.
+///
+/// Zones are picked by [`explain::find_zone`], the same way the record controller does.
+fn adoption_denial(cache: &Cache, namespace: &str, record: &impl RecordExt) -> Option<String> {
+    let (zone_ref, fqdn) = match (record.zone_ref(), record.domain_name()) {
+        (Some(zone_ref), DomainName::Partial(partial)) => {
+            let Some(zone) = cache.zone(zone_ref, Some(namespace)) else {
+                return Some(format!("referenced zone {zone_ref} does not exist"));
+            };
+
+            let Some(zone_fqdn) = zone.fqdn() else {
+                return Some(format!("referenced zone {zone_ref} has no fqdn yet"));
+            };
+
+            (Some(zone_ref), partial.with_origin(zone_fqdn))
+        }
+        (None, DomainName::Full(fqdn)) => (None, fqdn.clone()),
+        // Already rejected by spec validation.
+        _ => return None,
+    };
+
+    let Some(zone) = explain::find_zone(cache, namespace, &fqdn, zone_ref) else {
+        return Some(match zone_ref {
+            Some(zone_ref) => format!(
+                "referenced zone {zone_ref} cannot adopt records, since another zone has claimed its fqdn"
+            ),
+            None => format!("no zone exists for {fqdn}"),
+        });
+    };
+
+    let labels = cache.namespace_labels(namespace);
+
+    // Quotas depend on the other records of the zone, and are left to the zone controller.
+    let denied = record
+        .entries(&fqdn, zone.zone_spec().ttl)
+        .iter()
+        .map(|entry| zone.explain_record(namespace, &labels, entry, []).verdict)
+        .find(|verdict| !verdict.is_allowed());
+
+    denied.map(|verdict| {
+        format!(
+            "zone {zone} {verdict} {} record {fqdn} in namespace {namespace}",
+            record.type_()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::serde_json::{self, json};
+    use kube::core::admission::{AdmissionRequest, AdmissionReview};
+    use kubizone_crds::v1alpha1::Record;
+
+    use super::{WebhookContext, validate_record};
+
+    /// Update request of a record with invalid rdata, changing its labels
+    /// from `old_labels` to `labels`, and its rdata from `old_rdata` to `rdata`.
+    fn update(
+        (old_labels, labels): (serde_json::Value, serde_json::Value),
+        (old_rdata, rdata): (&str, &str),
+    ) -> AdmissionRequest<Record> {
+        let record = |labels, rdata| {
+            json!({
+                "apiVersion": "kubi.zone/v1alpha1",
+                "kind": "Record",
+                "metadata": { "name": "www", "namespace": "default", "labels": labels },
+                "spec": { "domainName": "www.example.org.", "type": "A", "rdata": rdata },
+            })
+        };
+
+        let review: AdmissionReview<Record> = serde_json::from_value(json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "kind": { "group": "kubi.zone", "version": "v1alpha1", "kind": "Record" },
+                "resource": { "group": "kubi.zone", "version": "v1alpha1", "resource": "records" },
+                "name": "www",
+                "namespace": "default",
+                "operation": "UPDATE",
+                "userInfo": {},
+                "object": record(labels, rdata),
+                "oldObject": record(old_labels, old_rdata),
+                "dryRun": false,
+            },
+        }))
+        .unwrap();
+
+        review.try_into().unwrap()
+    }
+
+    #[tokio::test]
+    async fn metadata_only_update() {
+        let context = WebhookContext {
+            adoption_cache: None,
+        };
+
+        // Records with invalid specs, such as ones created before the webhook
+        // was deployed, can still have their labels changed by the controllers.
+        let response = validate_record(
+            &context,
+            update(
+                (json!({}), json!({ "kubi.zone/parent-zone": "example-org" })),
+                ("not-an-address", "not-an-address"),
+            ),
+        )
+        .await;
+        assert!(response.allowed);
+
+        // Changes to the spec are still validated.
+        let response = validate_record(
+            &context,
+            update(
+                (json!({}), json!({})),
+                ("not-an-address", "still-not-an-address"),
+            ),
+        )
+        .await;
+        assert!(!response.allowed);
+    }
+}
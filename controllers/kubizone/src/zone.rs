@@ -1,30 +1,53 @@
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{BTreeMap, hash_map::DefaultHasher},
+    fmt::{Debug, Display},
+    future::Future,
     hash::{Hash, Hasher},
     sync::Arc,
     time::Duration,
 };
 
-use futures::StreamExt;
-use k8s_openapi::serde_json::json;
+use futures::{FutureExt, StreamExt};
+use k8s_openapi::{
+    api::core::v1::Namespace,
+    apimachinery::pkg::apis::meta::v1::{Condition, Time},
+    chrono::Utc,
+    serde::{Serialize, de::DeserializeOwned},
+    serde_json::json,
+};
 use kube::{
-    Api, Client, ResourceExt,
+    Api, Client, Resource, ResourceExt,
     api::{ListParams, Patch, PatchParams},
-    runtime::{Controller, controller::Action, watcher},
+    runtime::{
+        Controller,
+        controller::Action,
+        finalizer::{self, Event as Finalizer, finalizer},
+        reflector::ObjectRef,
+    },
 };
 use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Type};
 use kubizone_crds::{
     PARENT_ZONE_LABEL,
-    v1alpha1::{DomainExt as _, Record, Zone, ZoneEntry, ZoneSpec},
+    v1alpha1::{
+        ClusterZone, ConstraintViolation, DomainExt, Record, RecordExt, RecordSet, RecordVerdict,
+        Verdict, Zone, ZoneEntry, ZoneExt, ZoneKind, ZoneRef, ZoneSpec,
+    },
 };
 
 use tracing::log::*;
 
-use crate::{set_fqdn, set_parent, with_parent_zone};
+use crate::{
+    AnyZone, PENDING_ZONE_RETRY_TIME, Placement, ResourceApi, ZoneResource, cache::Cache,
+    dependents_of_zone, health::Health, metrics::Metrics, set_fqdn, set_parent, with_parent_zones,
+    zone_changes,
+};
 
 pub struct ZoneControllerContext {
     pub client: Client,
     pub requeue_time: Duration,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<Health>,
+    pub cache: Cache,
 }
 
 #[cfg(feature = "dev")]
@@ -32,80 +55,179 @@ const CONTROLLER_NAME: &str = "dev.kubi.zone/zone-resolver";
 #[cfg(not(feature = "dev"))]
 const CONTROLLER_NAME: &str = "kubi.zone/zone-resolver";
 
-pub async fn controller(context: ZoneControllerContext) {
-    let zones = Api::<Zone>::all(context.client.clone());
+/// Finalizer keeping deleted zones around until the resources
+/// they adopted have been detached from them.
+#[cfg(feature = "dev")]
+const ZONE_FINALIZER: &str = "dev.kubi.zone/detach-children";
+#[cfg(not(feature = "dev"))]
+const ZONE_FINALIZER: &str = "kubi.zone/detach-children";
 
-    let zone_controller = Controller::new(zones.clone(), watcher::Config::default())
-        .watches(
-            Api::<Zone>::all(context.client.clone()),
-            watcher::Config::default(),
-            with_parent_zone(),
-        )
-        .watches(
-            Api::<Record>::all(context.client.clone()),
-            watcher::Config::default(),
-            with_parent_zone(),
-        )
-        .shutdown_on_signal()
-        .run(reconcile_zones, zone_error_policy, Arc::new(context))
-        .for_each(|res| async move {
-            match res {
-                Ok(o) => info!("reconciled: {:?}", o),
-                Err(e) => warn!("reconciliation failed: {}", e),
-            }
-        });
+/// Run the controllers resolving [`Zone`]s and [`ClusterZone`]s.
+///
+/// The controllers subscribe to the cache right away, so this
+/// must be called before the cache is started.
+pub fn controller(context: ZoneControllerContext) -> impl Future<Output = ()> {
+    let context = Arc::new(context);
 
-    zone_controller.await;
-    warn!("zone controller exited");
+    futures::future::join(run::<Zone>(context.clone()), run::<ClusterZone>(context)).map(|_| ())
+}
+
+fn run<Z: ZoneResource>(context: Arc<ZoneControllerContext>) -> impl Future<Output = ()> {
+    let cache = &context.cache;
+    let triggers = cache.subscribe::<Z>();
+    let zones = cache.subscribe::<Zone>();
+    let cluster_zones = cache.subscribe::<ClusterZone>();
+    let zone_dependents = cache.subscribe::<Zone>();
+    let cluster_zone_dependents = cache.subscribe::<ClusterZone>();
+    let records = cache.subscribe::<Record>();
+    let record_sets = cache.subscribe::<RecordSet>();
+    let namespaces = cache.subscribe::<Namespace>();
+
+    async move {
+        let health = context.health.clone();
+        let name = Z::kind(&()).to_lowercase();
+        health.register(&name);
+
+        if !context.cache.wait_until_ready().await {
+            health.exited(&name);
+            warn!("{name} controller exited, since the cache stopped before synchronizing");
+            return;
+        }
+
+        let store = context.cache.store::<Z>();
+        let namespace_zones = store.clone();
+
+        let zone_controller = Controller::for_shared_stream(triggers, store.clone())
+            // Changes to a zone affect both the zones which might adopt it, and
+            // the zones depending on it through their zoneRef or fqdn. The latter
+            // only depend on its spec, labels and fqdn, not the rest of its status.
+            .watches_shared_stream(zones, with_parent_zones(context.cache.clone(), false))
+            .watches_shared_stream(
+                cluster_zones,
+                with_parent_zones(context.cache.clone(), false),
+            )
+            .watches_shared_stream(
+                zone_changes(zone_dependents),
+                dependents_of_zone(store.clone()),
+            )
+            .watches_shared_stream(
+                zone_changes(cluster_zone_dependents),
+                dependents_of_zone(store.clone()),
+            )
+            .watches_shared_stream(records, with_parent_zones(context.cache.clone(), true))
+            .watches_shared_stream(record_sets, with_parent_zones(context.cache.clone(), true))
+            // Namespace label changes might change which records and zones are covered
+            // by namespace selectors, so all zones using them must be reconciled, along
+            // with the zones within the namespace itself.
+            .watches_shared_stream(namespaces, move |namespace: Arc<Namespace>| {
+                namespace_zones
+                    .state()
+                    .into_iter()
+                    .filter(|zone| {
+                        zone.namespace() == namespace.metadata.name
+                            || zone
+                                .zone_spec()
+                                .delegations
+                                .iter()
+                                .any(|delegation| delegation.namespace_selector.is_some())
+                    })
+                    .map(|zone| ObjectRef::from_obj(&*zone))
+                    .collect::<Vec<_>>()
+            })
+            .shutdown_on_signal()
+            .run(
+                |zone, ctx| {
+                    let metrics = ctx.metrics.clone();
+                    async move {
+                        metrics
+                            .measure(&Z::kind(&()).to_lowercase(), reconcile_zones(zone, ctx))
+                            .await
+                    }
+                },
+                zone_error_policy,
+                context,
+            )
+            .for_each(|res| async move {
+                match res {
+                    Ok(o) => info!("reconciled: {:?}", o),
+                    Err(e) => warn!("reconciliation failed: {}", e),
+                }
+            });
+
+        futures::future::join(zone_controller, health.ready_when_synced(&name, store)).await;
+        health.exited(&name);
+        warn!("{name} controller exited");
+    }
 }
 
 #[tracing::instrument(name = "zone", skip_all)]
-async fn reconcile_zones(
-    zone: Arc<Zone>,
+async fn reconcile_zones<Z: ZoneResource>(
+    zone: Arc<Z>,
     ctx: Arc<ZoneControllerContext>,
+) -> Result<Action, finalizer::Error<kube::Error>> {
+    let api = Z::api(ctx.client.clone(), &zone);
+
+    finalizer(&api, ZONE_FINALIZER, zone, |event| async {
+        match event {
+            Finalizer::Apply(zone) => apply_zone(zone, &ctx).await,
+            Finalizer::Cleanup(zone) => detach_children(zone.as_ref(), &ctx).await,
+        }
+    })
+    .await
+}
+
+async fn apply_zone<Z: ZoneResource>(
+    zone: Arc<Z>,
+    ctx: &ZoneControllerContext,
 ) -> Result<Action, kube::Error> {
-    match (zone.spec.zone_ref.as_ref(), &zone.spec.domain_name) {
+    match zone.placement() {
         (Some(zone_ref), DomainName::Partial(partial_domain)) => {
-            // Follow the zoneRef to the supposed parent zone, if it exists
-            // or requeue later if it does not.
-            let Some(parent_zone) = Api::<Zone>::namespaced(
-                ctx.client.clone(),
-                &zone_ref
-                    .namespace
-                    .as_ref()
-                    .or(zone.namespace().as_ref())
-                    .cloned()
-                    .unwrap(),
-            )
-            .get_opt(&zone_ref.name)
-            .await?
-            else {
+            // Follow the zoneRef to the supposed parent zone, if it exists.
+            // Otherwise, the zone is reconciled again once the parent is created.
+            let Some(parent_zone) = ctx.cache.zone(zone_ref, zone.namespace().as_deref()) else {
                 warn!("zone {zone} references unknown zone {zone_ref}");
-                return Ok(Action::requeue(Duration::from_secs(30)));
+                return Ok(Action::requeue(PENDING_ZONE_RETRY_TIME));
             };
 
+            if !can_adopt::<Z>(&parent_zone) {
+                warn!(
+                    "zone {zone} references zone {parent_zone}, but cluster zones can only be adopted by other cluster zones"
+                );
+                set_parent(CONTROLLER_NAME, ctx.client.clone(), &zone, None).await?;
+                return Ok(Action::requeue(ctx.requeue_time));
+            }
+
             // If the parent does not have a fully qualified domain name defined
             // yet, we can't check if the delegations provided by it are valid.
-            // Postpone the reconcilliation until a later time, when the fqdn
-            // has (hopefully) been determined.
+            // The zone is reconciled again once the parent's fqdn is set.
             let Some(parent_fqdn) = parent_zone.fqdn() else {
-                info!(
-                    "parent zone {} missing fqdn, requeuing.",
-                    parent_zone.name_any()
-                );
-                return Ok(Action::requeue(Duration::from_secs(5)));
+                info!("parent zone {parent_zone} missing fqdn, postponing.");
+                return Ok(Action::requeue(PENDING_ZONE_RETRY_TIME));
             };
 
+            // Zones which lost the claim to their fqdn to another zone cannot adopt anything.
+            if let Some(owner) = ctx.cache.claimed_by(&parent_zone, parent_fqdn) {
+                warn!(
+                    "zone {zone} references zone {parent_zone}, but its fqdn {parent_fqdn} has already been claimed by zone {owner}"
+                );
+                set_parent(CONTROLLER_NAME, ctx.client.clone(), &zone, None).await?;
+                return Ok(Action::requeue(ctx.requeue_time));
+            }
+
             // This is only "alleged", since we don't know yet if the referenced
             // zone's delegations allow the adoption.
             let alleged_fqdn: FullyQualifiedDomainName = partial_domain.with_origin(parent_fqdn);
 
             trace!("zone alleged fqdn: {partial_domain} + {parent_fqdn} = {alleged_fqdn}");
 
-            if parent_zone.spec.delegations.iter().any(|delegation| {
-                delegation.covers_namespace(zone.namespace().as_deref().unwrap())
-                    && delegation.validate_zone(parent_fqdn, &alleged_fqdn)
-            }) {
+            // Cluster zones have no namespace, and are therefore
+            // only covered by delegations covering all namespaces.
+            let namespace = zone.namespace().unwrap_or_default();
+            let labels = ctx.cache.namespace_labels(&namespace);
+
+            let verdict = parent_zone.evaluate_zone(&namespace, &labels, &alleged_fqdn);
+
+            if verdict.is_allowed() {
                 set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &zone, &alleged_fqdn).await?;
                 set_parent(
                     CONTROLLER_NAME,
@@ -116,7 +238,7 @@ async fn reconcile_zones(
                 .await?;
             } else {
                 warn!(
-                    "parent zone {parent_zone} was found, but its delegations do not allow adoption of {zone} with {alleged_fqdn}"
+                    "parent zone {parent_zone} was found, but its delegations do not allow adoption of {zone} with {alleged_fqdn}: {verdict}"
                 );
                 return Ok(Action::requeue(ctx.requeue_time));
             }
@@ -124,23 +246,19 @@ async fn reconcile_zones(
         (None, DomainName::Full(fqdn)) => {
             set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &zone, fqdn).await?;
 
-            // Fetch all zones from across the cluster and then filter down results to only parent
-            // zones which are valid parent zones for this one.
-            //
-            // This means filtering out parent zones without fqdns, as well as ones which do not
-            // have appropriate delegations for our `zone`'s namespace and suffix.
-            if let Some(longest_parent_zone) = Api::<Zone>::all(ctx.client.clone())
-                .list(&ListParams::default())
-                .await?
-                .into_iter()
-                .filter(|parent| {
-                    parent
-                        .fqdn()
-                        .is_some_and(|parent_fqdn| fqdn.is_subdomain_of(parent_fqdn))
-                })
-                .max_by_key(|parent| parent.fqdn().unwrap().as_ref().len())
-            {
-                if longest_parent_zone.validate_zone(&zone) {
+            // Look up the most immediate parent zone of this one, and check
+            // whether its delegations allow our `zone`'s namespace and suffix.
+            if let Some(longest_parent_zone) = ctx.cache.parent_zone(fqdn, false) {
+                let labels = ctx
+                    .cache
+                    .namespace_labels(&zone.namespace().unwrap_or_default());
+
+                if !can_adopt::<Z>(&longest_parent_zone) {
+                    warn!(
+                        "{longest_parent_zone} is the most immediate parent zone of {zone}, but cluster zones can only be adopted by other cluster zones."
+                    );
+                    set_parent(CONTROLLER_NAME, ctx.client.clone(), &zone, None).await?;
+                } else if longest_parent_zone.validate_zone(zone.as_ref(), &labels) {
                     set_parent(
                         CONTROLLER_NAME,
                         ctx.client.clone(),
@@ -155,9 +273,8 @@ async fn reconcile_zones(
                 }
             } else {
                 info!(
-                    "zone {} ({}) does not fit into any found parent zone. If this is a top level zone, then this is expected.",
-                    zone.name_any(),
-                    &zone.spec.domain_name
+                    "zone {zone} ({}) does not fit into any found parent zone. If this is a top level zone, then this is expected.",
+                    zone.zone_spec().domain_name
                 );
             };
         }
@@ -175,59 +292,246 @@ async fn reconcile_zones(
         }
     }
 
-    update_zone_status(zone, ctx.client.clone()).await?;
+    update_zone_status(zone, ctx.client.clone(), &ctx.cache, &ctx.metrics).await?;
     Ok(Action::requeue(ctx.requeue_time))
 }
 
-async fn update_zone_status(zone: Arc<Zone>, client: Client) -> Result<(), kube::Error> {
+/// Move all resources adopted by `zone` to the next most immediate parent
+/// zone, if any, before the zone itself is deleted.
+///
+/// Resources are moved as part of the finalizer, rather than left to be adopted
+/// again once detached, so they never go without a parent in the meantime. Those
+/// not allowed by the next parent zone's delegations are detached instead.
+///
+/// Other finalizers are left untouched, so providers publishing the zone
+/// can add their own to withdraw it before it disappears.
+async fn detach_children<Z: ZoneResource>(
+    zone: &Z,
+    ctx: &ZoneControllerContext,
+) -> Result<Action, kube::Error> {
+    let zone_ref = zone.zone_ref();
+
+    info!("zone {zone} is being deleted, moving its records and zones to the next parent zone.");
+    detach::<Record>(ctx, &zone_ref, |record| {
+        next_record_parent(&ctx.cache, record)
+    })
+    .await?;
+    detach::<RecordSet>(ctx, &zone_ref, |record_set| {
+        next_record_parent(&ctx.cache, record_set)
+    })
+    .await?;
+    detach::<Zone>(ctx, &zone_ref, |zone| next_zone_parent(&ctx.cache, zone)).await?;
+    detach::<ClusterZone>(ctx, &zone_ref, |zone| next_zone_parent(&ctx.cache, zone)).await?;
+
+    ctx.metrics
+        .forget_zone(&zone.namespace().unwrap_or_default(), &zone.name_any());
+
+    Ok(Action::await_change())
+}
+
+/// Set the parent zone of all resources of kind `R` adopted by `zone_ref` to `next_parent`.
+///
+/// Resources are selected by both the hashed and the legacy form of the parent zone
+/// label, and then filtered by their parsed parent, since a legacy label might
+/// belong to a different zone with a dotted name.
+async fn detach<R>(
+    ctx: &ZoneControllerContext,
+    zone_ref: &ZoneRef,
+    next_parent: impl Fn(&R) -> Option<ZoneRef>,
+) -> Result<(), kube::Error>
+where
+    R: ResourceApi + DomainExt + Clone + Debug + DeserializeOwned + Serialize,
+{
+    let labels: Vec<String> = std::iter::once(zone_ref.as_label())
+        .chain(zone_ref.as_legacy_label())
+        .collect();
+    let labelled =
+        ListParams::default().labels(&format!("{PARENT_ZONE_LABEL} in ({})", labels.join(",")));
+
+    for child in Api::<R>::all(ctx.client.clone()).list(&labelled).await? {
+        if child.parent().as_ref() == Some(zone_ref) {
+            // The cache might not have dropped the deleted zone from its index yet.
+            let parent = next_parent(&child).filter(|parent| parent != zone_ref);
+
+            set_parent(
+                CONTROLLER_NAME,
+                ctx.client.clone(),
+                &Arc::new(child),
+                parent,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Most immediate parent zone of `record`, if its delegations allow it.
+///
+/// Records referencing a zone through their zoneRef have no other parent.
+fn next_record_parent<R>(cache: &Cache, record: &R) -> Option<ZoneRef>
+where
+    R: Resource + RecordExt + Placement,
+{
+    let (None, DomainName::Full(fqdn)) = record.placement() else {
+        return None;
+    };
+
+    let namespace = record.namespace().unwrap_or_default();
+    let parent = cache.parent_zone(fqdn, true)?;
+
+    parent
+        .evaluate_record(
+            &namespace,
+            &cache.namespace_labels(&namespace),
+            record.type_(),
+            fqdn,
+        )
+        .is_allowed()
+        .then(|| parent.zone_ref())
+}
+
+/// Most immediate parent zone of `zone`, if it can adopt it, and its delegations allow it.
+///
+/// Zones referencing another zone through their zoneRef have no other parent.
+fn next_zone_parent<Z: ZoneResource>(cache: &Cache, zone: &Z) -> Option<ZoneRef> {
+    let (None, DomainName::Full(fqdn)) = zone.placement() else {
+        return None;
+    };
+
+    let labels = cache.namespace_labels(&zone.namespace().unwrap_or_default());
+    let parent = cache.parent_zone(fqdn, false)?;
+
+    (can_adopt::<Z>(&parent) && parent.validate_zone(zone, &labels)).then(|| parent.zone_ref())
+}
+
+/// Whether zones of kind `Z` can be adopted by `parent`.
+///
+/// Cluster zones are visible to all namespaces, and can therefore
+/// not be adopted by zones confined to a single namespace.
+fn can_adopt<Z: ZoneResource>(parent: &AnyZone) -> bool {
+    Z::KIND == ZoneKind::Zone || matches!(parent, AnyZone::ClusterZone(_))
+}
+
+/// Maximum number of records listed in the `verdicts` of a zone's status.
+const MAX_VERDICTS: usize = 100;
+
+async fn update_zone_status<Z: ZoneResource>(
+    zone: Arc<Z>,
+    client: Client,
+    cache: &Cache,
+    metrics: &Metrics,
+) -> Result<(), kube::Error> {
     let Some(origin) = zone.fqdn() else {
         return Ok(());
     };
 
     // Reference to this zone, which other zones and records will use to refer to it by.
-    let zone_ref = ListParams::default().labels(&format!(
-        "{PARENT_ZONE_LABEL}={}",
-        zone.zone_ref().as_label()
-    ));
+    let zone_ref = zone.zone_ref();
 
-    let mut entries = Vec::new();
+    // Used for determining whether this zone is the most immediate parent of
+    // records not adopted by any zone.
+    let is_most_immediate_parent = |fqdn: &FullyQualifiedDomainName| {
+        cache
+            .parent_zone(fqdn, true)
+            .is_some_and(|parent| parent.zone_ref() == zone_ref)
+    };
+
+    // If another zone claimed the same fqdn first, this one must not adopt
+    // or publish any records, which are left to the other zone instead.
+    let claimed_by = cache.claimed_by(&zone.clone().into_any(), origin);
+    if let Some(owner) = &claimed_by {
+        warn!("zone {zone}'s fqdn {origin} has already been claimed by zone {owner}");
+    }
+
+    let conditions = conflict_conditions(zone.as_ref(), origin, claimed_by.as_ref());
 
-    // Insert all child records into the entries list
-    for record in Api::<Record>::all(client.clone())
-        .list(&zone_ref)
-        .await?
+    // Collect all records and record sets which have been adopted by this zone, or which are attempting to be.
+    let candidates: Vec<Candidate> = cache
+        .records()
         .into_iter()
-    {
-        if !zone.validate_record(&record) {
-            warn!(
-                "record {record} has {zone} configured as its parent, but the zone does not allow this delegation, action could be malicious."
-            );
-            continue;
+        .filter_map(|record| {
+            candidate(
+                zone.as_ref(),
+                &zone_ref,
+                record.as_ref(),
+                cache,
+                &is_most_immediate_parent,
+            )
+        })
+        .chain(cache.record_sets().into_iter().filter_map(|record_set| {
+            candidate(
+                zone.as_ref(),
+                &zone_ref,
+                record_set.as_ref(),
+                cache,
+                &is_most_immediate_parent,
+            )
+        }))
+        .filter(|_| claimed_by.is_none())
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut record_verdicts = Vec::new();
+    let mut allowed_records = 0;
+    let mut rejected_records = 0;
+
+    for (candidate, verdict) in evaluate_candidates(zone.as_ref(), candidates) {
+        if verdict.is_allowed() {
+            allowed_records += 1;
+        } else {
+            rejected_records += 1;
         }
 
-        entries.push(ZoneEntry {
-            fqdn: record.fqdn().unwrap().clone(), // Unwrap safe since fqdn presence is checked in validate_record
-            type_: record.spec.type_,
-            class: record.spec.class,
-            ttl: record.spec.ttl.unwrap_or(zone.spec.ttl),
-            rdata: record.spec.rdata,
-        })
+        if candidate.adopted {
+            if let Some(conditions) = admitted_conditions(&candidate, &verdict) {
+                set_candidate_conditions(client.clone(), &candidate, conditions).await?;
+            }
+
+            if verdict.is_allowed() {
+                entries.extend(candidate.entries.iter().cloned());
+            } else {
+                warn!(
+                    "{} {} has {zone} configured as its parent, but the zone does not allow it: {verdict}",
+                    candidate.kind.to_lowercase(),
+                    candidate.name
+                );
+            }
+        }
+
+        record_verdicts.push((
+            verdict.is_allowed(),
+            RecordVerdict {
+                kind: candidate.kind,
+                record: candidate.name,
+                fqdn: candidate.fqdn,
+                type_: candidate.type_,
+                effect: verdict.effect,
+                rule: verdict.rule,
+                reason: verdict.violation.map(|violation| violation.to_string()),
+            },
+        ));
     }
 
-    let mut hasher = DefaultHasher::new();
-    (&zone.spec, &entries).hash(&mut hasher);
-    let new_hash = hasher.finish().to_string();
+    // Denied records are listed first, since they are the ones needing attention,
+    // and the list is truncated to keep the status of large zones small.
+    record_verdicts.sort_by_key(|(allowed, _)| *allowed);
+    let record_verdicts: Vec<RecordVerdict> = record_verdicts
+        .into_iter()
+        .take(MAX_VERDICTS)
+        .map(|(_, verdict)| verdict)
+        .collect();
 
-    let current_hash = zone.status.as_ref().and_then(|status| status.hash.as_ref());
+    let new_hash = zone_hash(zone.zone_spec(), &entries);
 
-    let last_serial = zone
-        .status
-        .as_ref()
-        .and_then(|status| status.serial)
-        .unwrap_or_default();
+    let current_hash = zone.hash();
+
+    let last_serial = zone.serial().unwrap_or_default();
+
+    let changed = current_hash != Some(new_hash.as_str());
 
     // If the hash changed, we need to update the serial.
-    let serial = if current_hash != Some(&new_hash) {
+    let serial = if changed {
         info!(
             "zone {zone}'s hash changed (before: {current_hash:?}, now: {new_hash}), updating serial."
         );
@@ -255,7 +559,7 @@ async fn update_zone_status(zone: Arc<Zone>, client: Client) -> Result<(), kube:
         expire,
         negative_response_cache,
         ..
-    } = zone.spec;
+    } = *zone.zone_spec();
 
     entries.insert(0, ZoneEntry {
         fqdn: origin.clone(),
@@ -265,7 +569,7 @@ async fn update_zone_status(zone: Arc<Zone>, client: Client) -> Result<(), kube:
         rdata: format!("ns.{origin} noc.{origin} ({serial} {refresh} {retry} {expire} {negative_response_cache})"),
     });
 
-    Api::<Zone>::namespaced(client, zone.namespace().as_ref().unwrap())
+    Z::api(client, &zone)
         .patch_status(
             &zone.name_any(),
             &PatchParams::apply(CONTROLLER_NAME),
@@ -273,23 +577,385 @@ async fn update_zone_status(zone: Arc<Zone>, client: Client) -> Result<(), kube:
                 "status": {
                     "hash": new_hash,
                     "entries": entries,
-                    "serial": Some(serial)
+                    "serial": Some(serial),
+                    "verdicts": record_verdicts,
+                    "allowedRecords": allowed_records,
+                    "deniedRecords": rejected_records,
+                    "conditions": conditions,
                 },
             })),
         )
         .await?;
 
+    metrics.observe_zone(
+        &zone.namespace().unwrap_or_default(),
+        &zone.name_any(),
+        entries.len(),
+        serial,
+        rejected_records,
+        changed,
+    );
+
     Ok(())
 }
 
-fn zone_error_policy(
-    zone: Arc<Zone>,
-    error: &kube::Error,
-    _ctx: Arc<ZoneControllerContext>,
+/// Condition reporting whether another zone has already claimed the zone's fqdn.
+const CONFLICT_CONDITION: &str = "Conflict";
+
+/// Conditions of `zone`, with the [`CONFLICT_CONDITION`] reflecting whether
+/// `origin` has already been claimed by another zone.
+///
+/// The transition time of the condition is kept, unless its status changes.
+fn conflict_conditions<Z: ZoneResource>(
+    zone: &Z,
+    origin: &FullyQualifiedDomainName,
+    claimed_by: Option<&AnyZone>,
+) -> Vec<Condition> {
+    let mut conditions = zone
+        .zone_status()
+        .map(|status| status.conditions.clone())
+        .unwrap_or_default();
+
+    let (status, reason, message) = match claimed_by {
+        Some(owner) => (
+            "True",
+            "FqdnAlreadyClaimed",
+            format!("{origin} has already been claimed by zone {owner}, which was created first"),
+        ),
+        None => (
+            "False",
+            "FqdnClaimed",
+            format!("{origin} is claimed by this zone"),
+        ),
+    };
+
+    let last_transition_time = conditions
+        .iter()
+        .find(|condition| condition.type_ == CONFLICT_CONDITION && condition.status == status)
+        .map(|condition| condition.last_transition_time.clone())
+        .unwrap_or_else(|| Time(Utc::now()));
+
+    conditions.retain(|condition| condition.type_ != CONFLICT_CONDITION);
+    conditions.push(Condition {
+        type_: CONFLICT_CONDITION.to_string(),
+        status: status.to_string(),
+        reason: reason.to_string(),
+        message,
+        last_transition_time,
+        observed_generation: zone.meta().generation,
+    });
+
+    conditions
+}
+
+/// Record or record set which has been adopted by a zone, or is attempting to be.
+struct Candidate {
+    kind: String,
+    /// Name of the record, in `namespace/name` form.
+    name: String,
+    /// Name of the record object itself.
+    object_name: String,
+    namespace: String,
+    generation: Option<i64>,
+    conditions: Vec<Condition>,
+    labels: BTreeMap<String, String>,
+    fqdn: FullyQualifiedDomainName,
+    type_: Type,
+    entries: Vec<ZoneEntry>,
+    adopted: bool,
+}
+
+/// Evaluate the delegations of `zone` and their constraints for the entries of all
+/// `candidates`, producing a single verdict for each candidate with any entries.
+///
+/// The first denying verdict decides for the whole candidate, so record
+/// sets are never partially inserted into a zone.
+fn evaluate_candidates(
+    zone: &impl ZoneExt,
+    mut candidates: Vec<Candidate>,
+) -> Vec<(Candidate, Verdict)> {
+    // The order of the cache is arbitrary, but decides which records exceed the quotas
+    // of delegations, so candidates are evaluated in order of kind, namespace and name.
+    candidates.sort_by(|a, b| {
+        (&a.kind, &a.namespace, &a.object_name).cmp(&(&b.kind, &b.namespace, &b.object_name))
+    });
+
+    let verdicts = zone.evaluate_entries(candidates.iter().map(|candidate| {
+        (
+            candidate.namespace.as_str(),
+            &candidate.labels,
+            candidate.entries.as_slice(),
+        )
+    }));
+
+    candidates
+        .into_iter()
+        .zip(verdicts)
+        .filter_map(|(candidate, verdicts)| {
+            let verdict = verdicts
+                .iter()
+                .find(|verdict| !verdict.is_allowed())
+                .or(verdicts.first())
+                .cloned()?;
+
+            Some((candidate, verdict))
+        })
+        .collect()
+}
+
+/// Hash of the spec and entries of a zone, which changes whenever its serial should.
+fn zone_hash(spec: &ZoneSpec, entries: &[ZoneEntry]) -> String {
+    let mut hasher = DefaultHasher::new();
+    (spec, entries).hash(&mut hasher);
+    hasher.finish().to_string()
+}
+
+/// Determine if `record` is adopted by `zone`, or attempting to be, and if so
+/// produce the zone entries it would contribute.
+fn candidate<R>(
+    zone: &impl ZoneExt,
+    zone_ref: &ZoneRef,
+    record: &R,
+    cache: &Cache,
+    is_most_immediate_parent: &impl Fn(&FullyQualifiedDomainName) -> bool,
+) -> Option<Candidate>
+where
+    R: Resource<DynamicType = ()> + RecordExt + Display,
+{
+    // Unwrap safety: Zones always have a fqdn by the time candidates are collected.
+    let origin = zone.fqdn().unwrap();
+    let namespace = record.namespace().unwrap_or_default();
+    let adopted = record.parent().as_ref() == Some(zone_ref);
+
+    // Domain name of the record, or the one it would have, were it adopted.
+    let fqdn = match (record.zone_ref(), record.domain_name()) {
+        _ if adopted => record.fqdn().cloned(),
+        (Some(record_zone_ref), DomainName::Partial(partial))
+            if record_zone_ref.in_namespace(Some(&namespace)) == *zone_ref =>
+        {
+            Some(partial.with_origin(origin))
+        }
+        (None, DomainName::Full(fqdn))
+            if record.parent().is_none() && is_most_immediate_parent(fqdn) =>
+        {
+            Some(fqdn.clone())
+        }
+        _ => None,
+    };
+
+    let Some(fqdn) = fqdn else {
+        if adopted {
+            warn!(
+                "{} {record} has {zone} configured as its parent, but has no fqdn.",
+                R::kind(&())
+            );
+        }
+        return None;
+    };
+
+    Some(Candidate {
+        kind: R::kind(&()).to_string(),
+        name: record.to_string(),
+        object_name: record.name_any(),
+        generation: record.meta().generation,
+        conditions: record.conditions().to_vec(),
+        labels: cache.namespace_labels(&namespace),
+        namespace,
+        type_: record.type_(),
+        entries: record.entries(&fqdn, zone.zone_spec().ttl),
+        fqdn,
+        adopted,
+    })
+}
+
+const ADMITTED_CONDITION: &str = "Admitted";
+
+/// Conditions of `candidate`, with the [`ADMITTED_CONDITION`] reflecting `verdict`,
+/// or `None` if the condition is already up to date.
+///
+/// The transition time of the condition is kept, unless its status changes.
+fn admitted_conditions(candidate: &Candidate, verdict: &Verdict) -> Option<Vec<Condition>> {
+    let (status, reason) = match &verdict.violation {
+        _ if verdict.is_allowed() => ("True", "Allowed"),
+        Some(ConstraintViolation::QuotaExceeded(_)) => ("False", "QuotaExceeded"),
+        Some(_) => ("False", "ConstraintViolated"),
+        None => ("False", "Denied"),
+    };
+    let message = verdict.to_string();
+
+    let current = candidate
+        .conditions
+        .iter()
+        .find(|condition| condition.type_ == ADMITTED_CONDITION);
+
+    if current.is_some_and(|condition| {
+        condition.status == status
+            && condition.reason == reason
+            && condition.message == message
+            && condition.observed_generation == candidate.generation
+    }) {
+        return None;
+    }
+
+    let last_transition_time = current
+        .filter(|condition| condition.status == status)
+        .map(|condition| condition.last_transition_time.clone())
+        .unwrap_or_else(|| Time(Utc::now()));
+
+    let mut conditions = candidate.conditions.clone();
+    conditions.retain(|condition| condition.type_ != ADMITTED_CONDITION);
+    conditions.push(Condition {
+        type_: ADMITTED_CONDITION.to_string(),
+        status: status.to_string(),
+        reason: reason.to_string(),
+        message,
+        last_transition_time,
+        observed_generation: candidate.generation,
+    });
+
+    Some(conditions)
+}
+
+/// Replace the status conditions of the record or record set behind `candidate`.
+async fn set_candidate_conditions(
+    client: Client,
+    candidate: &Candidate,
+    conditions: Vec<Condition>,
+) -> Result<(), kube::Error> {
+    let patch = Patch::Merge(json!({
+        "status": {
+            "conditions": conditions,
+        },
+    }));
+    let params = PatchParams::apply(CONTROLLER_NAME);
+
+    if candidate.kind == RecordSet::kind(&()) {
+        Api::<RecordSet>::namespaced(client, &candidate.namespace)
+            .patch_status(&candidate.object_name, &params, &patch)
+            .await?;
+    } else {
+        Api::<Record>::namespaced(client, &candidate.namespace)
+            .patch_status(&candidate.object_name, &params, &patch)
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn zone_error_policy<Z: Resource<DynamicType = ()>>(
+    zone: Arc<Z>,
+    error: &finalizer::Error<kube::Error>,
+    ctx: Arc<ZoneControllerContext>,
 ) -> Action {
     error!(
         "zone {} reconciliation encountered error: {error}",
         zone.name_any()
     );
+    ctx.metrics.requeued(&Z::kind(&()).to_lowercase());
     Action::requeue(Duration::from_secs(60))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Pattern, Type};
+    use kubizone_crds::v1alpha1::{
+        Delegation, RecordDelegation, Zone, ZoneEntry, ZoneExt as _, ZoneSpec, ZoneStatus,
+    };
+
+    use super::{Candidate, evaluate_candidates, zone_hash};
+
+    fn candidate(kind: &str, namespace: &str, name: &str, hosts: &[&str]) -> Candidate {
+        let fqdn = FullyQualifiedDomainName::try_from(hosts[0]).unwrap();
+
+        Candidate {
+            kind: kind.to_string(),
+            name: format!("{namespace}/{name}"),
+            object_name: name.to_string(),
+            namespace: namespace.to_string(),
+            generation: Some(1),
+            conditions: vec![],
+            labels: BTreeMap::new(),
+            type_: Type::A,
+            entries: hosts
+                .iter()
+                .enumerate()
+                .map(|(i, host)| ZoneEntry {
+                    fqdn: FullyQualifiedDomainName::try_from(*host).unwrap(),
+                    type_: Type::A,
+                    class: Class::IN,
+                    ttl: 300,
+                    rdata: format!("192.0.2.{i}"),
+                })
+                .collect(),
+            fqdn,
+            adopted: true,
+        }
+    }
+
+    fn candidates() -> Vec<Candidate> {
+        vec![
+            candidate("Record", "team-a", "www", &["www.example.org."]),
+            candidate("Record", "team-b", "api", &["api.example.org."]),
+            candidate(
+                "RecordSet",
+                "team-a",
+                "mail",
+                &["mail.example.org.", "mail.example.org."],
+            ),
+            candidate("Record", "team-a", "blog", &["blog.example.org."]),
+        ]
+    }
+
+    /// Names of the admitted candidates, and hash of the zone with their entries.
+    fn admit(zone: &Zone, candidates: Vec<Candidate>) -> (Vec<String>, String) {
+        let mut admitted = Vec::new();
+        let mut entries = Vec::new();
+
+        for (candidate, verdict) in evaluate_candidates(zone, candidates) {
+            if verdict.is_allowed() {
+                admitted.push(candidate.name);
+                entries.extend(candidate.entries);
+            }
+        }
+
+        (admitted, zone_hash(zone.zone_spec(), &entries))
+    }
+
+    #[test]
+    fn candidate_order() {
+        // Not all candidates fit within the quota, so the order
+        // of evaluation decides which ones are admitted.
+        let zone = Zone {
+            spec: ZoneSpec {
+                domain_name: DomainName::try_from("example.org.").unwrap(),
+                delegations: vec![Delegation {
+                    records: vec![RecordDelegation {
+                        pattern: Pattern::try_from("*").unwrap(),
+                        ..Default::default()
+                    }],
+                    max_records: Some(3),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            status: Some(ZoneStatus {
+                fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap()),
+                ..Default::default()
+            }),
+            metadata: Default::default(),
+        };
+
+        let (admitted, hash) = admit(&zone, candidates());
+        assert_eq!(admitted, ["team-a/blog", "team-a/www", "team-b/api"]);
+
+        let mut reversed = candidates();
+        reversed.reverse();
+        assert_eq!(admit(&zone, reversed), (admitted.clone(), hash.clone()));
+
+        let mut rotated = candidates();
+        rotated.rotate_left(1);
+        assert_eq!(admit(&zone, rotated), (admitted, hash));
+    }
+}
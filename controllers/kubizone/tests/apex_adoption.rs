@@ -0,0 +1,54 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-apex-adoption").await.unwrap();
+
+            let org = ctx
+                .zone("kubizone-apex-adoption", "org", "org.", &[allow_all()])
+                .await
+                .unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-apex-adoption",
+                    "example-org",
+                    "example.org.",
+                    &[allow_all()],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&example_org, &[has_fqdn(), has_parent(&org)])
+                .await
+                .unwrap();
+
+            let apex = ctx
+                .a_record("kubizone-apex-adoption", "example-org", "example.org.")
+                .await
+                .unwrap();
+
+            ctx.wait_for(&apex, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&example_org, &[has_entry("example.org.")])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&org, &[not(has_entry("example.org."))])
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::Delegation;
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-cluster-zone-team-a").await.unwrap();
+            ctx.namespace("kubizone-cluster-zone-team-b").await.unwrap();
+            ctx.namespace("kubizone-cluster-zone-other").await.unwrap();
+
+            let example_org = ctx
+                .cluster_zone(
+                    "kubizone-cluster-zone-example-org",
+                    "example.org.",
+                    &[Delegation {
+                        namespaces: vec![
+                            "kubizone-cluster-zone-team-a".to_string(),
+                            "kubizone-cluster-zone-team-b".to_string(),
+                        ],
+                        zones: vec![Pattern::try_from("team-a").unwrap()],
+                        ..allow_all()
+                    }],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&example_org, &[has_fqdn(), has_serial()])
+                .await
+                .unwrap();
+
+            // Records from each of the delegated namespaces are adopted.
+            let a_example_org = ctx
+                .a_record(
+                    "kubizone-cluster-zone-team-a",
+                    "a-example-org",
+                    "a.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let b_example_org = ctx
+                .a_record(
+                    "kubizone-cluster-zone-team-b",
+                    "b-example-org",
+                    "b.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let other_example_org = ctx
+                .a_record(
+                    "kubizone-cluster-zone-other",
+                    "other-example-org",
+                    "other.example.org.",
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&a_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&b_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &other_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[
+                    has_entry("a.example.org."),
+                    has_entry("b.example.org."),
+                    not(has_entry("other.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+
+            // Namespaced zones can be adopted by cluster zones as well.
+            let team_a = ctx
+                .zone(
+                    "kubizone-cluster-zone-team-a",
+                    "team-a-example-org",
+                    "team-a.example.org.",
+                    &[],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&team_a, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+}
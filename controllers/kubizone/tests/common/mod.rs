@@ -2,21 +2,26 @@ use std::{sync::Arc, time::Duration};
 
 use futures::Future;
 use k8s_openapi::{
-    NamespaceResourceScope, api::core::v1::Namespace,
+    api::core::v1::Namespace,
     apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
-    serde::de::DeserializeOwned,
+    serde::de::DeserializeOwned, serde_json::json,
 };
 use kube::{
     Api, Client, CustomResourceExt, Resource, ResourceExt,
-    api::{DeleteParams, ObjectMeta, PostParams},
+    api::{DeleteParams, ObjectMeta, Patch, PatchParams, PostParams},
     runtime::{
         conditions,
         wait::{Condition, await_condition},
     },
 };
-use kubizone::{record::RecordControllerContext, zone::ZoneControllerContext};
-use kubizone_common::{DomainName, Type};
-use kubizone_crds::v1alpha1::{Delegation, DomainExt, Record, RecordSpec, Zone, ZoneSpec};
+use kubizone::{
+    ResourceApi, cache::Cache, record::RecordControllerContext, zone::ZoneControllerContext,
+};
+use kubizone_common::{DomainName, Pattern, Type};
+use kubizone_crds::v1alpha1::{
+    ClusterZone, ClusterZoneSpec, Delegation, DomainExt, Record, RecordDelegation, RecordSet,
+    RecordSetSpec, RecordSpec, Zone, ZoneExt, ZoneSpec,
+};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info};
 
@@ -24,7 +29,9 @@ use tracing::{debug, error, info};
 pub struct ContextInner {
     namespaces: Vec<Namespace>,
     records: Vec<Record>,
+    record_sets: Vec<RecordSet>,
     zones: Vec<Zone>,
+    cluster_zones: Vec<ClusterZone>,
     client: Client,
 }
 
@@ -36,6 +43,14 @@ pub struct Context {
 #[allow(dead_code)]
 impl Context {
     pub async fn namespace(&self, name: &str) -> Result<Namespace, kube::Error> {
+        self.labelled_namespace(name, &[]).await
+    }
+
+    pub async fn labelled_namespace(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+    ) -> Result<Namespace, kube::Error> {
         let api = Api::<Namespace>::all(self.inner.read().await.client.clone());
 
         // Try deleting it, in case it already exists.
@@ -47,6 +62,12 @@ impl Context {
                 &Namespace {
                     metadata: ObjectMeta {
                         name: Some(name.to_string()),
+                        labels: Some(
+                            labels
+                                .iter()
+                                .map(|(key, value)| (key.to_string(), value.to_string()))
+                                .collect(),
+                        ),
                         ..Default::default()
                     },
                     ..Default::default()
@@ -102,6 +123,45 @@ impl Context {
         self.record(namespace, name, fqdn, Type::A).await
     }
 
+    pub async fn record_set(
+        &self,
+        namespace: &str,
+        name: &str,
+        fqdn: &str,
+        type_: Type,
+        rdata: &[&str],
+    ) -> Result<RecordSet, kube::Error> {
+        let api = Api::<RecordSet>::namespaced(self.inner.read().await.client.clone(), namespace);
+
+        let record_set = api
+            .create(
+                &PostParams::default(),
+                &RecordSet {
+                    metadata: ObjectMeta {
+                        name: Some(name.to_string()),
+                        ..Default::default()
+                    },
+                    spec: RecordSetSpec {
+                        domain_name: DomainName::try_from(fqdn).unwrap(),
+                        zone_ref: None,
+                        type_,
+                        class: kubizone_common::Class::IN,
+                        ttl: None,
+                        rdata: rdata.iter().map(|rdata| rdata.to_string()).collect(),
+                    },
+                    status: None,
+                },
+            )
+            .await?;
+
+        self.inner
+            .write()
+            .await
+            .record_sets
+            .push(record_set.clone());
+        Ok(record_set)
+    }
+
     pub async fn zone(
         &self,
         namespace: &str,
@@ -133,14 +193,46 @@ impl Context {
         Ok(zone)
     }
 
+    pub async fn cluster_zone(
+        &self,
+        name: &str,
+        fqdn: &str,
+        delegations: &[Delegation],
+    ) -> Result<ClusterZone, kube::Error> {
+        let api = Api::<ClusterZone>::all(self.inner.read().await.client.clone());
+
+        // Try deleting it, in case it already exists, since it is not cleaned up along with a namespace.
+        api.delete(name, &DeleteParams::foreground()).await.ok();
+
+        let zone = api
+            .create(
+                &PostParams::default(),
+                &ClusterZone {
+                    metadata: ObjectMeta {
+                        name: Some(name.to_string()),
+                        ..Default::default()
+                    },
+                    spec: ClusterZoneSpec(ZoneSpec {
+                        domain_name: DomainName::try_from(fqdn.to_string()).unwrap(),
+                        delegations: delegations.to_vec(),
+                        ..Default::default()
+                    }),
+                    status: None,
+                },
+            )
+            .await?;
+
+        self.inner.write().await.cluster_zones.push(zone.clone());
+        Ok(zone)
+    }
+
     pub async fn wait_for<R>(&self, resource: &R, checks: &[Check<R>]) -> Result<R, ()>
     where
-        R: Resource<Scope = NamespaceResourceScope> + Clone + std::fmt::Debug + DeserializeOwned,
-        <R as Resource>::DynamicType: Default,
+        R: ResourceApi + Clone + std::fmt::Debug + DeserializeOwned,
     {
         let client = self.inner.read().await.client.clone();
 
-        let api = Api::<R>::namespaced(client, resource.meta().namespace.as_ref().unwrap());
+        let api = R::api(client, resource);
         let name = resource.name_any();
 
         tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
@@ -183,13 +275,12 @@ impl Context {
 
     pub async fn delete<R>(&self, resource: &R) -> Result<(), kube::Error>
     where
-        R: Resource<Scope = NamespaceResourceScope> + Clone + DeserializeOwned + std::fmt::Debug,
-        <R as Resource>::DynamicType: Default,
+        R: ResourceApi + Clone + DeserializeOwned + std::fmt::Debug,
     {
         info!("deleting {}", resource.name_any());
         let client = self.inner.read().await.client.clone();
 
-        let api = Api::<R>::namespaced(client, resource.namespace().as_deref().unwrap());
+        let api = R::api(client, resource);
 
         api.delete(&resource.name_any(), &DeleteParams::foreground())
             .await?;
@@ -197,6 +288,34 @@ impl Context {
         Ok(())
     }
 
+    /// Replace the finalizers of `resource`.
+    pub async fn set_finalizers<R>(
+        &self,
+        resource: &R,
+        finalizers: &[&str],
+    ) -> Result<R, kube::Error>
+    where
+        R: ResourceApi + Clone + DeserializeOwned + std::fmt::Debug,
+    {
+        info!(
+            "setting finalizers of {} to {finalizers:?}",
+            resource.name_any()
+        );
+        let client = self.inner.read().await.client.clone();
+
+        R::api(client, resource)
+            .patch(
+                &resource.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(json!({
+                    "metadata": {
+                        "finalizers": finalizers,
+                    }
+                })),
+            )
+            .await
+    }
+
     pub async fn client(&self) -> Client {
         self.inner.read().await.client.clone()
     }
@@ -215,6 +334,16 @@ impl Context {
                 .ok();
         }
 
+        for record_set in inner.record_sets.drain(..) {
+            let api = Api::<RecordSet>::namespaced(
+                client.clone(),
+                record_set.meta().namespace.as_ref().unwrap(),
+            );
+            api.delete(&record_set.name_any(), &DeleteParams::foreground())
+                .await
+                .ok();
+        }
+
         for zone in inner.zones.drain(..) {
             let api =
                 Api::<Zone>::namespaced(client.clone(), zone.meta().namespace.as_ref().unwrap());
@@ -223,6 +352,13 @@ impl Context {
                 .ok();
         }
 
+        for zone in inner.cluster_zones.drain(..) {
+            let api = Api::<ClusterZone>::all(client.clone());
+            api.delete(&zone.name_any(), &DeleteParams::foreground())
+                .await
+                .ok();
+        }
+
         for namespace in inner.namespaces.drain(..) {
             let api = Api::<Namespace>::all(client.clone());
             api.delete(&namespace.name_any(), &DeleteParams::foreground())
@@ -257,10 +393,26 @@ impl<R> Check<R> {
     }
 }
 
+/// Delegation of all records and zones to all namespaces, which tests
+/// can narrow down by overriding some of its fields.
+#[allow(dead_code)]
+pub fn allow_all() -> Delegation {
+    Delegation {
+        records: vec![RecordDelegation {
+            pattern: Pattern::try_from("*").unwrap(),
+            types: vec![],
+            ..Default::default()
+        }],
+        namespaces: vec![],
+        zones: vec![Pattern::try_from("*").unwrap()],
+        ..Default::default()
+    }
+}
+
 #[allow(dead_code)]
-pub fn has_serial() -> Check<Zone> {
-    Check::new("has-serial", move |zone: &Zone| {
-        if zone.status.iter().any(|status| status.serial.is_some()) {
+pub fn has_serial<Z: ZoneExt>() -> Check<Z> {
+    Check::new("has-serial", move |zone: &Z| {
+        if zone.serial().is_some() {
             Ok(())
         } else {
             Err("not present".to_string())
@@ -269,11 +421,11 @@ pub fn has_serial() -> Check<Zone> {
 }
 
 #[allow(dead_code)]
-pub fn has_entry(fqdn: &str) -> Check<Zone> {
+pub fn has_entry<Z: ZoneExt>(fqdn: &str) -> Check<Z> {
     let fqdn = fqdn.to_string();
 
-    Check::new("has-entry", move |zone: &Zone| {
-        if zone.status.iter().any(|status| {
+    Check::new("has-entry", move |zone: &Z| {
+        if zone.zone_status().iter().any(|status| {
             !status.entries.is_empty()
                 && status
                     .entries
@@ -288,7 +440,25 @@ pub fn has_entry(fqdn: &str) -> Check<Zone> {
 }
 
 #[allow(dead_code)]
-pub fn has_parent<R: DomainExt>(parent: &Zone) -> Check<R> {
+pub fn has_condition<Z: ZoneExt>(type_: &str) -> Check<Z> {
+    let type_ = type_.to_string();
+
+    Check::new("has-condition", move |zone: &Z| {
+        if zone.zone_status().iter().any(|status| {
+            status
+                .conditions
+                .iter()
+                .any(|condition| condition.type_ == type_ && condition.status == "True")
+        }) {
+            Ok(())
+        } else {
+            Err(format!("{type_} not true"))
+        }
+    })
+}
+
+#[allow(dead_code)]
+pub fn has_parent<R: DomainExt>(parent: &impl ZoneExt) -> Check<R> {
     let parent = parent.zone_ref();
     Check::new("has-parent", move |resource: &R| {
         let Some(label) = resource.parent() else {
@@ -305,6 +475,19 @@ pub fn has_parent<R: DomainExt>(parent: &Zone) -> Check<R> {
     })
 }
 
+#[allow(dead_code)]
+pub fn has_finalizer<R: Resource>(finalizer: &str) -> Check<R> {
+    let finalizer = finalizer.to_string();
+
+    Check::new("has-finalizer", move |resource: &R| {
+        if resource.finalizers().contains(&finalizer) {
+            Ok(())
+        } else {
+            Err(format!("{finalizer} not present"))
+        }
+    })
+}
+
 #[allow(dead_code)]
 pub fn has_fqdn<R: DomainExt>() -> Check<R> {
     Check::new("has-fqdn", move |resource: &R| match resource.fqdn() {
@@ -337,16 +520,39 @@ pub async fn run<F: Future<Output = ()> + Send + 'static>(
         inner: Arc::new(RwLock::new(ContextInner {
             namespaces: vec![],
             records: vec![],
+            record_sets: vec![],
             zones: vec![],
+            cluster_zones: vec![],
             client: client.clone(),
         })),
     };
 
     let controller_client = client.clone();
     tokio::spawn(async move {
+        let (cache, reflectors) = Cache::new(controller_client.clone());
+
+        // The controllers subscribe to the cache as they are constructed,
+        // so they must be constructed before the cache is started.
+        let zone_controller = kubizone::zone::controller(ZoneControllerContext {
+            client: controller_client.clone(),
+            requeue_time: Duration::from_secs(1),
+            metrics: Default::default(),
+            health: Default::default(),
+            cache: cache.clone(),
+        });
+
+        let record_controller = kubizone::record::controller(RecordControllerContext {
+            client: controller_client.clone(),
+            requeue_time: Duration::from_secs(1),
+            metrics: Default::default(),
+            health: Default::default(),
+            cache,
+        });
+
         tokio::select! {
-            _ = kubizone::zone::controller(ZoneControllerContext { client: controller_client.clone(), requeue_time: Duration::from_secs(1) }) => (),
-            _ = kubizone::record::controller(RecordControllerContext { client: controller_client.clone(), requeue_time: Duration::from_secs(1) }) => ()
+            _ = reflectors => (),
+            _ = zone_controller => (),
+            _ = record_controller => ()
         }
     });
 
@@ -360,8 +566,30 @@ pub async fn run<F: Future<Output = ()> + Send + 'static>(
 
 async fn destroy_crd<C>(client: Client)
 where
-    C: Resource<DynamicType = ()> + CustomResourceExt,
+    C: ResourceApi + CustomResourceExt + Clone + DeserializeOwned + std::fmt::Debug,
 {
+    // Resources left over from previous runs might still have finalizers,
+    // which would otherwise block the deletion of their definition.
+    if let Ok(resources) = Api::<C>::all(client.clone())
+        .list(&Default::default())
+        .await
+    {
+        for resource in resources {
+            C::api(client.clone(), &resource)
+                .patch(
+                    &resource.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "finalizers": null,
+                        }
+                    })),
+                )
+                .await
+                .ok();
+        }
+    }
+
     let api = Api::<CustomResourceDefinition>::all(client);
 
     let api_clone = api.clone();
@@ -405,7 +633,11 @@ where
 
 async fn recreate_crds_destructively(client: Client) {
     destroy_crd::<Record>(client.clone()).await;
+    destroy_crd::<RecordSet>(client.clone()).await;
     destroy_crd::<Zone>(client.clone()).await;
+    destroy_crd::<ClusterZone>(client.clone()).await;
+    create_crd::<ClusterZone>(client.clone()).await;
     create_crd::<Zone>(client.clone()).await;
     create_crd::<Record>(client.clone()).await;
+    create_crd::<RecordSet>(client.clone()).await;
 }
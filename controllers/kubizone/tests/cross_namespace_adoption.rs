@@ -26,8 +26,10 @@ mod tests {
                         records: vec![RecordDelegation {
                             pattern: Pattern::try_from("*").unwrap(),
                             types: vec![],
+                            ..Default::default()
                         }],
                         zones: vec![],
+                        ..Default::default()
                     }],
                 )
                 .await
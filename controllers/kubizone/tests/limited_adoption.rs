@@ -24,9 +24,11 @@ mod tests {
                         records: vec![RecordDelegation {
                             pattern: Pattern::try_from("good").unwrap(),
                             types: vec![Type::A],
+                            ..Default::default()
                         }],
                         namespaces: vec![],
                         zones: vec![],
+                        ..Default::default()
                     }],
                 )
                 .await
@@ -24,9 +24,11 @@ mod tests {
                         records: vec![RecordDelegation {
                             pattern: Pattern::try_from("*").unwrap(),
                             types: vec![],
+                            ..Default::default()
                         }],
                         namespaces: vec![],
                         zones: vec![],
+                        ..Default::default()
                     }],
                 )
                 .await
@@ -41,9 +43,11 @@ mod tests {
                         records: vec![RecordDelegation {
                             pattern: Pattern::try_from("*").unwrap(),
                             types: vec![],
+                            ..Default::default()
                         }],
                         namespaces: vec![],
                         zones: vec![],
+                        ..Default::default()
                     }],
                 )
                 .await
@@ -0,0 +1,95 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-selector-root").await.unwrap();
+            ctx.labelled_namespace("kubizone-selector-payments", &[("team", "payments")])
+                .await
+                .unwrap();
+            ctx.labelled_namespace("kubizone-selector-billing", &[("team", "billing")])
+                .await
+                .unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-selector-root",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        namespaces: vec![],
+                        namespace_selector: Some(LabelSelector {
+                            match_labels: Some(BTreeMap::from([(
+                                "team".to_string(),
+                                "payments".to_string(),
+                            )])),
+                            ..Default::default()
+                        }),
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            types: vec![],
+                            ..Default::default()
+                        }],
+                        zones: vec![],
+                        ..Default::default()
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let good_example_org = ctx
+                .a_record(
+                    "kubizone-selector-payments",
+                    "good-example-org",
+                    "good.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let bad_example_org = ctx
+                .a_record(
+                    "kubizone-selector-billing",
+                    "bad-example-org",
+                    "bad.example.org.",
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&good_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &bad_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[
+                    has_serial(),
+                    has_entry("good.example.org."),
+                    not(has_entry("bad.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use k8s_openapi::serde_json::json;
+    use kube::{
+        Api, ResourceExt,
+        api::{Patch, PatchParams},
+    };
+    use kubizone_crds::{
+        PARENT_ZONE_ANNOTATION, PARENT_ZONE_LABEL,
+        v1alpha1::{Record, ZoneExt as _},
+    };
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-parent-zone-label-migration")
+                .await
+                .unwrap();
+
+            // Zone names may contain dots, which legacy labels could not represent.
+            let example_org = ctx
+                .zone(
+                    "kubizone-parent-zone-label-migration",
+                    "example.org",
+                    "example.org.",
+                    &[allow_all()],
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .a_record(
+                    "kubizone-parent-zone-label-migration",
+                    "www-example-org",
+                    "www.example.org.",
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&www_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&example_org, &[has_entry("www.example.org.")])
+                .await
+                .unwrap();
+
+            // Replace the label and annotation with the legacy form of the label.
+            Api::<Record>::namespaced(ctx.client().await, "kubizone-parent-zone-label-migration")
+                .patch(
+                    &www_example_org.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "labels": {
+                                PARENT_ZONE_LABEL: "example.org.kubizone-parent-zone-label-migration",
+                            },
+                            "annotations": {
+                                PARENT_ZONE_ANNOTATION: null,
+                            },
+                        }
+                    })),
+                )
+                .await
+                .unwrap();
+
+            // The legacy label is understood, and migrated to the current one.
+            let zone_ref = example_org.zone_ref();
+            ctx.wait_for(
+                &www_example_org,
+                &[
+                    has_parent(&example_org),
+                    Check::new("has-annotation", move |record: &Record| {
+                        if record.annotations().get(PARENT_ZONE_ANNOTATION)
+                            == Some(&zone_ref.as_annotation())
+                            && record.labels().get(PARENT_ZONE_LABEL) == Some(&zone_ref.as_label())
+                        {
+                            Ok(())
+                        } else {
+                            Err("not migrated".to_string())
+                        }
+                    }),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(&example_org, &[has_entry("www.example.org.")])
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+}
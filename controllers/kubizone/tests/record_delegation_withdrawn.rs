@@ -31,9 +31,11 @@ mod tests {
                         records: vec![RecordDelegation {
                             pattern: Pattern::try_from("*").unwrap(),
                             types: vec![],
+                            ..Default::default()
                         }],
                         namespaces: vec![],
                         zones: vec![],
+                        ..Default::default()
                     }],
                 )
                 .await
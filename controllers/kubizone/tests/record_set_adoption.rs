@@ -0,0 +1,121 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone_common::{Pattern, Type};
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation, RecordExt, RecordSet};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    fn is_admitted(status: &'static str, reason: &'static str) -> Check<RecordSet> {
+        Check::new("is-admitted", move |record_set: &RecordSet| {
+            let Some(condition) = record_set
+                .conditions()
+                .iter()
+                .find(|condition| condition.type_ == "Admitted")
+            else {
+                return Err("no Admitted condition".to_string());
+            };
+
+            if condition.status == status && condition.reason == reason {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Admitted is {} ({}), expected {status} ({reason})",
+                    condition.status, condition.reason
+                ))
+            }
+        })
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-record-set-adoption").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-record-set-adoption",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            targets: vec![Pattern::try_from("*.example.org.").unwrap()],
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .record_set(
+                    "kubizone-record-set-adoption",
+                    "www-example-org",
+                    "www.example.org.",
+                    Type::A,
+                    &["192.0.2.1", "192.0.2.2"],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[has_fqdn(), has_serial(), has_entry("www.example.org.")],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &www_example_org,
+                &[
+                    has_fqdn(),
+                    has_parent(&example_org),
+                    is_admitted("True", "Allowed"),
+                ],
+            )
+            .await
+            .unwrap();
+
+            // One of the mail exchanges is outside of the delegation's targets,
+            // so none of the set's entries are included in the zone.
+            let lists_example_org = ctx
+                .record_set(
+                    "kubizone-record-set-adoption",
+                    "lists-example-org",
+                    "lists.example.org.",
+                    Type::MX,
+                    &["10 mail.example.org.", "20 mail.example.net."],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &lists_example_org,
+                &[
+                    has_fqdn(),
+                    has_parent(&example_org),
+                    is_admitted("False", "ConstraintViolated"),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[
+                    has_entry("www.example.org."),
+                    not(has_entry("lists.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
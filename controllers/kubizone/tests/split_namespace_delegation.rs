@@ -28,16 +28,20 @@ mod tests {
                             records: vec![RecordDelegation {
                                 pattern: Pattern::try_from("*.dev").unwrap(),
                                 types: vec![],
+                                ..Default::default()
                             }],
                             zones: vec![],
+                            ..Default::default()
                         },
                         Delegation {
                             namespaces: vec!["kubizone-split-prod".to_string()],
                             records: vec![RecordDelegation {
                                 pattern: Pattern::try_from("*").unwrap(),
                                 types: vec![],
+                                ..Default::default()
                             }],
                             zones: vec![],
+                            ..Default::default()
                         },
                     ],
                 )
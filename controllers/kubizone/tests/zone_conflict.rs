@@ -0,0 +1,102 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone_crds::v1alpha1::Delegation;
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    fn delegation() -> Delegation {
+        Delegation {
+            namespaces: vec!["kubizone-zone-conflict-records".to_string()],
+            zones: vec![],
+            ..allow_all()
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-zone-conflict-first").await.unwrap();
+            ctx.namespace("kubizone-zone-conflict-second")
+                .await
+                .unwrap();
+            ctx.namespace("kubizone-zone-conflict-records")
+                .await
+                .unwrap();
+
+            let first = ctx
+                .zone(
+                    "kubizone-zone-conflict-first",
+                    "example-org",
+                    "example.org.",
+                    &[delegation()],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&first, &[has_fqdn(), not(has_condition("Conflict"))])
+                .await
+                .unwrap();
+
+            let second = ctx
+                .zone(
+                    "kubizone-zone-conflict-second",
+                    "example-org",
+                    "example.org.",
+                    &[delegation()],
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .a_record(
+                    "kubizone-zone-conflict-records",
+                    "www-example-org",
+                    "www.example.org.",
+                )
+                .await
+                .unwrap();
+
+            // The first zone claimed example.org., so it adopts the record.
+            ctx.wait_for(&www_example_org, &[has_fqdn(), has_parent(&first)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&first, &[has_entry("www.example.org.")])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &second,
+                &[
+                    has_condition("Conflict"),
+                    not(has_entry("www.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+
+            // Once the first zone is deleted, the second one takes over.
+            ctx.delete(&first).await.unwrap();
+
+            ctx.wait_for(
+                &second,
+                &[
+                    not(has_condition("Conflict")),
+                    has_entry("www.example.org."),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(&www_example_org, &[has_parent(&second)])
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+}
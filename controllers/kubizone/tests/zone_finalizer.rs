@@ -0,0 +1,97 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    const ZONE_FINALIZER: &str = "dev.kubi.zone/detach-children";
+    const PROVIDER_FINALIZER: &str = "example.org/provider";
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-zone-finalizer").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-zone-finalizer",
+                    "example-org",
+                    "example.org.",
+                    &[allow_all()],
+                )
+                .await
+                .unwrap();
+
+            let sub_example_org = ctx
+                .zone(
+                    "kubizone-zone-finalizer",
+                    "sub-example-org",
+                    "sub.example.org.",
+                    &[allow_all()],
+                )
+                .await
+                .unwrap();
+
+            let www_sub_example_org = ctx
+                .a_record(
+                    "kubizone-zone-finalizer",
+                    "www-sub-example-org",
+                    "www.sub.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let sub_example_org = ctx
+                .wait_for(
+                    &sub_example_org,
+                    &[
+                        has_parent(&example_org),
+                        has_finalizer(ZONE_FINALIZER),
+                        has_entry("www.sub.example.org."),
+                    ],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&www_sub_example_org, &[has_parent(&sub_example_org)])
+                .await
+                .unwrap();
+
+            // Keep the zone around after the controller is done with it,
+            // like a provider withdrawing the zone would.
+            ctx.set_finalizers(&sub_example_org, &[ZONE_FINALIZER, PROVIDER_FINALIZER])
+                .await
+                .unwrap();
+
+            ctx.delete(&sub_example_org).await.unwrap();
+
+            // The record is moved to the next parent zone by the
+            // finalizer, while the deleted zone still exists.
+            ctx.wait_for(&www_sub_example_org, &[has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            let sub_example_org = ctx
+                .wait_for(
+                    &sub_example_org,
+                    &[
+                        not(has_finalizer(ZONE_FINALIZER)),
+                        has_finalizer(PROVIDER_FINALIZER),
+                    ],
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&example_org, &[has_entry("www.sub.example.org.")])
+                .await
+                .unwrap();
+
+            ctx.set_finalizers(&sub_example_org, &[]).await.unwrap();
+        })
+        .await;
+    }
+}
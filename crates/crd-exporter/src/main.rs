@@ -1,22 +1,66 @@
-use kube::{CustomResourceExt, Resource};
+use k8s_openapi::{
+    ByteString,
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+        CustomResourceConversion, CustomResourceDefinition, ServiceReference, WebhookClientConfig,
+        WebhookConversion,
+    },
+};
+use kube::{CustomResourceExt, Resource, core::crd::merge_crds};
 use regex::Regex;
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Service serving the conversion webhook, as started by `kubizone webhook`.
+///
+/// Defaults to the service of `deploy/webhook.yaml`, but can be overridden with
+/// the `WEBHOOK_SERVICE_NAMESPACE`, `WEBHOOK_SERVICE_NAME` and `WEBHOOK_SERVICE_PORT`
+/// environment variables, when deploying the webhook elsewhere.
+struct WebhookService {
+    namespace: String,
+    name: String,
+    port: i32,
+    /// PEM-encoded certificate authority of the webhook's serving certificate, read from
+    /// the file at `WEBHOOK_CA_BUNDLE`. If not set, cert-manager is expected to inject
+    /// the certificate authority of the Certificate named like the service.
+    ca_bundle: Option<ByteString>,
+}
+
+impl WebhookService {
+    fn from_env() -> Self {
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or(default.to_string());
+
+        WebhookService {
+            namespace: var("WEBHOOK_SERVICE_NAMESPACE", "kubizone"),
+            name: var("WEBHOOK_SERVICE_NAME", "kubizone-webhook"),
+            port: var("WEBHOOK_SERVICE_PORT", "443")
+                .parse()
+                .expect("WEBHOOK_SERVICE_PORT must be a port number"),
+            ca_bundle: std::env::var("WEBHOOK_CA_BUNDLE")
+                .ok()
+                .map(|path| ByteString(std::fs::read(path).unwrap())),
+        }
+    }
+}
 
 fn main() {
-    write_to_path::<kubizone_crds::v1alpha1::Record>().unwrap();
-    write_to_path::<kubizone_crds::v1alpha1::Zone>().unwrap();
+    let webhook = WebhookService::from_env();
+
+    write_versions_to_path::<kubizone_crds::v1alpha1::Record, kubizone_crds::v1beta1::Record>(
+        &webhook,
+    )
+    .unwrap();
+    write_versions_to_path::<kubizone_crds::v1alpha1::Zone, kubizone_crds::v1beta1::Zone>(&webhook)
+        .unwrap();
+    write_to_path::<kubizone_crds::v1alpha1::RecordSet>().unwrap();
+    write_to_path::<kubizone_crds::v1alpha1::ClusterZone>().unwrap();
     write_to_path::<zonefile_crds::v1alpha1::ZoneFile>().unwrap();
 }
 
-fn serialize_crd<C>() -> Result<String, serde_yaml::Error>
-where
-    C: Resource<DynamicType = ()> + CustomResourceExt,
-{
+fn serialize_crd(crd: &CustomResourceDefinition) -> Result<String, serde_yaml::Error> {
     // Removes nice docs.rs [`Type`](crate::path) references in the Custom Resource Definition spec,
     // since the references are meaningless to the end user.
     let regex = Regex::new(r#"\[`(?<label>\w+)`\](\([^\)]+\))?"#).unwrap();
 
-    let document = serde_yaml::to_string(&C::crd())?;
+    let document = serde_yaml::to_string(crd)?;
     let document = regex.replace_all(&document, "$label");
 
     Ok(format!("---\n{}", document))
@@ -32,7 +76,55 @@ where
 
     std::fs::write(
         directory.join(format!("{name}.yaml", name = C::kind(&()))),
-        serialize_crd::<C>().unwrap(),
+        serialize_crd(&C::crd()).unwrap(),
+    )
+    .unwrap();
+
+    Ok(())
+}
+
+/// Write a custom resource definition serving both the `Storage` and `Served` versions
+/// of a resource, converted between using the kubizone conversion webhook.
+///
+/// Since the definition spans multiple versions, it is written to the group's directory.
+fn write_versions_to_path<Storage, Served>(webhook: &WebhookService) -> Result<(), std::io::Error>
+where
+    Storage: Resource<DynamicType = ()> + CustomResourceExt,
+    Served: Resource<DynamicType = ()> + CustomResourceExt,
+{
+    let mut crd = merge_crds(vec![Storage::crd(), Served::crd()], &Storage::version(&())).unwrap();
+
+    if webhook.ca_bundle.is_none() {
+        crd.metadata.annotations = Some(BTreeMap::from([(
+            String::from("cert-manager.io/inject-ca-from"),
+            format!("{}/{}", webhook.namespace, webhook.name),
+        )]));
+    }
+
+    crd.spec.conversion = Some(CustomResourceConversion {
+        strategy: String::from("Webhook"),
+        webhook: Some(WebhookConversion {
+            client_config: Some(WebhookClientConfig {
+                service: Some(ServiceReference {
+                    namespace: webhook.namespace.clone(),
+                    name: webhook.name.clone(),
+                    path: Some(String::from("/convert")),
+                    port: Some(webhook.port),
+                }),
+                ca_bundle: webhook.ca_bundle.clone(),
+                ..Default::default()
+            }),
+            conversion_review_versions: vec![String::from("v1")],
+        }),
+    });
+
+    let directory = PathBuf::from("crds").join(Storage::group(&()).as_ref());
+
+    std::fs::create_dir_all(&directory)?;
+
+    std::fs::write(
+        directory.join(format!("{name}.yaml", name = Storage::kind(&()))),
+        serialize_crd(&crd).unwrap(),
     )
     .unwrap();
 
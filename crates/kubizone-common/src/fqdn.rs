@@ -179,9 +179,9 @@ impl<'a> Sub for &'a FullyQualifiedDomainName {
         let parent_segments = rhs.0.iter().rev();
 
         for parent_domain in parent_segments {
-            if !own_segments
+            if own_segments
                 .next()
-                .is_some_and(|segment| &segment == parent_domain)
+                .is_none_or(|segment| &segment != parent_domain)
             {
                 return Err(self);
             }
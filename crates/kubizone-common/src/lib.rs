@@ -5,6 +5,7 @@ mod ident;
 mod pattern;
 mod pqdn;
 mod segment;
+mod trie;
 mod r#type;
 
 pub use class::Class;
@@ -14,6 +15,7 @@ pub use ident::RecordIdent;
 pub use pattern::{Pattern, PatternSegment};
 pub use pqdn::PartiallyQualifiedDomainName;
 pub use segment::DomainSegment;
+pub use trie::SuffixTrie;
 pub use r#type::Type;
 
 pub mod error {
@@ -1,70 +1,171 @@
-use std::fmt::{Display, Write};
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Write},
+    hash::{Hash, Hasher},
+};
 
+use regex::{Regex, RegexBuilder};
 use schemars::{JsonSchema, SchemaGenerator, schema::Schema};
 use serde::{Deserialize, Serialize, de::Error};
 use thiserror::Error;
 
 use crate::{FullyQualifiedDomainName, segment::DomainSegment};
 
+/// String representation of the [`Pattern::origin`] pattern.
+const ORIGIN: &str = "@";
+
+/// Pattern matching fully qualified domain names.
+///
+/// Patterns come in two forms:
+///
+/// * Globs, made up of [`PatternSegment`]s separated by dots, such as
+///   `*.dev.example.org.` A leading standalone `*` segment matches one or
+///   more segments, while a `**` segment matches any number of segments
+///   (including none) at any position, such as `api.**.example.org.`
+///
+/// * Anchored regular expressions enclosed in slashes, such as `/api-[0-9]+\.dev/`,
+///   which must match the entire domain name, excluding its trailing dot
+///   and any origin appended using [`Pattern::with_origin`].
 #[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Pattern(Vec<PatternSegment>);
+pub struct Pattern {
+    segments: Vec<PatternSegment>,
+    regex: Option<AnchoredRegex>,
+}
 
 impl Pattern {
     /// Returns a pattern that only matches the origin of the parent
     /// FQDN.
+    ///
+    /// Written as `@`, like in zone files.
     pub fn origin() -> Self {
         Pattern::default()
     }
 
     /// Iterates over the [`PatternSegment`]s of the pattern.
+    ///
+    /// For regular expression patterns, these are the segments of the origin.
     pub fn iter(&self) -> impl Iterator<Item = &PatternSegment> + '_ {
-        self.0.iter()
+        self.segments.iter()
+    }
+
+    /// Returns true if the pattern is an anchored regular expression.
+    pub fn is_regex(&self) -> bool {
+        self.regex.is_some()
     }
 
     /// Returns a new pattern with the origin appended.
     pub fn with_origin(&self, origin: &FullyQualifiedDomainName) -> Pattern {
         let mut cloned = self.clone();
-        cloned.0.extend(origin.iter().map(PatternSegment::from));
+        cloned
+            .segments
+            .extend(origin.iter().map(PatternSegment::from));
         cloned
     }
 
     /// Returns true if the papttern matches the given domain.
     pub fn matches(&self, domain: &FullyQualifiedDomainName) -> bool {
-        let domain_segments = domain.as_ref().iter().rev();
-        let pattern_segments = self.0[..].iter().rev();
+        let domain = domain.as_ref();
 
-        if domain_segments.len() < pattern_segments.len() {
-            // Patterns longer than the domain segment cannot possibly match.
-            return false;
-        }
-
-        if domain_segments.len() > pattern_segments.len()
-            // Domains longer than patterns can never match, unless the first
-            // segment of the pattern is a standalone wildcard (*)
-            && !self.0.first().is_some_and(|pattern| pattern.as_ref() == "*")
-        {
-            return false;
+        if let Some(regex) = &self.regex {
+            // The trailing segments of the domain must match the origin,
+            // while the ones preceding it must match the regular expression.
+            let Some(split) = domain.len().checked_sub(self.segments.len()) else {
+                return false;
+            };
+
+            let (prefix, origin) = domain.split_at(split);
+
+            return matches_segments(&self.segments, origin)
+                && regex.is_match(
+                    &prefix
+                        .iter()
+                        .map(DomainSegment::to_string)
+                        .collect::<Vec<_>>()
+                        .join("."),
+                );
         }
 
-        for (pattern, domain) in pattern_segments.zip(domain_segments) {
-            // If we have hit a pattern segment containing only a wildcard, the rest of the
-            // domain segments are automatically matched.
-            if pattern.as_ref() == "*" {
-                return true;
+        match self.segments.split_first() {
+            // A leading standalone wildcard matches one or more segments.
+            Some((first, rest)) if first.as_ref() == "*" => {
+                (1..=domain.len()).any(|skip| matches_segments(rest, &domain[skip..]))
             }
+            _ => matches_segments(&self.segments, domain),
+        }
+    }
+}
 
-            if !pattern.matches(domain) {
-                return false;
-            }
+/// Match pattern segments against domain segments, with `**` segments
+/// matching any number of domain segments.
+fn matches_segments(pattern: &[PatternSegment], domain: &[DomainSegment]) -> bool {
+    match pattern.split_first() {
+        None => domain.is_empty(),
+        Some((first, rest)) if first.is_recursive_wildcard() => {
+            (0..=domain.len()).any(|skip| matches_segments(rest, &domain[skip..]))
         }
+        Some((first, rest)) => domain.split_first().is_some_and(|(segment, domain)| {
+            first.matches(segment) && matches_segments(rest, domain)
+        }),
+    }
+}
+
+/// Regular expression anchored at both ends, compared by its source.
+#[derive(Debug, Clone)]
+struct AnchoredRegex {
+    source: String,
+    regex: Regex,
+}
+
+impl AnchoredRegex {
+    fn new(source: &str) -> Result<Self, PatternSegmentError> {
+        let regex = RegexBuilder::new(&format!("^(?:{source})$"))
+            .case_insensitive(true)
+            .build()
+            .map_err(|error| PatternSegmentError::InvalidRegex(error.to_string()))?;
+
+        Ok(AnchoredRegex {
+            source: source.to_string(),
+            regex,
+        })
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
+
+impl PartialEq for AnchoredRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for AnchoredRegex {}
+
+impl PartialOrd for AnchoredRegex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AnchoredRegex {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.source.cmp(&other.source)
+    }
+}
 
-        true
+impl Hash for AnchoredRegex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
     }
 }
 
 impl FromIterator<PatternSegment> for Pattern {
     fn from_iter<T: IntoIterator<Item = PatternSegment>>(iter: T) -> Self {
-        Pattern(iter.into_iter().collect())
+        Pattern {
+            segments: iter.into_iter().collect(),
+            regex: None,
+        }
     }
 }
 
@@ -72,13 +173,41 @@ impl TryFrom<&str> for Pattern {
     type Error = PatternSegmentError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if value == ORIGIN {
+            return Ok(Pattern::origin());
+        }
+
+        // Regular expressions are enclosed in slashes, optionally
+        // followed by the segments of an origin: `/regex/.example.org.`
+        if let Some((source, origin)) = value
+            .strip_prefix('/')
+            .and_then(|value| value.rsplit_once('/'))
+        {
+            let origin = origin.trim_start_matches('.').trim_end_matches('.');
+
+            let segments = if origin.is_empty() {
+                Vec::new()
+            } else {
+                Result::from_iter(origin.split('.').map(PatternSegment::try_from))?
+            };
+
+            return Ok(Pattern {
+                segments,
+                regex: Some(AnchoredRegex::new(source)?),
+            });
+        }
+
         let segments = Result::from_iter(
             value
                 .trim_end_matches('.')
                 .split('.')
                 .map(PatternSegment::try_from),
         )?;
-        Ok(Pattern(segments))
+
+        Ok(Pattern {
+            segments,
+            regex: None,
+        })
     }
 }
 
@@ -92,7 +221,26 @@ impl TryFrom<String> for Pattern {
 
 impl Display for Pattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for segment in &self.0 {
+        if let Some(regex) = &self.regex {
+            write!(f, "/{}/", regex.source)?;
+
+            for segment in &self.segments {
+                f.write_char('.')?;
+                write!(f, "{}", segment)?;
+            }
+
+            if !self.segments.is_empty() {
+                f.write_char('.')?;
+            }
+
+            return Ok(());
+        }
+
+        if self.segments.is_empty() {
+            return f.write_str(ORIGIN);
+        }
+
+        for segment in &self.segments {
             write!(f, "{}", segment)?;
             f.write_char('.')?;
         }
@@ -133,23 +281,69 @@ impl Serialize for Pattern {
 
 /// Segment of a pattern.
 ///
-/// Used for matching against a single [`DomainSegment`].
+/// Used for matching against a single [`DomainSegment`]. Segments may contain
+/// any number of wildcards (`*`), as well as character classes such as `[a-z]`
+/// or `[!0-9]`, each matching a single character.
+///
+/// The recursive wildcard `**` must be a segment of its own, and matches any
+/// number of domain segments when used within a [`Pattern`].
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PatternSegment(String);
 
 impl PatternSegment {
     /// Returns true if the pattern segment matches the provided domain segment.
     pub fn matches(&self, domain_segment: &DomainSegment) -> bool {
-        if self.0 == domain_segment.as_ref() {
+        self.matches_str(domain_segment.as_ref())
+    }
+
+    /// Returns true if the pattern segment matches the provided string.
+    ///
+    /// Useful for matching other kinds of labels than domain segments, such
+    /// as namespace names, using the same wildcard semantics.
+    pub fn matches_str(&self, value: &str) -> bool {
+        if self.0 == value {
             return true;
         }
 
-        if let Some((head, tail)) = self.0.split_once('*') {
-            return domain_segment.as_ref().starts_with(head)
-                && domain_segment.as_ref().ends_with(tail);
+        let tokens = tokenize(&self.0);
+        let value = value.as_bytes();
+
+        // Position in tokens and value respectively, as well as the positions
+        // to backtrack to, if matching fails after the most recent wildcard.
+        let (mut token, mut position) = (0, 0);
+        let mut backtrack = None;
+
+        while position < value.len() {
+            match tokens.get(token) {
+                Some(Token::Wildcard) => {
+                    backtrack = Some((token, position));
+                    token += 1;
+                }
+                Some(other) if other.matches(value[position]) => {
+                    token += 1;
+                    position += 1;
+                }
+                _ => {
+                    // Let the most recent wildcard consume one more character.
+                    let Some((wildcard, consumed)) = backtrack else {
+                        return false;
+                    };
+
+                    backtrack = Some((wildcard, consumed + 1));
+                    token = wildcard + 1;
+                    position = consumed + 1;
+                }
+            }
         }
 
-        false
+        tokens[token..]
+            .iter()
+            .all(|token| matches!(token, Token::Wildcard))
+    }
+
+    /// Returns true if this is the recursive wildcard segment `**`.
+    pub fn is_recursive_wildcard(&self) -> bool {
+        self.0 == "**"
     }
 
     // Segments cannot be empty.
@@ -180,12 +374,116 @@ pub enum PatternSegmentError {
     /// Domain segment is empty.
     #[error("pattern is an empty string")]
     EmptyString,
-    /// Pattern contains more than one wildcard (*) character.
-    #[error("patterns can only have one wildcard")]
-    MultipleWildcards,
+    /// Recursive wildcard (**) is combined with other characters in a segment.
+    #[error("recursive wildcards must be a segment of their own")]
+    IllegalRecursiveWildcard,
+    /// Character class is missing its closing bracket, is empty, or is nested.
+    #[error("malformed character class")]
+    MalformedCharacterClass,
+    /// Regular expression pattern could not be compiled.
+    #[error("invalid regular expression: {0}")]
+    InvalidRegex(String),
+}
+
+const VALID_CHARACTERS: &str = "_-0123456789abcdefghijklmnopqrstuvwxyz*[]!";
+
+/// Single element of a [`PatternSegment`].
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    /// Literal character.
+    Literal(u8),
+    /// Any number of characters, including none.
+    Wildcard,
+    /// Any single character within (or outside of, if negated) the ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(u8, u8)>,
+    },
+}
+
+impl Token {
+    /// Returns true if the token matches the single character.
+    fn matches(&self, character: u8) -> bool {
+        match self {
+            Token::Literal(literal) => *literal == character,
+            Token::Wildcard => true,
+            Token::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&character))
+                    != *negated
+            }
+        }
+    }
 }
 
-const VALID_CHARACTERS: &str = "_-0123456789abcdefghijklmnopqrstuvwxyz*";
+/// Split a segment into tokens, assuming it has already been validated.
+fn tokenize(segment: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut characters = segment.bytes();
+
+    while let Some(character) = characters.next() {
+        match character {
+            b'*' => tokens.push(Token::Wildcard),
+            b'[' => {
+                let class: Vec<u8> = characters.by_ref().take_while(|c| *c != b']').collect();
+                let (negated, class) = match class.split_first() {
+                    Some((b'!', rest)) => (true, rest),
+                    _ => (false, &class[..]),
+                };
+
+                let mut ranges = Vec::new();
+                let mut index = 0;
+                while index < class.len() {
+                    if class.get(index + 1) == Some(&b'-') && index + 2 < class.len() {
+                        ranges.push((class[index], class[index + 2]));
+                        index += 3;
+                    } else {
+                        ranges.push((class[index], class[index]));
+                        index += 1;
+                    }
+                }
+
+                tokens.push(Token::Class { negated, ranges });
+            }
+            literal => tokens.push(Token::Literal(literal)),
+        }
+    }
+
+    tokens
+}
+
+/// Verify that all character classes within the segment are closed, non-empty
+/// and not nested, and that `!` is only used for negating them.
+fn validate_classes(segment: &str) -> Result<(), PatternSegmentError> {
+    let mut class: Option<String> = None;
+
+    for character in segment.chars() {
+        match (&mut class, character) {
+            (None, '[') => class = Some(String::new()),
+            (None, ']') => return Err(PatternSegmentError::MalformedCharacterClass),
+            (None, '!') => return Err(PatternSegmentError::InvalidCharacter('!')),
+            (Some(_), '[' | '*') => return Err(PatternSegmentError::MalformedCharacterClass),
+            (Some(contents), ']') => {
+                if contents.is_empty() || contents == "!" {
+                    return Err(PatternSegmentError::MalformedCharacterClass);
+                }
+                class = None;
+            }
+            (Some(contents), '!') if !contents.is_empty() => {
+                return Err(PatternSegmentError::InvalidCharacter('!'));
+            }
+            (Some(contents), character) => contents.push(character),
+            (None, _) => {}
+        }
+    }
+
+    if class.is_some() {
+        return Err(PatternSegmentError::MalformedCharacterClass);
+    }
+
+    Ok(())
+}
 
 impl TryFrom<&str> for PatternSegment {
     type Error = PatternSegmentError;
@@ -217,10 +515,12 @@ impl TryFrom<&str> for PatternSegment {
             return Err(PatternSegmentError::IllegalHyphen(3));
         }
 
-        if value.chars().filter(|c| *c == '*').count() > 1 {
-            return Err(PatternSegmentError::MultipleWildcards);
+        if value.contains("**") && value != "**" {
+            return Err(PatternSegmentError::IllegalRecursiveWildcard);
         }
 
+        validate_classes(&value)?;
+
         Ok(PatternSegment(value))
     }
 }
@@ -309,12 +609,154 @@ mod tests {
         )
     }
 
+    #[test]
+    fn overlapping_wildcard() {
+        assert!(
+            !PatternSegment::try_from("a*a")
+                .unwrap()
+                .matches(&DomainSegment::try_from("a").unwrap())
+        );
+
+        assert!(
+            PatternSegment::try_from("a*a")
+                .unwrap()
+                .matches(&DomainSegment::try_from("aa").unwrap())
+        );
+    }
+
+    #[test]
+    fn string_matches() {
+        let pattern = PatternSegment::try_from("team-*").unwrap();
+
+        assert!(pattern.matches_str("team-payments"));
+        assert!(!pattern.matches_str("payments"));
+    }
+
     #[test]
     fn multiple_wildcards() {
+        let pattern = PatternSegment::try_from("*amp*").unwrap();
+
+        assert!(pattern.matches_str("example"));
+        assert!(pattern.matches_str("amp"));
+        assert!(!pattern.matches_str("apm"));
+
+        let pattern = PatternSegment::try_from("a*b*c").unwrap();
+
+        assert!(pattern.matches_str("abc"));
+        assert!(pattern.matches_str("aXbYbZc"));
+        assert!(!pattern.matches_str("acb"));
+    }
+
+    #[test]
+    fn character_classes() {
+        let pattern = PatternSegment::try_from("web-[0-9][a-c]").unwrap();
+
+        assert!(pattern.matches_str("web-1a"));
+        assert!(pattern.matches_str("web-9c"));
+        assert!(!pattern.matches_str("web-1d"));
+        assert!(!pattern.matches_str("web-xa"));
+
+        let negated = PatternSegment::try_from("[!_]*").unwrap();
+
+        assert!(negated.matches_str("www"));
+        assert!(!negated.matches_str("_acme-challenge"));
+    }
+
+    #[test]
+    fn invalid_segments() {
+        assert_eq!(
+            PatternSegment::try_from("a**"),
+            Err(PatternSegmentError::IllegalRecursiveWildcard)
+        );
+
+        assert_eq!(
+            PatternSegment::try_from("web-[0-9"),
+            Err(PatternSegmentError::MalformedCharacterClass)
+        );
+
+        assert_eq!(
+            PatternSegment::try_from("web[]"),
+            Err(PatternSegmentError::MalformedCharacterClass)
+        );
+
         assert_eq!(
-            PatternSegment::try_from("*amp*"),
-            Err(PatternSegmentError::MultipleWildcards)
+            PatternSegment::try_from("w!b"),
+            Err(PatternSegmentError::InvalidCharacter('!'))
+        );
+    }
+
+    #[test]
+    fn recursive_wildcard() {
+        let pattern = Pattern::try_from("api.**.example.org").unwrap();
+
+        for domain in [
+            "api.example.org.",
+            "api.eu.example.org.",
+            "api.eu.west.example.org.",
+        ] {
+            assert!(pattern.matches(&FullyQualifiedDomainName::try_from(domain).unwrap()));
+        }
+
+        assert!(
+            !pattern.matches(&FullyQualifiedDomainName::try_from("www.eu.example.org.").unwrap())
         );
+
+        let pattern = Pattern::try_from("_acme-challenge.**").unwrap();
+        assert!(pattern.matches(
+            &FullyQualifiedDomainName::try_from("_acme-challenge.www.example.org.").unwrap()
+        ));
+    }
+
+    #[test]
+    fn regex_pattern() {
+        let pattern = Pattern::try_from(r"/api-[0-9]+\.(dev|staging)/").unwrap();
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        assert!(pattern.is_regex());
+        assert!(
+            pattern
+                .with_origin(&origin)
+                .matches(&FullyQualifiedDomainName::try_from("api-12.dev.example.org.").unwrap())
+        );
+
+        // The regular expression is anchored, and must match everything preceding the origin.
+        assert!(
+            !pattern.with_origin(&origin).matches(
+                &FullyQualifiedDomainName::try_from("www.api-12.dev.example.org.").unwrap()
+            )
+        );
+        assert!(
+            !pattern
+                .with_origin(&origin)
+                .matches(&FullyQualifiedDomainName::try_from("api-12.dev.example.com.").unwrap())
+        );
+
+        assert!(matches!(
+            Pattern::try_from("/api-[0-9/"),
+            Err(PatternSegmentError::InvalidRegex(_))
+        ));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        for pattern in [
+            "@",
+            "*.dev.",
+            "api.**.",
+            "web-[0-9]*.*.",
+            r"/api-[0-9]+\.dev/",
+        ] {
+            let parsed = Pattern::try_from(pattern).unwrap();
+            assert_eq!(parsed.to_string(), pattern);
+
+            let with_origin = parsed.with_origin(&origin);
+            assert_eq!(
+                Pattern::try_from(with_origin.to_string()).unwrap(),
+                with_origin
+            );
+        }
     }
 
     #[test]
@@ -386,4 +828,20 @@ mod tests {
                 .matches(&FullyQualifiedDomainName::try_from("example.org.").unwrap())
         );
     }
+
+    #[test]
+    fn origin_pattern() {
+        let pattern = Pattern::try_from("@").unwrap();
+        assert_eq!(pattern, Pattern::origin());
+        assert_eq!(pattern.to_string(), "@");
+
+        let origin = FullyQualifiedDomainName::try_from("example.org.").unwrap();
+
+        assert!(pattern.with_origin(&origin).matches(&origin));
+        assert!(
+            !pattern
+                .with_origin(&origin)
+                .matches(&FullyQualifiedDomainName::try_from("www.example.org.").unwrap())
+        );
+    }
 }
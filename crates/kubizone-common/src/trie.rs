@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{DomainSegment, FullyQualifiedDomainName};
+
+/// Trie of [`FullyQualifiedDomainName`]s, keyed by their [`DomainSegment`]s
+/// in reverse order, such that domain names sharing a suffix share a branch.
+///
+/// Each domain name can hold any number of values, which are kept in order,
+/// so ties between values of the same domain name are always resolved in
+/// favor of the least one.
+///
+/// ```rust
+/// # use kubizone_common::{FullyQualifiedDomainName, SuffixTrie};
+/// let mut trie = SuffixTrie::new();
+/// trie.insert(&FullyQualifiedDomainName::try_from("org.").unwrap(), "org");
+/// trie.insert(&FullyQualifiedDomainName::try_from("example.org.").unwrap(), "example");
+///
+/// let www = FullyQualifiedDomainName::try_from("www.example.org.").unwrap();
+/// assert_eq!(trie.longest_match(&www), Some(&"example"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SuffixTrie<T> {
+    root: Node<T>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    children: BTreeMap<DomainSegment, Node<T>>,
+    values: BTreeSet<T>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Node {
+            children: BTreeMap::new(),
+            values: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T> Node<T> {
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.values.is_empty()
+    }
+}
+
+impl<T: Ord> Node<T> {
+    /// Remove `value` from the node at `segments`, relative to this one,
+    /// pruning nodes left empty along the way.
+    fn remove(&mut self, segments: &[DomainSegment], value: &T) -> bool {
+        let Some((segment, rest)) = segments.split_last() else {
+            return self.values.remove(value);
+        };
+
+        let Some(child) = self.children.get_mut(segment) else {
+            return false;
+        };
+
+        let removed = child.remove(rest, value);
+
+        if child.is_empty() {
+            self.children.remove(segment);
+        }
+
+        removed
+    }
+}
+
+impl<T> Default for SuffixTrie<T> {
+    fn default() -> Self {
+        SuffixTrie {
+            root: Node::default(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Ord> SuffixTrie<T> {
+    /// Construct an empty trie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of values in the trie, across all domain names.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the trie contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert `value` at `fqdn`.
+    ///
+    /// Returns false if the value was already present at the domain name.
+    pub fn insert(&mut self, fqdn: &FullyQualifiedDomainName, value: T) -> bool {
+        let node = fqdn.iter().rev().fold(&mut self.root, |node, segment| {
+            node.children.entry(segment.clone()).or_default()
+        });
+
+        let inserted = node.values.insert(value);
+        if inserted {
+            self.len += 1;
+        }
+
+        inserted
+    }
+
+    /// Remove `value` from `fqdn`.
+    ///
+    /// Returns false if the value was not present at the domain name.
+    pub fn remove(&mut self, fqdn: &FullyQualifiedDomainName, value: &T) -> bool {
+        let removed = self.root.remove(fqdn.as_ref(), value);
+        if removed {
+            self.len -= 1;
+        }
+
+        removed
+    }
+
+    /// Values at exactly `fqdn`, in order.
+    pub fn get(&self, fqdn: &FullyQualifiedDomainName) -> impl Iterator<Item = &T> {
+        self.node(fqdn)
+            .into_iter()
+            .flat_map(|node| node.values.iter())
+    }
+
+    /// Least value at the longest suffix of `fqdn` holding any values,
+    /// including `fqdn` itself.
+    pub fn longest_match(&self, fqdn: &FullyQualifiedDomainName) -> Option<&T> {
+        self.longest_suffix_match(fqdn.as_ref())
+    }
+
+    /// Least value at the longest suffix of `fqdn` holding any values,
+    /// excluding `fqdn` itself, such as the most immediate parent zone of a zone.
+    pub fn longest_parent_match(&self, fqdn: &FullyQualifiedDomainName) -> Option<&T> {
+        self.longest_suffix_match(fqdn.as_ref().get(1..)?)
+    }
+
+    fn node(&self, fqdn: &FullyQualifiedDomainName) -> Option<&Node<T>> {
+        fqdn.iter()
+            .rev()
+            .try_fold(&self.root, |node, segment| node.children.get(segment))
+    }
+
+    /// Walk down the trie along `segments` in reverse, keeping track
+    /// of the least value of the deepest node holding any.
+    fn longest_suffix_match(&self, segments: &[DomainSegment]) -> Option<&T> {
+        let mut node = &self.root;
+        let mut longest = node.values.first();
+
+        for segment in segments.iter().rev() {
+            let Some(child) = node.children.get(segment) else {
+                break;
+            };
+
+            node = child;
+            longest = node.values.first().or(longest);
+        }
+
+        longest
+    }
+}
+
+impl<T: Ord> FromIterator<(FullyQualifiedDomainName, T)> for SuffixTrie<T> {
+    fn from_iter<I: IntoIterator<Item = (FullyQualifiedDomainName, T)>>(iter: I) -> Self {
+        let mut trie = SuffixTrie::new();
+        for (fqdn, value) in iter {
+            trie.insert(&fqdn, value);
+        }
+        trie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FullyQualifiedDomainName;
+
+    use super::SuffixTrie;
+
+    fn fqdn(fqdn: &str) -> FullyQualifiedDomainName {
+        FullyQualifiedDomainName::try_from(fqdn).unwrap()
+    }
+
+    #[test]
+    fn longest_match() {
+        let trie: SuffixTrie<&str> = [
+            (fqdn("org."), "org"),
+            (fqdn("example.org."), "example"),
+            (fqdn("dev.example.org."), "dev"),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            trie.longest_match(&fqdn("www.example.org.")),
+            Some(&"example")
+        );
+        assert_eq!(trie.longest_match(&fqdn("example.org.")), Some(&"example"));
+        assert_eq!(
+            trie.longest_match(&fqdn("a.b.dev.example.org.")),
+            Some(&"dev")
+        );
+        assert_eq!(trie.longest_match(&fqdn("example.com.")), None);
+
+        assert_eq!(
+            trie.longest_parent_match(&fqdn("example.org.")),
+            Some(&"org")
+        );
+        assert_eq!(
+            trie.longest_parent_match(&fqdn("www.example.org.")),
+            Some(&"example")
+        );
+        assert_eq!(trie.longest_parent_match(&fqdn("org.")), None);
+    }
+
+    #[test]
+    fn root_match() {
+        let root = FullyQualifiedDomainName::default();
+
+        let mut trie = SuffixTrie::new();
+        trie.insert(&root, "root");
+
+        assert_eq!(trie.longest_match(&fqdn("example.org.")), Some(&"root"));
+        assert_eq!(trie.longest_match(&root), Some(&"root"));
+        assert_eq!(trie.longest_parent_match(&root), None);
+    }
+
+    #[test]
+    fn ties_resolve_to_least_value() {
+        let mut trie = SuffixTrie::new();
+        trie.insert(&fqdn("example.org."), ("team-b", "example"));
+        trie.insert(&fqdn("example.org."), ("team-a", "example"));
+
+        assert_eq!(
+            trie.longest_match(&fqdn("www.example.org.")),
+            Some(&("team-a", "example"))
+        );
+        assert_eq!(
+            trie.get(&fqdn("example.org.")).collect::<Vec<_>>(),
+            vec![&("team-a", "example"), &("team-b", "example")]
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let mut trie = SuffixTrie::new();
+        assert!(trie.insert(&fqdn("org."), "org"));
+        assert!(trie.insert(&fqdn("www.example.org."), "www"));
+        assert!(!trie.insert(&fqdn("www.example.org."), "www"));
+        assert_eq!(trie.len(), 2);
+
+        assert!(trie.remove(&fqdn("www.example.org."), &"www"));
+        assert!(!trie.remove(&fqdn("www.example.org."), &"www"));
+        assert!(!trie.remove(&fqdn("example.org."), &"org"));
+        assert_eq!(trie.len(), 1);
+
+        assert_eq!(trie.longest_match(&fqdn("www.example.org.")), Some(&"org"));
+
+        // Branches left empty are pruned.
+        assert!(
+            trie.root
+                .children
+                .values()
+                .all(|org| org.children.is_empty())
+        );
+
+        assert!(trie.remove(&fqdn("org."), &"org"));
+        assert!(trie.is_empty());
+        assert!(trie.root.is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+//! Conversion between the served versions of the kubizone resources.
+//!
+//! Record and Zone status conditions exist in both versions, and are converted as-is.
+//! Conditions stashed in the [`CONVERSION_DATA_ANNOTATION`] by earlier conversions,
+//! from before `v1alpha1` had conditions, are restored when converting to `v1beta1`.
+//!
+//! The `v1alpha1` rdata of a Record is free-form, while `v1beta1` structures it, so
+//! rdata which is not written the way `v1beta1` would format it, such as unquoted TXT
+//! strings or uppercase IPv6 addresses, is stashed in the annotation as written. It is
+//! restored when converting back to `v1alpha1`, unless the structured data changed.
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kube::{Resource, core::ObjectMeta};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{CONVERSION_DATA_ANNOTATION, v1alpha1, v1beta1};
+
+/// Produced when an object cannot be converted to the desired version.
+#[derive(Error, Debug)]
+pub enum ConversionError {
+    #[error("kind {0} is not convertible")]
+    UnsupportedKind(String),
+    #[error("api version {0} is not supported")]
+    UnsupportedVersion(String),
+    #[error("object is malformed: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+/// Data which cannot be represented in the version an object is converted to,
+/// stored in the [`CONVERSION_DATA_ANNOTATION`].
+#[derive(Serialize, Deserialize, Default)]
+struct ConversionData {
+    /// Conditions stashed by conversions to earlier revisions of `v1alpha1`.
+    #[serde(default, skip_serializing)]
+    conditions: Vec<Condition>,
+
+    /// Record rdata as written in `v1alpha1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rdata: Option<String>,
+}
+
+/// Remove and return the data stored in the conversion annotation of `metadata`.
+fn unstash(metadata: &mut ObjectMeta) -> ConversionData {
+    let Some(annotations) = metadata.annotations.as_mut() else {
+        return ConversionData::default();
+    };
+
+    let data = annotations
+        .remove(CONVERSION_DATA_ANNOTATION)
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+
+    if annotations.is_empty() {
+        metadata.annotations = None;
+    }
+
+    data
+}
+
+/// Store `data` in the conversion annotation of `metadata`.
+fn stash(metadata: &mut ObjectMeta, data: &ConversionData) {
+    // Unwrap safety: The data consists of strings only.
+    let data = serde_json::to_string(data).unwrap();
+
+    metadata
+        .annotations
+        .get_or_insert_default()
+        .insert(CONVERSION_DATA_ANNOTATION.to_string(), data);
+}
+
+impl From<v1alpha1::Record> for v1beta1::Record {
+    fn from(mut record: v1alpha1::Record) -> Self {
+        let data = unstash(&mut record.metadata);
+        let rdata = v1beta1::RecordData::from_rdata(record.spec.type_, &record.spec.rdata);
+
+        if rdata.to_string() != record.spec.rdata {
+            let data = ConversionData {
+                rdata: Some(record.spec.rdata),
+                ..Default::default()
+            };
+
+            stash(&mut record.metadata, &data);
+        }
+
+        v1beta1::Record {
+            metadata: record.metadata,
+            spec: v1beta1::RecordSpec {
+                domain_name: record.spec.domain_name,
+                zone_ref: record.spec.zone_ref,
+                class: record.spec.class,
+                ttl: record.spec.ttl,
+                rdata,
+            },
+            status: record.status.map(|status| v1beta1::RecordStatus {
+                fqdn: status.fqdn,
+                conditions: if status.conditions.is_empty() {
+                    data.conditions
+                } else {
+                    status.conditions
+                },
+            }),
+        }
+    }
+}
+
+impl From<v1beta1::Record> for v1alpha1::Record {
+    fn from(mut record: v1beta1::Record) -> Self {
+        let type_ = record.spec.rdata.type_();
+
+        // The rdata as originally written is only restored if it still
+        // describes the same data, and has not been changed in v1beta1.
+        let rdata = unstash(&mut record.metadata)
+            .rdata
+            .filter(|rdata| v1beta1::RecordData::from_rdata(type_, rdata) == record.spec.rdata)
+            .unwrap_or_else(|| record.spec.rdata.to_string());
+
+        let status = record.status.map(|status| v1alpha1::RecordStatus {
+            fqdn: status.fqdn,
+            conditions: status.conditions,
+        });
+
+        v1alpha1::Record {
+            metadata: record.metadata,
+            spec: v1alpha1::RecordSpec {
+                domain_name: record.spec.domain_name,
+                zone_ref: record.spec.zone_ref,
+                type_,
+                class: record.spec.class,
+                ttl: record.spec.ttl,
+                rdata,
+            },
+            status,
+        }
+    }
+}
+
+impl From<v1alpha1::Zone> for v1beta1::Zone {
+    fn from(mut zone: v1alpha1::Zone) -> Self {
+        let data = unstash(&mut zone.metadata);
+
+        v1beta1::Zone {
+            metadata: zone.metadata,
+            spec: v1beta1::ZoneSpec {
+                domain_name: zone.spec.domain_name,
+                zone_ref: zone.spec.zone_ref,
+                delegations: zone.spec.delegations,
+                ttl: zone.spec.ttl,
+                soa: v1beta1::StartOfAuthority {
+                    refresh: zone.spec.refresh,
+                    retry: zone.spec.retry,
+                    expire: zone.spec.expire,
+                    negative_response_cache: zone.spec.negative_response_cache,
+                },
+            },
+            status: zone.status.map(|status| v1beta1::ZoneStatus {
+                entries: status.entries,
+                fqdn: status.fqdn,
+                hash: status.hash,
+                serial: status.serial,
+                verdicts: status.verdicts,
+                allowed_records: status.allowed_records,
+                denied_records: status.denied_records,
+                conditions: if status.conditions.is_empty() {
+                    data.conditions
+                } else {
+                    status.conditions
+                },
+            }),
+        }
+    }
+}
+
+impl From<v1beta1::Zone> for v1alpha1::Zone {
+    fn from(zone: v1beta1::Zone) -> Self {
+        let status = zone.status.map(|status| v1alpha1::ZoneStatus {
+            entries: status.entries,
+            fqdn: status.fqdn,
+            hash: status.hash,
+            serial: status.serial,
+            verdicts: status.verdicts,
+            allowed_records: status.allowed_records,
+            denied_records: status.denied_records,
+            conditions: status.conditions,
+        });
+
+        v1alpha1::Zone {
+            metadata: zone.metadata,
+            spec: v1alpha1::ZoneSpec {
+                domain_name: zone.spec.domain_name,
+                zone_ref: zone.spec.zone_ref,
+                delegations: zone.spec.delegations,
+                ttl: zone.spec.ttl,
+                refresh: zone.spec.soa.refresh,
+                retry: zone.spec.soa.retry,
+                expire: zone.spec.soa.expire,
+                negative_response_cache: zone.spec.soa.negative_response_cache,
+            },
+            status,
+        }
+    }
+}
+
+/// Deserialize `object` as `From`, and serialize it again as `To`.
+fn convert_as<From, To>(object: Value) -> Result<Value, ConversionError>
+where
+    From: DeserializeOwned,
+    To: std::convert::From<From> + Serialize,
+{
+    let object: From = serde_json::from_value(object)?;
+    Ok(serde_json::to_value(To::from(object))?)
+}
+
+/// Convert a Record or Zone `object`, of any served version, to `desired_api_version`.
+///
+/// Used for implementing the conversion webhook of the custom resource definitions.
+pub fn convert(object: Value, desired_api_version: &str) -> Result<Value, ConversionError> {
+    let api_version = object["apiVersion"].as_str().unwrap_or_default();
+    let kind = object["kind"].as_str().unwrap_or_default();
+
+    if api_version == desired_api_version {
+        return Ok(object);
+    }
+
+    if kind != "Record" && kind != "Zone" {
+        return Err(ConversionError::UnsupportedKind(kind.to_string()));
+    }
+
+    // Records and Zones share group and versions.
+    let alpha = v1alpha1::Record::api_version(&());
+    let beta = v1beta1::Record::api_version(&());
+
+    for version in [api_version, desired_api_version] {
+        if version != alpha && version != beta {
+            return Err(ConversionError::UnsupportedVersion(version.to_string()));
+        }
+    }
+
+    match (kind, desired_api_version == beta) {
+        ("Record", true) => convert_as::<v1alpha1::Record, v1beta1::Record>(object),
+        ("Record", false) => convert_as::<v1beta1::Record, v1alpha1::Record>(object),
+        (_, true) => convert_as::<v1alpha1::Zone, v1beta1::Zone>(object),
+        (_, false) => convert_as::<v1beta1::Zone, v1alpha1::Zone>(object),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+    use kube::Resource as _;
+    use kubizone_common::{DomainName, Type};
+    use serde_json::json;
+
+    use crate::{
+        CONVERSION_DATA_ANNOTATION,
+        v1alpha1::{self, ZoneKind, ZoneRef},
+        v1beta1,
+    };
+
+    use super::{ConversionError, convert};
+
+    #[test]
+    fn record_round_trip() {
+        let record = v1alpha1::Record::new(
+            "www",
+            v1alpha1::RecordSpec {
+                domain_name: DomainName::try_from("www").unwrap(),
+                zone_ref: Some(ZoneRef {
+                    kind: ZoneKind::Zone,
+                    name: String::from("example-org"),
+                    namespace: None,
+                }),
+                type_: Type::MX,
+                rdata: String::from("10 mail.example.org."),
+                ..Default::default()
+            },
+        );
+
+        let original = serde_json::to_value(&record).unwrap();
+        let beta = convert(original.clone(), &v1beta1::Record::api_version(&())).unwrap();
+
+        assert_eq!(
+            beta["spec"]["rdata"],
+            json!({"MX": {"preference": 10, "exchange": "mail.example.org."}})
+        );
+
+        assert!(beta["metadata"]["annotations"].is_null());
+
+        let alpha = convert(beta, &v1alpha1::Record::api_version(&())).unwrap();
+        assert_eq!(alpha, original);
+    }
+
+    fn alpha_record(type_: Type, rdata: &str) -> v1alpha1::Record {
+        v1alpha1::Record::new(
+            "www",
+            v1alpha1::RecordSpec {
+                domain_name: DomainName::try_from("www.example.org.").unwrap(),
+                type_,
+                rdata: rdata.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn non_canonical_rdata_round_trip() {
+        for (type_, rdata) in [
+            (Type::TXT, "v=spf1 -all"),
+            (Type::TXT, "\"v=spf1\"   \"-all\""),
+            (Type::AAAA, "2001:DB8::1"),
+            (Type::CNAME, "WWW.Example.org."),
+            (Type::MX, "  10\tmail.example.org. "),
+            (Type::SRV, "10 5 443   api.example.org."),
+        ] {
+            let original = serde_json::to_value(alpha_record(type_, rdata)).unwrap();
+            let beta = convert(original.clone(), &v1beta1::Record::api_version(&())).unwrap();
+
+            assert!(
+                beta["metadata"]["annotations"][CONVERSION_DATA_ANNOTATION].is_string(),
+                "{type_} rdata {rdata:?} was not stashed"
+            );
+
+            let alpha = convert(beta, &v1alpha1::Record::api_version(&())).unwrap();
+            assert_eq!(
+                alpha, original,
+                "{type_} rdata {rdata:?} did not round trip"
+            );
+        }
+    }
+
+    #[test]
+    fn changed_rdata_is_not_restored() {
+        let original = serde_json::to_value(alpha_record(Type::TXT, "v=spf1 -all")).unwrap();
+        let mut beta = convert(original, &v1beta1::Record::api_version(&())).unwrap();
+
+        assert_eq!(beta["spec"]["rdata"], json!({"TXT": ["v=spf1", "-all"]}));
+
+        // Changed in v1beta1, so the stashed rdata no longer applies.
+        beta["spec"]["rdata"] = json!({"TXT": ["v=spf1 ~all"]});
+
+        let alpha = convert(beta, &v1alpha1::Record::api_version(&())).unwrap();
+        assert_eq!(alpha["spec"]["rdata"], json!("\"v=spf1 ~all\""));
+        assert!(alpha["metadata"]["annotations"].is_null());
+    }
+
+    fn condition() -> Condition {
+        Condition {
+            type_: String::from("Ready"),
+            status: String::from("True"),
+            reason: String::from("Reconciled"),
+            message: String::new(),
+            last_transition_time: Time(Default::default()),
+            observed_generation: None,
+        }
+    }
+
+    #[test]
+    fn conditions_are_preserved() {
+        let mut record = v1beta1::Record::new(
+            "www",
+            v1beta1::RecordSpec {
+                domain_name: DomainName::try_from("www.example.org.").unwrap(),
+                zone_ref: None,
+                class: v1beta1::defaults::CLASS,
+                ttl: None,
+                rdata: v1beta1::RecordData::A("192.0.2.1".parse().unwrap()),
+            },
+        );
+
+        record.status = Some(v1beta1::RecordStatus {
+            fqdn: None,
+            conditions: vec![condition()],
+        });
+
+        let original = serde_json::to_value(&record).unwrap();
+        let alpha = convert(original.clone(), &v1alpha1::Record::api_version(&())).unwrap();
+
+        assert_eq!(alpha["status"]["conditions"][0]["type"], json!("Ready"));
+        assert!(alpha["metadata"]["annotations"].is_null());
+
+        let beta = convert(alpha.clone(), &v1beta1::Record::api_version(&())).unwrap();
+        assert_eq!(beta, original);
+
+        // Conditions stashed by earlier conversions are restored.
+        let mut stashed = alpha;
+        let conditions = stashed["status"]
+            .as_object_mut()
+            .unwrap()
+            .remove("conditions")
+            .unwrap();
+        stashed["metadata"]["annotations"] = json!({
+            CONVERSION_DATA_ANNOTATION: json!({"conditions": conditions}).to_string()
+        });
+
+        let beta = convert(stashed, &v1beta1::Record::api_version(&())).unwrap();
+        assert_eq!(beta, original);
+    }
+
+    #[test]
+    fn zone_conditions() {
+        let mut zone = v1beta1::Zone::new(
+            "example-org",
+            v1beta1::ZoneSpec {
+                domain_name: DomainName::try_from("example.org.").unwrap(),
+                zone_ref: None,
+                delegations: vec![],
+                ttl: v1beta1::defaults::TTL,
+                soa: v1beta1::StartOfAuthority::default(),
+            },
+        );
+
+        zone.status = Some(v1beta1::ZoneStatus {
+            conditions: vec![condition()],
+            ..Default::default()
+        });
+
+        let original = serde_json::to_value(&zone).unwrap();
+        let alpha = convert(original.clone(), &v1alpha1::Zone::api_version(&())).unwrap();
+
+        assert_eq!(alpha["spec"]["refresh"], json!(v1beta1::defaults::REFRESH));
+        assert_eq!(alpha["status"]["conditions"][0]["type"], json!("Ready"));
+        assert!(alpha["metadata"]["annotations"].is_null());
+
+        let beta = convert(alpha.clone(), &v1beta1::Zone::api_version(&())).unwrap();
+        assert_eq!(beta, original);
+
+        // Conditions stashed by earlier conversions are restored.
+        let mut stashed = alpha;
+        let conditions = stashed["status"]
+            .as_object_mut()
+            .unwrap()
+            .remove("conditions")
+            .unwrap();
+        stashed["metadata"]["annotations"] = json!({
+            CONVERSION_DATA_ANNOTATION: json!({"conditions": conditions}).to_string()
+        });
+
+        let beta = convert(stashed, &v1beta1::Zone::api_version(&())).unwrap();
+        assert_eq!(beta, original);
+    }
+
+    #[test]
+    fn unsupported() {
+        assert!(matches!(
+            convert(
+                json!({"apiVersion": "kubi.zone/v1alpha1", "kind": "ZoneFile"}),
+                "kubi.zone/v1beta1"
+            ),
+            Err(ConversionError::UnsupportedKind(_))
+        ));
+
+        assert!(matches!(
+            convert(
+                json!({"apiVersion": v1alpha1::Zone::api_version(&()), "kind": "Zone"}),
+                "kubi.zone/v2"
+            ),
+            Err(ConversionError::UnsupportedVersion(_))
+        ));
+    }
+}
@@ -1,6 +1,25 @@
+pub mod conversion;
+pub mod ownership;
 pub mod v1alpha1;
+pub mod v1beta1;
 
+/// Label identifying the zone a resource has been adopted by, for selecting
+/// the resources of a zone. See [`ZoneRef::as_label`](v1alpha1::ZoneRef::as_label).
 #[cfg(feature = "dev")]
 pub const PARENT_ZONE_LABEL: &str = "dev.kubi.zone/parent-zone";
 #[cfg(not(feature = "dev"))]
 pub const PARENT_ZONE_LABEL: &str = "kubi.zone/parent-zone";
+
+/// Annotation referencing the zone a resource has been adopted by, in the
+/// form of [`ZoneRef::as_annotation`](v1alpha1::ZoneRef::as_annotation).
+#[cfg(feature = "dev")]
+pub const PARENT_ZONE_ANNOTATION: &str = "dev.kubi.zone/parent-zone";
+#[cfg(not(feature = "dev"))]
+pub const PARENT_ZONE_ANNOTATION: &str = "kubi.zone/parent-zone";
+
+/// Annotation used for preserving data which cannot be represented in older
+/// versions of a resource, when converting between versions.
+#[cfg(feature = "dev")]
+pub const CONVERSION_DATA_ANNOTATION: &str = "dev.kubi.zone/conversion-data";
+#[cfg(not(feature = "dev"))]
+pub const CONVERSION_DATA_ANNOTATION: &str = "kubi.zone/conversion-data";
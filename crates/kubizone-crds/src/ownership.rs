@@ -0,0 +1,228 @@
+//! TXT registry used for tracking ownership of generated records.
+//!
+//! Similar to [external-dns' TXT registry](https://kubernetes-sigs.github.io/external-dns/latest/docs/registry/txt/),
+//! every record generated by a kubizone controller can be accompanied by a TXT
+//! record describing which cluster and which Kubernetes resource it was generated
+//! for. Provider backends can use this to avoid modifying records created by
+//! other clusters, or by humans.
+//!
+//! The TXT record for a record of type `A` at `www.example.org.` is placed
+//! at `_kubizone-a.www.example.org.`, and contains:
+//!
+//! ```text
+//! "heritage=kubizone,kubizone/owner=<cluster id>,kubizone/resource=<kind>/<namespace>/<name>"
+//! ```
+//!
+//! Since fields are separated by `,` and `=`, cluster ids containing either are
+//! rejected by [`Ownership::validate_cluster_id`]. Only records generated for
+//! ingresses are currently accompanied by registry records.
+
+use std::{fmt::Display, str::FromStr};
+
+use kubizone_common::{DomainSegment, FullyQualifiedDomainName, Type};
+use thiserror::Error;
+
+/// Heritage value identifying TXT records managed by kubizone.
+pub const HERITAGE: &str = "kubizone";
+
+/// Prefix of the label prepended to the owned record's name, to produce
+/// the name of the TXT record tracking its ownership.
+pub const REGISTRY_PREFIX: &str = "_kubizone";
+
+/// Ownership information encoded in a registry TXT record.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ownership {
+    /// Identifier of the cluster which generated the record.
+    pub cluster_id: String,
+    /// Kind of the Kubernetes resource the record was generated for, e.g. `ingress`.
+    pub kind: String,
+    /// Namespace of the Kubernetes resource the record was generated for.
+    pub namespace: String,
+    /// Name of the Kubernetes resource the record was generated for.
+    pub name: String,
+}
+
+impl Ownership {
+    /// Fully qualified domain name of the TXT record tracking ownership
+    /// of records of `type_` at `fqdn`.
+    ///
+    /// Leading wildcard segments cannot be prefixed, so they are replaced
+    /// by a `-wildcard` suffix on the registry label instead.
+    pub fn registry_fqdn(fqdn: &FullyQualifiedDomainName, type_: Type) -> FullyQualifiedDomainName {
+        let mut segments = fqdn.iter().peekable();
+
+        let label = if segments.next_if(|segment| segment.is_wildcard()).is_some() {
+            format!(
+                "{REGISTRY_PREFIX}-{}-wildcard",
+                type_.to_string().to_lowercase()
+            )
+        } else {
+            format!("{REGISTRY_PREFIX}-{}", type_.to_string().to_lowercase())
+        };
+
+        std::iter::once(DomainSegment::new_unchecked(&label))
+            .chain(segments.cloned())
+            .collect()
+    }
+
+    /// Check that `cluster_id` can be encoded in a registry TXT record and parsed back.
+    ///
+    /// Cluster ids must not be empty, and may not contain whitespace, quotes,
+    /// backslashes, or the `,` and `=` characters used for separating fields.
+    pub fn validate_cluster_id(cluster_id: &str) -> Result<(), OwnershipError> {
+        if cluster_id.is_empty()
+            || cluster_id
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, ',' | '=' | '"' | '\\'))
+        {
+            return Err(OwnershipError::InvalidClusterId(cluster_id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the record is owned by the given cluster.
+    pub fn is_owned_by(&self, cluster_id: &str) -> bool {
+        self.cluster_id == cluster_id
+    }
+
+    /// Produce the quoted rdata of the registry TXT record.
+    pub fn to_rdata(&self) -> String {
+        format!("\"{self}\"")
+    }
+}
+
+impl Display for Ownership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "heritage={HERITAGE},{HERITAGE}/owner={},{HERITAGE}/resource={}/{}/{}",
+            self.cluster_id, self.kind, self.namespace, self.name
+        )
+    }
+}
+
+/// Produced when parsing the contents of a registry TXT record fails.
+#[derive(Error, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OwnershipError {
+    /// The TXT record was not created by kubizone.
+    #[error("record heritage is not {HERITAGE}")]
+    ForeignHeritage,
+    /// The TXT record is missing the named field.
+    #[error("missing field {0}")]
+    MissingField(&'static str),
+    /// The resource field is not of the form `kind/namespace/name`.
+    #[error("malformed resource {0}")]
+    MalformedResource(String),
+    /// The cluster id cannot be encoded in a registry TXT record.
+    #[error(
+        "invalid cluster id {0:?}, must be non-empty and not contain whitespace, quotes, backslashes, `,` or `=`"
+    )]
+    InvalidClusterId(String),
+}
+
+impl FromStr for Ownership {
+    type Err = OwnershipError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().trim_matches('"');
+
+        let field = |key: &str| {
+            value
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(field, _)| *field == key)
+                .map(|(_, value)| value)
+        };
+
+        if field("heritage") != Some(HERITAGE) {
+            return Err(OwnershipError::ForeignHeritage);
+        }
+
+        let cluster_id =
+            field(&format!("{HERITAGE}/owner")).ok_or(OwnershipError::MissingField("owner"))?;
+
+        let resource = field(&format!("{HERITAGE}/resource"))
+            .ok_or(OwnershipError::MissingField("resource"))?;
+
+        let mut parts = resource.splitn(3, '/');
+        let (Some(kind), Some(namespace), Some(name)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(OwnershipError::MalformedResource(resource.to_string()));
+        };
+
+        Ok(Ownership {
+            cluster_id: cluster_id.to_string(),
+            kind: kind.to_string(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{FullyQualifiedDomainName, Type};
+
+    use super::{Ownership, OwnershipError};
+
+    fn ownership() -> Ownership {
+        Ownership {
+            cluster_id: String::from("prod-1"),
+            kind: String::from("ingress"),
+            namespace: String::from("default"),
+            name: String::from("website"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let ownership = ownership();
+
+        assert_eq!(
+            ownership.to_rdata(),
+            r#""heritage=kubizone,kubizone/owner=prod-1,kubizone/resource=ingress/default/website""#
+        );
+
+        assert_eq!(ownership.to_rdata().parse::<Ownership>(), Ok(ownership));
+    }
+
+    #[test]
+    fn cluster_id_validation() {
+        assert_eq!(Ownership::validate_cluster_id("prod-1"), Ok(()));
+
+        for cluster_id in ["", "prod 1", "prod,1", "prod=1", "\"prod\""] {
+            assert_eq!(
+                Ownership::validate_cluster_id(cluster_id),
+                Err(OwnershipError::InvalidClusterId(cluster_id.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn foreign_heritage() {
+        assert_eq!(
+            "heritage=external-dns,external-dns/owner=default".parse::<Ownership>(),
+            Err(OwnershipError::ForeignHeritage)
+        );
+    }
+
+    #[test]
+    fn registry_fqdn() {
+        assert_eq!(
+            Ownership::registry_fqdn(
+                &FullyQualifiedDomainName::try_from("www.example.org.").unwrap(),
+                Type::AAAA
+            ),
+            FullyQualifiedDomainName::try_from("_kubizone-aaaa.www.example.org.").unwrap()
+        );
+
+        assert_eq!(
+            Ownership::registry_fqdn(
+                &FullyQualifiedDomainName::try_from("*.example.org.").unwrap(),
+                Type::A
+            ),
+            FullyQualifiedDomainName::try_from("_kubizone-a-wildcard.example.org.").unwrap()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::{fmt::Display, ops::Deref};
+
+use kube::{CustomResource, Resource as _, ResourceExt};
+use kubizone_common::FullyQualifiedDomainName;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{DomainExt, ZoneExt, ZoneKind, ZoneRef, ZoneSpec, ZoneStatus};
+
+/// Cluster-scoped equivalent of a [`Zone`](super::Zone), with identical
+/// specification and delegation semantics.
+///
+/// Since ClusterZones are not namespaced, records and zones across all
+/// namespaces can be adopted into them, subject to their delegations.
+/// ClusterZones are themselves only adopted into other ClusterZones.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[cfg_attr(
+    feature = "dev",
+    kube(group = "dev.kubi.zone", version = "v1alpha1", kind = "ClusterZone")
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(group = "kubi.zone", version = "v1alpha1", kind = "ClusterZone")
+)]
+#[kube(status = "ZoneStatus")]
+#[kube(printcolumn = r#"{"name":"domain name", "jsonPath": ".spec.domainName", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"fqdn", "jsonPath": ".status.fqdn", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"hash", "jsonPath": ".status.hash", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"serial", "jsonPath": ".status.serial", "type": "string"}"#)]
+#[cfg_attr(
+    feature = "dev",
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.dev\\.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+// The spec is shared with Zones, including its validation rules.
+#[serde(transparent)]
+pub struct ClusterZoneSpec(pub ZoneSpec);
+
+impl Deref for ClusterZoneSpec {
+    type Target = ZoneSpec;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ZoneExt for ClusterZone {
+    fn zone_spec(&self) -> &ZoneSpec {
+        &self.spec
+    }
+
+    fn zone_status(&self) -> Option<&ZoneStatus> {
+        self.status.as_ref()
+    }
+
+    fn zone_ref(&self) -> ZoneRef {
+        ZoneRef {
+            kind: ZoneKind::ClusterZone,
+            name: self.name_any(),
+            namespace: None,
+        }
+    }
+}
+
+impl DomainExt for ClusterZone {
+    fn fqdn(&self) -> Option<&FullyQualifiedDomainName> {
+        self.status.as_ref().and_then(|status| status.fqdn.as_ref())
+    }
+
+    fn parent(&self) -> Option<ZoneRef> {
+        ZoneRef::parent_of(self.meta())
+    }
+}
+
+impl Display for ClusterZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ClusterZone/{}", self.name_any())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use kube::CustomResourceExt as _;
+
+    use crate::{
+        PARENT_ZONE_ANNOTATION, PARENT_ZONE_LABEL,
+        v1alpha1::{ClusterZone, Zone, ZoneKind, ZoneRef},
+    };
+
+    #[test]
+    fn schema_matches_zone() {
+        let cluster_zone = ClusterZone::crd();
+        assert_eq!(cluster_zone.spec.scope, "Cluster");
+
+        let properties = |crd: serde_json::Value| {
+            crd.pointer("/spec/versions/0/schema/openAPIV3Schema/properties")
+                .cloned()
+                .unwrap()
+        };
+
+        assert_eq!(
+            properties(serde_json::to_value(cluster_zone).unwrap()),
+            properties(serde_json::to_value(Zone::crd()).unwrap())
+        );
+    }
+
+    #[test]
+    fn parent_label() {
+        let cluster_zone = ZoneRef {
+            kind: ZoneKind::ClusterZone,
+            name: String::from("example.org"),
+            namespace: None,
+        };
+
+        let zone = ZoneRef {
+            kind: ZoneKind::Zone,
+            name: String::from("example.org"),
+            namespace: Some(String::from("cluster")),
+        };
+
+        assert_eq!(cluster_zone.as_annotation(), "ClusterZone/example.org");
+        assert_eq!(zone.as_annotation(), "cluster/example.org");
+
+        for zone_ref in [&cluster_zone, &zone] {
+            assert_eq!(
+                ZoneRef::from_annotation(&zone_ref.as_annotation()),
+                *zone_ref
+            );
+
+            let label = zone_ref.as_label();
+            assert!(label.len() <= 63);
+            assert!(label.chars().all(|c| c.is_ascii_alphanumeric()));
+        }
+
+        assert_ne!(cluster_zone.as_label(), zone.as_label());
+
+        // Legacy labels are still understood, even for names containing dots.
+        assert_eq!(ZoneRef::from("example.org_cluster"), cluster_zone);
+        assert_eq!(ZoneRef::from("example.org.cluster"), zone);
+
+        for zone_ref in [&cluster_zone, &zone] {
+            let legacy = zone_ref.as_legacy_label().unwrap();
+            assert_eq!(ZoneRef::from(legacy), *zone_ref);
+        }
+
+        let long = ZoneRef {
+            name: "a".repeat(60),
+            ..zone.clone()
+        };
+        assert_eq!(long.as_legacy_label(), None);
+    }
+
+    #[test]
+    fn parent_annotation_precedes_label() {
+        let zone = ZoneRef {
+            kind: ZoneKind::Zone,
+            name: String::from("example.org"),
+            namespace: Some(String::from("default")),
+        };
+
+        let mut meta = ObjectMeta {
+            labels: Some(BTreeMap::from([(
+                PARENT_ZONE_LABEL.to_string(),
+                String::from("example.org.default"),
+            )])),
+            ..Default::default()
+        };
+
+        assert_eq!(ZoneRef::parent_of(&meta), Some(zone.clone()));
+
+        meta.labels = Some(BTreeMap::from([(
+            PARENT_ZONE_LABEL.to_string(),
+            zone.as_label(),
+        )]));
+        meta.annotations = Some(BTreeMap::from([(
+            PARENT_ZONE_ANNOTATION.to_string(),
+            zone.as_annotation(),
+        )]));
+
+        assert_eq!(ZoneRef::parent_of(&meta), Some(zone));
+    }
+}
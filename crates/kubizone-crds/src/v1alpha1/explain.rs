@@ -0,0 +1,401 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use kubizone_common::{FullyQualifiedDomainName, Pattern, Type};
+
+use super::{
+    Delegation, DelegationEffect, NamespaceCoverage, Verdict, ZoneEntry, ZoneExt,
+    zone::find_record_rule,
+};
+
+/// Structured explanation of how the delegations of a [`Zone`](super::Zone) were
+/// evaluated against a record, produced by [`ZoneExt::explain_record`](super::ZoneExt::explain_record).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// Fully qualified domain name of the zone, if it has been determined.
+    pub zone_fqdn: Option<FullyQualifiedDomainName>,
+
+    /// Verdict reached, identical to the one produced by [`ZoneExt::evaluate_record`](super::ZoneExt::evaluate_record).
+    pub verdict: Verdict,
+
+    /// Set if the record could not be evaluated against the
+    /// delegations at all, in which case it is denied.
+    pub precondition: Option<Precondition>,
+
+    /// Explanation of each of the zone's delegations, in evaluation order.
+    pub delegations: Vec<DelegationExplanation>,
+}
+
+/// Reasons a record cannot be evaluated against the delegations of a zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precondition {
+    /// The zone does not have a fully qualified domain name yet.
+    ZoneMissingFqdn,
+    /// The record is not within the zone.
+    OutsideZone,
+}
+
+/// Explanation of a single delegation's part in the evaluation of a record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelegationExplanation {
+    /// Index of the delegation within the zone's `delegations`.
+    pub index: usize,
+
+    pub effect: DelegationEffect,
+
+    /// Whether, and how, the delegation covers the record's namespace.
+    pub coverage: NamespaceCoverage,
+
+    /// Explanation of each record rule of the delegation. Empty
+    /// if the delegation does not cover the namespace.
+    pub rules: Vec<RuleExplanation>,
+
+    /// True if this delegation decided the verdict.
+    pub decisive: bool,
+}
+
+/// Explanation of a single record rule within a delegation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleExplanation {
+    /// Index of the rule within the delegation's `records`.
+    pub index: usize,
+
+    /// Pattern of the rule, with the zone's origin appended.
+    pub pattern: Pattern,
+
+    /// True if the pattern matches the record's domain name.
+    pub pattern_matches: bool,
+
+    /// True if the rule allows the record's type.
+    pub type_matches: bool,
+}
+
+impl RuleExplanation {
+    /// Returns true if the rule matches the record.
+    pub fn matches(&self) -> bool {
+        self.pattern_matches && self.type_matches
+    }
+}
+
+/// Explain how the delegations of `zone` are evaluated against the zone `entry`
+/// of a record, see [`ZoneExt::explain_record`](super::ZoneExt::explain_record).
+pub(super) fn explain_record<'a, Z: ZoneExt + ?Sized>(
+    zone: &Z,
+    namespace: &str,
+    namespace_labels: &BTreeMap<String, String>,
+    entry: &ZoneEntry,
+    admitted: impl IntoIterator<Item = &'a ZoneEntry>,
+) -> Explanation {
+    let Some(zone_fqdn) = zone.fqdn() else {
+        return Explanation {
+            zone_fqdn: None,
+            verdict: Verdict::default(),
+            precondition: Some(Precondition::ZoneMissingFqdn),
+            delegations: Vec::new(),
+        };
+    };
+
+    if entry.fqdn != *zone_fqdn && !entry.fqdn.is_subdomain_of(zone_fqdn) {
+        return Explanation {
+            zone_fqdn: Some(zone_fqdn.clone()),
+            verdict: Verdict::default(),
+            precondition: Some(Precondition::OutsideZone),
+            delegations: Vec::new(),
+        };
+    }
+
+    // The verdict is reached exactly the way the zone controller does, with the
+    // already admitted entries counting towards the quotas of the delegations.
+    let verdict = zone
+        .evaluate_entries(
+            admitted
+                .into_iter()
+                .map(|admitted| (namespace, namespace_labels, std::slice::from_ref(admitted)))
+                .chain(std::iter::once((
+                    namespace,
+                    namespace_labels,
+                    std::slice::from_ref(entry),
+                ))),
+        )
+        .pop()
+        .and_then(|mut verdicts| verdicts.pop())
+        .unwrap_or_default();
+
+    let decisive = find_record_rule(zone, namespace, namespace_labels, entry.type_, &entry.fqdn)
+        .map(|(delegation_index, _)| delegation_index);
+
+    let delegations = zone
+        .zone_spec()
+        .delegations
+        .iter()
+        .enumerate()
+        .map(|(index, delegation)| DelegationExplanation {
+            decisive: decisive == Some(index),
+            ..explain_delegation(
+                index,
+                delegation,
+                namespace,
+                namespace_labels,
+                zone_fqdn,
+                entry.type_,
+                &entry.fqdn,
+            )
+        })
+        .collect();
+
+    Explanation {
+        zone_fqdn: Some(zone_fqdn.clone()),
+        verdict,
+        precondition: None,
+        delegations,
+    }
+}
+
+fn explain_delegation(
+    index: usize,
+    delegation: &Delegation,
+    namespace: &str,
+    namespace_labels: &BTreeMap<String, String>,
+    zone_fqdn: &FullyQualifiedDomainName,
+    record_type: Type,
+    record_fqdn: &FullyQualifiedDomainName,
+) -> DelegationExplanation {
+    let coverage = delegation.namespace_coverage(namespace, namespace_labels);
+
+    let rules = if coverage.is_covered() {
+        delegation
+            .records
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let pattern = rule.pattern.with_origin(zone_fqdn);
+
+                RuleExplanation {
+                    index,
+                    pattern_matches: pattern.matches(record_fqdn),
+                    type_matches: rule.types.is_empty() || rule.types.contains(&record_type),
+                    pattern,
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    DelegationExplanation {
+        index,
+        effect: delegation.effect,
+        coverage,
+        rules,
+        decisive: false,
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.zone_fqdn, self.precondition) {
+            (_, Some(Precondition::ZoneMissingFqdn)) | (None, _) => {
+                return writeln!(f, "zone has no fqdn yet, so the record is denied.");
+            }
+            (Some(zone_fqdn), Some(Precondition::OutsideZone)) => {
+                return writeln!(
+                    f,
+                    "record is not within zone {zone_fqdn}, so the record is denied."
+                );
+            }
+            (Some(zone_fqdn), None) => writeln!(f, "zone {zone_fqdn} {}", self.verdict)?,
+        }
+
+        for delegation in &self.delegations {
+            writeln!(
+                f,
+                "{} delegations[{}] ({:?}): {}",
+                if delegation.decisive { "*" } else { "-" },
+                delegation.index,
+                delegation.effect,
+                delegation.coverage
+            )?;
+
+            for rule in &delegation.rules {
+                writeln!(
+                    f,
+                    "    records[{}] {}: pattern {}, type {}",
+                    rule.index,
+                    rule.pattern,
+                    if rule.pattern_matches {
+                        "matches"
+                    } else {
+                        "does not match"
+                    },
+                    if rule.type_matches {
+                        "allowed"
+                    } else {
+                        "not allowed"
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Pattern, Type};
+
+    use crate::v1alpha1::{
+        ConstraintViolation, Delegation, NamespaceCoverage, RecordDelegation, Zone, ZoneEntry,
+        ZoneExt as _, ZoneSpec, ZoneStatus,
+    };
+
+    use super::Precondition;
+
+    fn rule(pattern: &str, types: Vec<Type>) -> RecordDelegation {
+        RecordDelegation {
+            pattern: Pattern::try_from(pattern).unwrap(),
+            types,
+            ..Default::default()
+        }
+    }
+
+    fn entry(fqdn: &str, type_: Type, ttl: u32) -> ZoneEntry {
+        ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(fqdn).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl,
+            rdata: String::from("www.example.net."),
+        }
+    }
+
+    #[test]
+    fn explain_record() {
+        let zone = Zone {
+            spec: ZoneSpec {
+                domain_name: DomainName::try_from("example.org.").unwrap(),
+                zone_ref: None,
+                delegations: vec![
+                    Delegation {
+                        namespaces: vec![String::from("infra")],
+                        zones: vec![],
+                        records: vec![rule("*", vec![])],
+                        ..Default::default()
+                    },
+                    Delegation {
+                        namespaces: vec![String::from("team-*")],
+                        zones: vec![],
+                        records: vec![rule("*.dev", vec![]), rule("*", vec![Type::CNAME])],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            status: Some(ZoneStatus {
+                fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap()),
+                ..Default::default()
+            }),
+            metadata: kube::core::ObjectMeta::default(),
+        };
+
+        let www = entry("www.example.org.", Type::A, 300);
+        let explanation = zone.explain_record("team-a", &BTreeMap::new(), &www, []);
+
+        assert_eq!(
+            explanation.verdict,
+            zone.evaluate_record("team-a", &BTreeMap::new(), www.type_, &www.fqdn)
+        );
+        assert!(!explanation.verdict.is_allowed());
+
+        // The first delegation does not cover the namespace, so its rules are not evaluated.
+        assert_eq!(
+            explanation.delegations[0].coverage,
+            NamespaceCoverage::NotCovered
+        );
+        assert!(explanation.delegations[0].rules.is_empty());
+
+        // The second one does, but neither of its rules match.
+        let rules = &explanation.delegations[1].rules;
+        assert_eq!(
+            explanation.delegations[1].coverage,
+            NamespaceCoverage::Name(String::from("team-*"))
+        );
+        assert!(!rules[0].pattern_matches);
+        assert!(rules[1].pattern_matches && !rules[1].type_matches);
+
+        let cname = entry("www.example.org.", Type::CNAME, 300);
+        let explanation = zone.explain_record("team-a", &BTreeMap::new(), &cname, []);
+        assert!(explanation.verdict.is_allowed());
+        assert!(explanation.delegations[1].decisive);
+        assert_eq!(
+            explanation.verdict.rule.as_deref(),
+            Some("delegations[1].records[1]")
+        );
+
+        let explanation = zone.explain_record(
+            "team-a",
+            &BTreeMap::new(),
+            &entry("www.example.com.", Type::A, 300),
+            [],
+        );
+        assert_eq!(explanation.precondition, Some(Precondition::OutsideZone));
+    }
+
+    #[test]
+    fn explain_record_constraints() {
+        let zone = Zone {
+            spec: ZoneSpec {
+                domain_name: DomainName::try_from("example.org.").unwrap(),
+                delegations: vec![Delegation {
+                    records: vec![RecordDelegation {
+                        max_ttl: Some(600),
+                        ..rule("*", vec![])
+                    }],
+                    max_records: Some(1),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            status: Some(ZoneStatus {
+                fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap()),
+                ..Default::default()
+            }),
+            metadata: kube::core::ObjectMeta::default(),
+        };
+
+        let labels = BTreeMap::new();
+
+        let explanation = zone.explain_record(
+            "team-a",
+            &labels,
+            &entry("www.example.org.", Type::A, 3600),
+            [],
+        );
+        assert!(!explanation.verdict.is_allowed());
+        assert!(explanation.delegations[0].decisive);
+        assert_eq!(
+            explanation.verdict.violation,
+            Some(ConstraintViolation::TtlTooHigh(3600, 600))
+        );
+
+        // The quota of the delegation is already used up by an admitted record.
+        let admitted = entry("api.example.org.", Type::A, 300);
+        let www = entry("www.example.org.", Type::A, 300);
+        let explanation = zone.explain_record("team-a", &labels, &www, [&admitted]);
+        assert_eq!(
+            explanation.verdict,
+            zone.evaluate_entries([
+                ("team-a", &labels, std::slice::from_ref(&admitted)),
+                ("team-a", &labels, std::slice::from_ref(&www))
+            ])
+            .concat()
+            .pop()
+            .unwrap()
+        );
+        assert_eq!(
+            explanation.verdict.violation,
+            Some(ConstraintViolation::QuotaExceeded(1))
+        );
+    }
+}
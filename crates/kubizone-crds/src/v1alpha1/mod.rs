@@ -1,54 +1,236 @@
+mod cluster_zone;
+mod explain;
 mod record;
+mod record_set;
+mod validation;
 mod zone;
 
 use std::fmt::Display;
 
-use kubizone_common::FullyQualifiedDomainName;
+pub use cluster_zone::*;
+pub use explain::*;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, ObjectMeta};
+use kubizone_common::{DomainName, FullyQualifiedDomainName, Type};
 pub use record::*;
+pub use record_set::*;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+pub(crate) use validation::rules;
+pub use validation::*;
 pub use zone::*;
 
-/// Reference to a Zone, optionally in a specific namespace.
+use crate::{PARENT_ZONE_ANNOTATION, PARENT_ZONE_LABEL};
+
+/// Reference to a Zone, optionally in a specific namespace, or to a ClusterZone.
 #[derive(
     Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
 )]
 pub struct ZoneRef {
+    /// Kind of the referenced zone. Defaults to `Zone`.
+    #[serde(default, skip_serializing_if = "ZoneKind::is_zone")]
+    pub kind: ZoneKind,
     pub name: String,
+    /// Namespace of the referenced Zone. Ignored for ClusterZones,
+    /// which are not namespaced.
     pub namespace: Option<String>,
 }
 
-/// Functionality common between Zones and Records, such as fetching the Fully Qualified Domain Name
+/// Kind of zone referenced by a [`ZoneRef`].
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    JsonSchema,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub enum ZoneKind {
+    /// Namespaced [`Zone`].
+    #[default]
+    Zone,
+    /// Cluster-scoped [`ClusterZone`].
+    ClusterZone,
+}
+
+impl ZoneKind {
+    pub fn is_zone(&self) -> bool {
+        *self == ZoneKind::Zone
+    }
+}
+
+/// Functionality common between Zones, ClusterZones and Records, such as fetching the Fully Qualified Domain Name
 /// of the resource, or parsing the parent zone label.
 pub trait DomainExt {
     /// Fetch the computed FQDN from this resource, if one has been set.
     fn fqdn(&self) -> Option<&FullyQualifiedDomainName>;
 
-    /// Retrieve the kubi.zone/parent-zone label as a ZoneRef, if present.
+    /// Retrieve the zone this resource has been adopted by, if any.
+    ///
+    /// See [`ZoneRef::parent_of`].
     fn parent(&self) -> Option<ZoneRef>;
 }
 
+/// Functionality common between resources which are adopted into zones
+/// as records, namely [`Record`]s and [`RecordSet`]s.
+pub trait RecordExt: DomainExt {
+    /// Domain name of the resource, as specified.
+    fn domain_name(&self) -> &DomainName;
+
+    /// Zone the resource claims to be part of, if any.
+    fn zone_ref(&self) -> Option<&ZoneRef>;
+
+    /// Type of the records produced by the resource.
+    fn type_(&self) -> Type;
+
+    /// Status conditions of the resource.
+    fn conditions(&self) -> &[Condition];
+
+    /// Zone entries produced by the resource, once adopted at `fqdn`.
+    ///
+    /// Entries without an explicit Time-to-Live use `default_ttl`.
+    fn entries(&self, fqdn: &FullyQualifiedDomainName, default_ttl: u32) -> Vec<ZoneEntry>;
+}
+
+/// Suffix distinguishing ClusterZones from Zones in legacy parent zone labels.
+///
+/// Neither names nor namespaces may contain underscores, so
+/// the suffix can never be part of a Zone's label.
+const CLUSTER_ZONE_LABEL_SUFFIX: &str = "_cluster";
+
+/// Prefix distinguishing ClusterZones from Zones in parent zone annotations.
+///
+/// Namespaces must be lowercase, so the prefix can never be part of a Zone's annotation.
+const CLUSTER_ZONE_ANNOTATION_PREFIX: &str = "ClusterZone/";
+
 impl ZoneRef {
     /// Serialize the ZoneRef into a label-compatible format.
+    ///
+    /// Names of zones can be longer than label values, so the label is a hash of
+    /// the [annotation](Self::as_annotation), which can't be parsed back into a ZoneRef.
     pub fn as_label(&self) -> String {
-        if let Some(namespace) = &self.namespace {
-            format!("{}.{namespace}", self.name)
+        Sha256::digest(self.as_annotation())
+            .iter()
+            .take(20)
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Serialize the ZoneRef into the legacy label format parsed by [`ZoneRef::from`],
+    /// if it fits into a label value, for finding resources adopted before labels
+    /// were hashed.
+    pub fn as_legacy_label(&self) -> Option<String> {
+        let label = match (self.kind, &self.namespace) {
+            (ZoneKind::ClusterZone, _) => format!("{}{CLUSTER_ZONE_LABEL_SUFFIX}", self.name),
+            (ZoneKind::Zone, Some(namespace)) => format!("{}.{namespace}", self.name),
+            (ZoneKind::Zone, None) => self.name.clone(),
+        };
+
+        // Names and namespaces only contain characters valid in label values.
+        (label.len() <= 63).then_some(label)
+    }
+
+    /// Serialize the ZoneRef into an unambiguous annotation value:
+    /// `namespace/name` for Zones, and `ClusterZone/name` for ClusterZones.
+    pub fn as_annotation(&self) -> String {
+        match (self.kind, &self.namespace) {
+            (ZoneKind::ClusterZone, _) => format!("{CLUSTER_ZONE_ANNOTATION_PREFIX}{}", self.name),
+            (ZoneKind::Zone, Some(namespace)) => format!("{namespace}/{}", self.name),
+            (ZoneKind::Zone, None) => self.name.clone(),
+        }
+    }
+
+    /// Parse a ZoneRef serialized by [`ZoneRef::as_annotation`].
+    pub fn from_annotation(annotation: &str) -> Self {
+        if let Some(name) = annotation.strip_prefix(CLUSTER_ZONE_ANNOTATION_PREFIX) {
+            ZoneRef {
+                kind: ZoneKind::ClusterZone,
+                name: name.to_string(),
+                namespace: None,
+            }
+        } else if let Some((namespace, name)) = annotation.split_once('/') {
+            ZoneRef {
+                kind: ZoneKind::Zone,
+                name: name.to_string(),
+                namespace: Some(namespace.to_string()),
+            }
         } else {
-            self.name.clone()
+            ZoneRef {
+                kind: ZoneKind::Zone,
+                name: annotation.to_string(),
+                namespace: None,
+            }
+        }
+    }
+
+    /// Zone which the resource with the given metadata has been adopted by, if any.
+    ///
+    /// Resources adopted before the [`PARENT_ZONE_ANNOTATION`] was introduced
+    /// only have the [`PARENT_ZONE_LABEL`], which is parsed in its legacy
+    /// `name.namespace` form instead.
+    pub fn parent_of(meta: &ObjectMeta) -> Option<Self> {
+        if let Some(annotation) = meta
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(PARENT_ZONE_ANNOTATION))
+        {
+            return Some(ZoneRef::from_annotation(annotation));
+        }
+
+        meta.labels
+            .as_ref()?
+            .get(PARENT_ZONE_LABEL)
+            .map(ZoneRef::from)
+    }
+
+    /// Resolve the reference relative to a resource in `namespace`, if any.
+    ///
+    /// Zones referenced without a namespace are in the same namespace as the
+    /// referencing resource, while ClusterZones never have a namespace.
+    pub fn in_namespace(&self, namespace: Option<&str>) -> ZoneRef {
+        ZoneRef {
+            kind: self.kind,
+            name: self.name.clone(),
+            namespace: match self.kind {
+                ZoneKind::Zone => self
+                    .namespace
+                    .clone()
+                    .or_else(|| namespace.map(str::to_string)),
+                ZoneKind::ClusterZone => None,
+            },
         }
     }
 }
 
+/// Parse a legacy parent zone label, in the form of `name.namespace`
+/// for Zones, and `name_cluster` for ClusterZones.
+///
+/// Names of zones might contain dots, but namespaces can't,
+/// so the namespace follows the last dot.
 impl From<&str> for ZoneRef {
     fn from(s: &str) -> Self {
-        if let Some((name, namespace)) = s.split_once('.') {
+        if let Some(name) = s.strip_suffix(CLUSTER_ZONE_LABEL_SUFFIX) {
             ZoneRef {
+                kind: ZoneKind::ClusterZone,
+                name: name.to_string(),
+                namespace: None,
+            }
+        } else if let Some((name, namespace)) = s.rsplit_once('.') {
+            ZoneRef {
+                kind: ZoneKind::Zone,
                 name: name.to_string(),
                 namespace: Some(namespace.to_string()),
             }
         } else {
             // TODO: Might be valuable to do some validation here.
             ZoneRef {
+                kind: ZoneKind::Zone,
                 name: s.to_string(),
                 namespace: None,
             }
@@ -70,10 +252,10 @@ impl From<String> for ZoneRef {
 
 impl Display for ZoneRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(namespace) = &self.namespace {
-            write!(f, "{namespace}/{}", self.name)
-        } else {
-            f.write_str(&self.name)
+        match (self.kind, &self.namespace) {
+            (ZoneKind::ClusterZone, _) => write!(f, "ClusterZone/{}", self.name),
+            (ZoneKind::Zone, Some(namespace)) => write!(f, "{namespace}/{}", self.name),
+            (ZoneKind::Zone, None) => f.write_str(&self.name),
         }
     }
 }
@@ -1,13 +1,12 @@
 use std::fmt::Display;
 
-use kube::{CustomResource, Resource, ResourceExt};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kube::{CustomResource, KubeSchema, Resource, ResourceExt};
 use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, RecordIdent, Type};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::PARENT_ZONE_LABEL;
-
-use super::{DomainExt, ZoneRef};
+use super::{DomainExt, RecordExt, ZoneEntry, ZoneRef};
 
 #[derive(
     CustomResource,
@@ -16,7 +15,7 @@ use super::{DomainExt, ZoneRef};
     Clone,
     Debug,
     Default,
-    JsonSchema,
+    KubeSchema,
     Hash,
     PartialEq,
     Eq,
@@ -45,9 +44,19 @@ use super::{DomainExt, ZoneRef};
 #[kube(printcolumn = r#"{"name":"type", "jsonPath": ".spec.type", "type": "string"}"#)]
 #[kube(printcolumn = r#"{"name":"data", "jsonPath": ".spec.rdata", "type": "string"}"#)]
 #[kube(printcolumn = r#"{"name":"fqdn", "jsonPath": ".status.fqdn", "type": "string"}"#)]
-#[kube(
-    printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.labels.kubi\\.zone/parent-zone", "type": "string"}"#
+#[cfg_attr(
+    feature = "dev",
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.dev\\.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
 )]
+#[x_kube(validation = super::validation::rules::domain_name_or_zone_ref())]
 #[serde(rename_all = "camelCase")]
 pub struct RecordSpec {
     pub domain_name: DomainName,
@@ -56,13 +65,19 @@ pub struct RecordSpec {
     pub type_: Type,
     #[serde(default = "super::defaults::class")]
     pub class: Class,
+    #[x_kube(validation = super::validation::rules::ttl())]
     pub ttl: Option<u32>,
     pub rdata: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 pub struct RecordStatus {
     pub fqdn: Option<FullyQualifiedDomainName>,
+
+    /// Latest observations of the record's state, such as whether
+    /// its parent zone admitted it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
 }
 
 impl DomainExt for Record {
@@ -71,11 +86,38 @@ impl DomainExt for Record {
     }
 
     fn parent(&self) -> Option<ZoneRef> {
-        self.meta()
-            .labels
-            .as_ref()?
-            .get(PARENT_ZONE_LABEL)
-            .map(ZoneRef::from)
+        ZoneRef::parent_of(self.meta())
+    }
+}
+
+impl RecordExt for Record {
+    fn domain_name(&self) -> &DomainName {
+        &self.spec.domain_name
+    }
+
+    fn zone_ref(&self) -> Option<&ZoneRef> {
+        self.spec.zone_ref.as_ref()
+    }
+
+    fn type_(&self) -> Type {
+        self.spec.type_
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn entries(&self, fqdn: &FullyQualifiedDomainName, default_ttl: u32) -> Vec<ZoneEntry> {
+        vec![ZoneEntry {
+            fqdn: fqdn.clone(),
+            type_: self.spec.type_,
+            class: self.spec.class,
+            ttl: self.spec.ttl.unwrap_or(default_ttl),
+            rdata: self.spec.rdata.clone(),
+        }]
     }
 }
 
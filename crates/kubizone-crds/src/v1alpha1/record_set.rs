@@ -0,0 +1,178 @@
+use std::fmt::Display;
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kube::{CustomResource, KubeSchema, Resource, ResourceExt};
+use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Type};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{DomainExt, RecordExt, ZoneEntry, ZoneRef, rules};
+
+/// Set of records sharing a domain name, type and Time-to-Live, but with
+/// different rdata, such as round-robin `A` records or multiple `MX` records.
+#[derive(
+    CustomResource,
+    Deserialize,
+    Serialize,
+    Clone,
+    Debug,
+    Default,
+    KubeSchema,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+// The 'dev' feature flag puts the resource in a separate dev.kubi.zone group,
+// instead of the real one. This way you can have the production and dev versions
+// of kubizone resources running side by side, without interfering with each other.
+#[cfg_attr(
+    feature = "dev",
+    kube(
+        group = "dev.kubi.zone",
+        version = "v1alpha1",
+        kind = "RecordSet",
+        namespaced
+    )
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(
+        group = "kubi.zone",
+        version = "v1alpha1",
+        kind = "RecordSet",
+        namespaced
+    )
+)]
+#[kube(status = "RecordSetStatus")]
+#[kube(printcolumn = r#"{"name":"domain name", "jsonPath": ".spec.domainName", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"class", "jsonPath": ".spec.class", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"type", "jsonPath": ".spec.type", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"data", "jsonPath": ".spec.rdata", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"fqdn", "jsonPath": ".status.fqdn", "type": "string"}"#)]
+#[cfg_attr(
+    feature = "dev",
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.dev\\.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+#[x_kube(validation = rules::domain_name_or_zone_ref())]
+#[serde(rename_all = "camelCase")]
+pub struct RecordSetSpec {
+    pub domain_name: DomainName,
+    pub zone_ref: Option<ZoneRef>,
+    #[serde(rename = "type")]
+    pub type_: Type,
+    #[serde(default = "super::defaults::class")]
+    pub class: Class,
+    #[x_kube(validation = rules::ttl())]
+    pub ttl: Option<u32>,
+    /// Record data of each record in the set.
+    #[schemars(length(min = 1))]
+    pub rdata: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct RecordSetStatus {
+    pub fqdn: Option<FullyQualifiedDomainName>,
+
+    /// Latest observations of the record set's state, such as whether
+    /// its parent zone admitted it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+}
+
+impl DomainExt for RecordSet {
+    fn fqdn(&self) -> Option<&FullyQualifiedDomainName> {
+        self.status.as_ref().and_then(|status| status.fqdn.as_ref())
+    }
+
+    fn parent(&self) -> Option<ZoneRef> {
+        ZoneRef::parent_of(self.meta())
+    }
+}
+
+impl RecordExt for RecordSet {
+    fn domain_name(&self) -> &DomainName {
+        &self.spec.domain_name
+    }
+
+    fn zone_ref(&self) -> Option<&ZoneRef> {
+        self.spec.zone_ref.as_ref()
+    }
+
+    fn type_(&self) -> Type {
+        self.spec.type_
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn entries(&self, fqdn: &FullyQualifiedDomainName, default_ttl: u32) -> Vec<ZoneEntry> {
+        self.spec
+            .rdata
+            .iter()
+            .map(|rdata| ZoneEntry {
+                fqdn: fqdn.clone(),
+                type_: self.spec.type_,
+                class: self.spec.class,
+                ttl: self.spec.ttl.unwrap_or(default_ttl),
+                rdata: rdata.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Display for RecordSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Unwrap safety: RecordSets are namespaced and therefore always have a name.
+        write!(
+            f,
+            "{}/{}",
+            self.metadata.namespace.as_ref().unwrap(),
+            self.name_any()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{DomainName, FullyQualifiedDomainName, Type};
+
+    use crate::v1alpha1::{RecordExt as _, RecordSet, RecordSetSpec};
+
+    #[test]
+    fn entries() {
+        let set = RecordSet::new(
+            "www",
+            RecordSetSpec {
+                domain_name: DomainName::try_from("www.example.org.").unwrap(),
+                type_: Type::A,
+                rdata: vec![String::from("192.168.0.1"), String::from("192.168.0.2")],
+                ..Default::default()
+            },
+        );
+
+        let fqdn = FullyQualifiedDomainName::try_from("www.example.org.").unwrap();
+        let entries = set.entries(&fqdn, 300);
+
+        assert_eq!(entries.len(), 2);
+        assert!(
+            entries
+                .iter()
+                .all(|entry| entry.ttl == 300 && entry.fqdn == fqdn)
+        );
+        assert_eq!(entries[1].rdata, "192.168.0.2");
+    }
+}
@@ -0,0 +1,264 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use kubizone_common::{DomainName, FullyQualifiedDomainName, PartiallyQualifiedDomainName, Type};
+use thiserror::Error;
+
+use super::{RecordSetSpec, RecordSpec, ZoneRef, ZoneSpec};
+
+/// Produced when a Record or Zone specification is invalid on its own,
+/// regardless of which other resources exist in the cluster.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SpecError {
+    /// Resources with a `zoneRef` must use a partially qualified `domainName`.
+    #[error("domainName {0} is fully qualified, and therefore cannot be combined with zoneRef {1}")]
+    ZoneRefWithFullyQualifiedName(FullyQualifiedDomainName, ZoneRef),
+    /// Resources without a `zoneRef` must use a fully qualified `domainName`.
+    #[error("domainName {0} is not fully qualified, and therefore requires a zoneRef")]
+    PartialNameWithoutZoneRef(PartiallyQualifiedDomainName),
+    /// The record's rdata cannot be parsed according to its type.
+    #[error("rdata {rdata:?} is not valid for {type_} records: {reason}")]
+    InvalidRdata {
+        type_: Type,
+        rdata: String,
+        reason: &'static str,
+    },
+}
+
+/// Highest Time-to-Live permitted by [RFC 2181](https://www.rfc-editor.org/rfc/rfc2181#section-8).
+pub const MAX_TTL: u32 = i32::MAX as u32;
+
+/// CEL validation rules embedded into the generated custom resource definitions,
+/// so the invariants they express are enforced by the API server itself, even
+/// in clusters where the admission webhook is not deployed.
+pub(crate) mod rules {
+    use kube::core::{Message, Rule};
+
+    use super::MAX_TTL;
+
+    pub fn domain_name_or_zone_ref() -> Rule {
+        Rule::new("has(self.zoneRef) != self.domainName.endsWith('.')").message(
+            "exactly one of zoneRef or a fully qualified domainName (ending in a '.') must be set",
+        )
+    }
+
+    pub fn ttl() -> Rule {
+        Rule::new(format!("self <= {MAX_TTL}")).message(Message::Message(format!(
+            "must not exceed {MAX_TTL} seconds"
+        )))
+    }
+
+    pub fn min_ttl_below_max_ttl() -> Rule {
+        Rule::new("!has(self.minTtl) || !has(self.maxTtl) || self.minTtl <= self.maxTtl")
+            .message("minTtl must not exceed maxTtl")
+    }
+}
+
+/// Verify that a resource has either a `zoneRef` and a partially qualified
+/// domain name, or a fully qualified domain name without a `zoneRef`.
+fn validate_domain_name(
+    domain_name: &DomainName,
+    zone_ref: Option<&ZoneRef>,
+) -> Result<(), SpecError> {
+    match (domain_name, zone_ref) {
+        (DomainName::Full(fqdn), Some(zone_ref)) => Err(SpecError::ZoneRefWithFullyQualifiedName(
+            fqdn.clone(),
+            zone_ref.clone(),
+        )),
+        (DomainName::Partial(pqdn), None) => {
+            Err(SpecError::PartialNameWithoutZoneRef(pqdn.clone()))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl RecordSpec {
+    /// Validate the domain name, zone reference and rdata of the record.
+    pub fn validate(&self) -> Result<(), SpecError> {
+        validate_domain_name(&self.domain_name, self.zone_ref.as_ref())?;
+
+        validate_rdata(self.type_, &self.rdata).map_err(|reason| SpecError::InvalidRdata {
+            type_: self.type_,
+            rdata: self.rdata.clone(),
+            reason,
+        })
+    }
+}
+
+impl RecordSetSpec {
+    /// Validate the domain name, zone reference and rdata of each record in the set.
+    pub fn validate(&self) -> Result<(), SpecError> {
+        validate_domain_name(&self.domain_name, self.zone_ref.as_ref())?;
+
+        for rdata in &self.rdata {
+            validate_rdata(self.type_, rdata).map_err(|reason| SpecError::InvalidRdata {
+                type_: self.type_,
+                rdata: rdata.clone(),
+                reason,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ZoneSpec {
+    /// Validate the domain name and zone reference of the zone.
+    pub fn validate(&self) -> Result<(), SpecError> {
+        validate_domain_name(&self.domain_name, self.zone_ref.as_ref())
+    }
+}
+
+/// Verify that `rdata` is well-formed for records of type `type_`.
+///
+/// Only the most common record types are checked in depth, while
+/// the rdata of any other type is merely required to be non-empty.
+pub fn validate_rdata(type_: Type, rdata: &str) -> Result<(), &'static str> {
+    let fields: Vec<&str> = rdata.split_whitespace().collect();
+
+    if fields.is_empty() {
+        return Err("rdata is empty");
+    }
+
+    let domain_name = |field: &str| field == "." || DomainName::try_from(field).is_ok();
+
+    match (type_, fields.as_slice()) {
+        (Type::A, [address]) => address
+            .parse::<Ipv4Addr>()
+            .map(|_| ())
+            .map_err(|_| "expected an IPv4 address"),
+        (Type::A, _) => Err("expected an IPv4 address"),
+        (Type::AAAA, [address]) => address
+            .parse::<Ipv6Addr>()
+            .map(|_| ())
+            .map_err(|_| "expected an IPv6 address"),
+        (Type::AAAA, _) => Err("expected an IPv6 address"),
+        (Type::CNAME | Type::DNAME | Type::NS | Type::PTR, [target]) if domain_name(target) => {
+            Ok(())
+        }
+        (Type::CNAME | Type::DNAME | Type::NS | Type::PTR, _) => {
+            Err("expected a single domain name")
+        }
+        (Type::MX, [preference, exchange])
+            if preference.parse::<u16>().is_ok() && domain_name(exchange) =>
+        {
+            Ok(())
+        }
+        (Type::MX, _) => Err("expected a preference and a domain name"),
+        (Type::SRV, [priority, weight, port, target])
+            if [priority, weight, port]
+                .iter()
+                .all(|field| field.parse::<u16>().is_ok())
+                && domain_name(target) =>
+        {
+            Ok(())
+        }
+        (Type::SRV, _) => Err("expected a priority, weight, port and domain name"),
+        (Type::CAA, [flags, tag, _, ..])
+            if flags.parse::<u8>().is_ok() && tag.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            Ok(())
+        }
+        (Type::CAA, _) => Err("expected flags, a tag and a value"),
+        (Type::TXT, _) if rdata.chars().filter(|c| *c == '"').count() % 2 != 0 => {
+            Err("unbalanced quotes")
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::{DomainName, Type};
+
+    use kube::CustomResourceExt as _;
+
+    use crate::v1alpha1::{Record, RecordSpec, Zone, ZoneKind, ZoneRef};
+
+    use super::{SpecError, validate_rdata};
+
+    #[test]
+    fn domain_name_and_zone_ref() {
+        let record = RecordSpec {
+            domain_name: DomainName::try_from("www.example.org.").unwrap(),
+            zone_ref: Some(ZoneRef {
+                kind: ZoneKind::Zone,
+                name: String::from("example-org"),
+                namespace: None,
+            }),
+            type_: Type::A,
+            rdata: String::from("192.168.0.1"),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            record.validate(),
+            Err(SpecError::ZoneRefWithFullyQualifiedName(..))
+        ));
+
+        let record = RecordSpec {
+            domain_name: DomainName::try_from("www").unwrap(),
+            zone_ref: None,
+            ..record
+        };
+
+        assert!(matches!(
+            record.validate(),
+            Err(SpecError::PartialNameWithoutZoneRef(..))
+        ));
+    }
+
+    /// Schema of the `spec` of the given `version` of a custom resource definition.
+    fn spec_schema(crd: &serde_json::Value, version: &str) -> serde_json::Value {
+        let version = crd["spec"]["versions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|candidate| candidate["name"] == version)
+            .unwrap();
+
+        version["schema"]["openAPIV3Schema"]["properties"]["spec"].clone()
+    }
+
+    #[test]
+    fn crd_validation_rules() {
+        let crd = serde_json::to_value(Record::crd()).unwrap();
+        let spec = spec_schema(&crd, "v1alpha1");
+
+        assert_eq!(
+            spec["x-kubernetes-validations"][0]["rule"],
+            "has(self.zoneRef) != self.domainName.endsWith('.')"
+        );
+        assert_eq!(
+            spec["properties"]["ttl"]["x-kubernetes-validations"][0]["rule"],
+            "self <= 2147483647"
+        );
+
+        // SOA timers are not ordered by validation rules, since rejecting existing
+        // zones on unrelated updates would be worse than tolerating unusual timers.
+        let crd = serde_json::to_value(Zone::crd()).unwrap();
+        let spec = spec_schema(&crd, "v1alpha1");
+
+        assert_eq!(
+            spec["x-kubernetes-validations"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn rdata() {
+        assert!(validate_rdata(Type::A, "192.168.0.1").is_ok());
+        assert!(validate_rdata(Type::A, "192.168.0").is_err());
+        assert!(validate_rdata(Type::AAAA, "2001:db8::1").is_ok());
+        assert!(validate_rdata(Type::AAAA, "192.168.0.1").is_err());
+        assert!(validate_rdata(Type::CNAME, "www.example.org.").is_ok());
+        assert!(validate_rdata(Type::CNAME, "www.example.org. extra").is_err());
+        assert!(validate_rdata(Type::MX, "10 mail.example.org.").is_ok());
+        assert!(validate_rdata(Type::MX, "mail.example.org.").is_err());
+        assert!(validate_rdata(Type::SRV, "10 5 443 api.example.org.").is_ok());
+        assert!(validate_rdata(Type::SRV, "10 5 https api.example.org.").is_err());
+        assert!(validate_rdata(Type::CAA, "0 issue \"letsencrypt.org\"").is_ok());
+        assert!(validate_rdata(Type::TXT, "\"v=spf1 -all\"").is_ok());
+        assert!(validate_rdata(Type::TXT, "\"v=spf1 -all").is_err());
+        assert!(validate_rdata(Type::TXT, "").is_err());
+    }
+}
@@ -1,14 +1,25 @@
-use std::fmt::Display;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
 
-use kube::{CustomResource, Resource as _, ResourceExt, core::object::HasSpec};
-use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Pattern, RecordIdent, Type};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, LabelSelector};
+use kube::{
+    CustomResource, KubeSchema, Resource as _, ResourceExt,
+    core::{Selector, SelectorExt as _},
+};
+use kubizone_common::{
+    Class, DomainName, FullyQualifiedDomainName, Pattern, PatternSegment, RecordIdent, Type,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tracing::*;
 
-use crate::PARENT_ZONE_LABEL;
-
-use super::{DomainExt, Record, ZoneRef};
+use super::{
+    DomainExt, Explanation, Record, ZoneKind, ZoneRef, explain::explain_record, validation::rules,
+};
 
 pub mod defaults {
 
@@ -52,21 +63,14 @@ pub mod defaults {
     pub(super) const fn negative_response_cache() -> u32 {
         NEGATIVE_RESPONSE_CACHE
     }
+
+    pub(super) fn record_kind() -> String {
+        String::from("Record")
+    }
 }
 
 #[derive(
-    Default,
-    CustomResource,
-    Deserialize,
-    Serialize,
-    Clone,
-    Debug,
-    JsonSchema,
-    Hash,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
+    Default, CustomResource, Deserialize, Serialize, Clone, Debug, KubeSchema, Hash, PartialEq,
 )]
 // The 'dev' feature flag puts the resource in a separate dev.kubi.zone group,
 // instead of the real one. This way you can have the production and dev versions
@@ -92,15 +96,16 @@ pub mod defaults {
 #[cfg_attr(
     feature = "dev",
     kube(
-        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.labels.kubi\\.zone/parent-zone", "type": "string"}"#
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.dev\\.kubi\\.zone/parent-zone", "type": "string"}"#
     )
 )]
 #[cfg_attr(
     not(feature = "dev"),
     kube(
-        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.labels.dev\\.kubi\\.zone/parent-zone", "type": "string"}"#
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.kubi\\.zone/parent-zone", "type": "string"}"#
     )
 )]
+#[x_kube(validation = rules::domain_name_or_zone_ref())]
 #[serde(rename_all = "camelCase")]
 pub struct ZoneSpec {
     pub domain_name: DomainName,
@@ -118,6 +123,7 @@ pub struct ZoneSpec {
     /// Time-to-Live. Represents how long (in seconds) recursive resolvers should
     /// keep this record in their cache.
     #[serde(default = "defaults::ttl")]
+    #[x_kube(validation = rules::ttl())]
     pub ttl: u32,
 
     /// Number of seconds after which secondary name servers should
@@ -127,6 +133,7 @@ pub struct ZoneSpec {
     ///
     /// [^1]: <https://www.ripe.net/publications/docs/ripe-203>
     #[serde(default = "defaults::refresh")]
+    #[x_kube(validation = rules::ttl())]
     pub refresh: u32,
 
     /// Number of seconds after which secondary name servers should
@@ -139,6 +146,7 @@ pub struct ZoneSpec {
     ///
     /// [^1]: <https://www.ripe.net/publications/docs/ripe-203>
     #[serde(default = "defaults::retry")]
+    #[x_kube(validation = rules::ttl())]
     pub retry: u32,
 
     /// Number of seconds after which secondary name servers should
@@ -150,6 +158,7 @@ pub struct ZoneSpec {
     ///
     /// [^1]: <https://www.ripe.net/publications/docs/ripe-203>
     #[serde(default = "defaults::expire")]
+    #[x_kube(validation = rules::ttl())]
     pub expire: u32,
 
     /// Used in calculating the time to live for purposes of negative caching.
@@ -163,88 +172,309 @@ pub struct ZoneSpec {
     ///
     /// [^1]: <https://www.ripe.net/publications/docs/ripe-203>
     #[serde(default = "defaults::negative_response_cache")]
+    #[x_kube(validation = rules::ttl())]
     pub negative_response_cache: u32,
 }
 
-impl Zone {
+/// Functionality common between [`Zone`]s and [`ClusterZone`]s, namely
+/// evaluating their delegations against records and sub-zones.
+pub trait ZoneExt: DomainExt + Display {
+    /// Specification of the zone.
+    fn zone_spec(&self) -> &ZoneSpec;
+
+    /// Status of the zone, if it has been set.
+    fn zone_status(&self) -> Option<&ZoneStatus>;
+
     /// Produce a zoneRef pointing to this zone
-    pub fn zone_ref(&self) -> ZoneRef {
-        ZoneRef {
-            name: self.name_any(),
-            namespace: self.namespace(),
-        }
-    }
+    fn zone_ref(&self) -> ZoneRef;
 
     /// Retrieve the hash value of this zone, if present.
-    pub fn hash(&self) -> Option<&str> {
-        self.status
-            .as_ref()
-            .and_then(|status| status.hash.as_deref())
+    fn hash(&self) -> Option<&str> {
+        self.zone_status().and_then(|status| status.hash.as_deref())
     }
 
     /// Retrieve the computed serial of this zone, if present.
-    pub fn serial(&self) -> Option<u32> {
-        self.status.as_ref().and_then(|status| status.serial)
+    fn serial(&self) -> Option<u32> {
+        self.zone_status().and_then(|status| status.serial)
     }
 
-    /// Validate that the given Record is allowed, given the delegations of this Zone.
-    pub fn validate_record(&self, record: &Record) -> bool {
-        let Some(parent_fqdn) = self.fqdn() else {
-            trace!("parent zone {self} has no fqdn, and can therefore not validate record");
-            return false;
-        };
+    /// Evaluate the delegations of this zone against a record of type `record_type`
+    /// at `record_fqdn`, located in `namespace` with the given `namespace_labels`.
+    ///
+    /// Delegations are evaluated in order, and the first delegation which covers the
+    /// namespace and has a record rule matching the record decides the verdict. If no
+    /// delegation matches, the record is denied.
+    ///
+    /// Constraints such as TTL limits are not checked, see [`ZoneExt::evaluate_entries`].
+    fn evaluate_record(
+        &self,
+        namespace: &str,
+        namespace_labels: &BTreeMap<String, String>,
+        record_type: Type,
+        record_fqdn: &FullyQualifiedDomainName,
+    ) -> Verdict {
+        let spec = self.zone_spec();
+        let verdict = find_record_rule(self, namespace, namespace_labels, record_type, record_fqdn)
+            .map(|(delegation, record)| spec.record_verdict(delegation, record))
+            .unwrap_or_default();
 
-        let Some(record_fqdn) = record.fqdn() else {
-            trace!("record {record} has no fqdn, and can therefore not be validated");
-            return false;
-        };
+        debug!(
+            "zone {} {verdict} record {record_fqdn} of type {record_type}",
+            spec.domain_name
+        );
+        verdict
+    }
 
-        if !record
-            .fqdn()
-            .is_some_and(|fqdn| fqdn == parent_fqdn || fqdn.is_subdomain_of(parent_fqdn))
-        {
-            trace!("record {record_fqdn} is not a subdomain of {parent_fqdn}");
-            return false;
-        }
+    /// Evaluate the delegations of this zone against a list of `(namespace, namespace labels, entries)`
+    /// candidates, like [`ZoneExt::evaluate_record`], additionally enforcing the TTL, target
+    /// and record quota constraints of the deciding rules. Produces a verdict for each entry,
+    /// grouped by candidate.
+    ///
+    /// Quotas are counted in the order the candidates are given. The entries of a candidate,
+    /// such as the values of a record set, are admitted together, so they only count towards
+    /// the quotas if all of them are allowed.
+    fn evaluate_entries<'a>(
+        &self,
+        candidates: impl IntoIterator<Item = (&'a str, &'a BTreeMap<String, String>, &'a [ZoneEntry])>,
+    ) -> Vec<Vec<Verdict>> {
+        let spec = self.zone_spec();
+        let mut admitted = BTreeMap::<(usize, &str), u32>::new();
 
-        if self.spec().delegations.iter().any(|delegation| {
-            delegation.covers_namespace(&record.namespace().unwrap_or_default())
-                && delegation.validate_record(parent_fqdn, record.spec.type_, record_fqdn)
-        }) {
-            debug!("zone {parent_fqdn} allows delegation to record {record_fqdn}");
-            true
-        } else {
-            trace!("zone {parent_fqdn} forbid delegation to record {record_fqdn}");
-            false
-        }
+        candidates
+            .into_iter()
+            .map(|(namespace, namespace_labels, entries)| {
+                let mut pending = admitted.clone();
+
+                let verdicts: Vec<Verdict> = entries
+                    .iter()
+                    .map(|entry| {
+                        let Some((delegation_index, record_index)) = find_record_rule(
+                            self,
+                            namespace,
+                            namespace_labels,
+                            entry.type_,
+                            &entry.fqdn,
+                        ) else {
+                            return Verdict::default();
+                        };
+
+                        let mut verdict = spec.record_verdict(delegation_index, record_index);
+                        if !verdict.is_allowed() {
+                            return verdict;
+                        }
+
+                        let delegation = &spec.delegations[delegation_index];
+                        let count = pending.entry((delegation_index, namespace)).or_default();
+
+                        verdict.violation = delegation.records[record_index]
+                            .check_constraints(entry)
+                            .err()
+                            .or_else(|| {
+                                delegation
+                                    .max_records
+                                    .filter(|max_records| *count >= *max_records)
+                                    .map(ConstraintViolation::QuotaExceeded)
+                            });
+
+                        if verdict.violation.is_some() {
+                            verdict.effect = DelegationEffect::Deny;
+                        } else {
+                            *count += 1;
+                        }
+
+                        debug!(
+                            "zone {} {verdict} entry {} of type {}",
+                            spec.domain_name, entry.fqdn, entry.type_
+                        );
+                        verdict
+                    })
+                    .collect();
+
+                if verdicts.iter().all(Verdict::is_allowed) {
+                    admitted = pending;
+                }
+
+                verdicts
+            })
+            .collect()
     }
 
-    /// Validate that the given Zone is allowed by the delgations specified in this Zone.
-    pub fn validate_zone(&self, zone: &Zone) -> bool {
+    /// Evaluate the delegations of this zone against a zone at `zone_fqdn`, located
+    /// in `namespace` with the given `namespace_labels`.
+    ///
+    /// Evaluation order is the same as for [`ZoneExt::evaluate_record`].
+    fn evaluate_zone(
+        &self,
+        namespace: &str,
+        namespace_labels: &BTreeMap<String, String>,
+        zone_fqdn: &FullyQualifiedDomainName,
+    ) -> Verdict {
         let Some(parent_fqdn) = self.fqdn() else {
             trace!("zone {self}'s fqdn is not defined.");
+            return Verdict::default();
+        };
+
+        if !zone_fqdn.is_subdomain_of(parent_fqdn) {
+            trace!("zone {} is not a subdomain of {}", zone_fqdn, parent_fqdn);
+            return Verdict::default();
+        }
+
+        let spec = self.zone_spec();
+        let verdict = spec
+            .find_rule(namespace, namespace_labels, |delegation| {
+                delegation.match_zone(parent_fqdn, zone_fqdn)
+            })
+            .map(|(delegation_index, zone_index)| Verdict {
+                effect: spec.delegations[delegation_index].effect,
+                rule: Some(format!(
+                    "delegations[{delegation_index}].zones[{zone_index}]"
+                )),
+                violation: None,
+            })
+            .unwrap_or_default();
+
+        debug!("zone {parent_fqdn} {verdict} zone {zone_fqdn}");
+        verdict
+    }
+
+    /// Validate that the given Record is allowed, given the delegations of this zone.
+    ///
+    /// `namespace_labels` are the labels of the Record's namespace, used for
+    /// evaluating the [`Delegation::namespace_selector`].
+    fn validate_record(
+        &self,
+        record: &Record,
+        namespace_labels: &BTreeMap<String, String>,
+    ) -> bool {
+        let Some(record_fqdn) = record.fqdn() else {
+            trace!("record {record} has no fqdn, and can therefore not be validated");
             return false;
         };
 
+        self.evaluate_record(
+            &record.namespace().unwrap_or_default(),
+            namespace_labels,
+            record.spec.type_,
+            record_fqdn,
+        )
+        .is_allowed()
+    }
+
+    /// Validate that the given zone is allowed by the delgations specified in this zone.
+    ///
+    /// `namespace_labels` are the labels of the given zone's namespace, used for
+    /// evaluating the [`Delegation::namespace_selector`]. ClusterZones have no
+    /// namespace, and are therefore only covered by delegations covering all namespaces.
+    fn validate_zone(
+        &self,
+        zone: &(impl ZoneExt + ?Sized),
+        namespace_labels: &BTreeMap<String, String>,
+    ) -> bool {
         let Some(zone_fqdn) = zone.fqdn() else {
             trace!("zone {self}'s fqdn is not defined.");
             return false;
         };
 
-        if !zone_fqdn.is_subdomain_of(parent_fqdn) {
-            trace!("zone {} is not a subdomain of {}", zone_fqdn, parent_fqdn);
-            return false;
-        }
-
         // Cannot be a subdomain of itself
-        if self.uid() == zone.uid() {
+        let zone_ref = zone.zone_ref();
+        if self.zone_ref() == zone_ref {
             return false;
         }
 
-        self.spec().delegations.iter().any(|delegation| {
-            delegation.covers_namespace(&zone.namespace().unwrap_or_default())
-                && delegation.validate_zone(parent_fqdn, zone_fqdn)
+        self.evaluate_zone(
+            &zone_ref.namespace.unwrap_or_default(),
+            namespace_labels,
+            zone_fqdn,
+        )
+        .is_allowed()
+    }
+
+    /// Explain how the delegations of this zone are evaluated against the zone `entry`
+    /// of a record, located in `namespace` with the given `namespace_labels`.
+    ///
+    /// The verdict is reached like [`ZoneExt::evaluate_entries`] would, including TTL,
+    /// target and quota constraints. `admitted` are the entries of records in the same
+    /// namespace already adopted by this zone, which count towards the quotas first.
+    ///
+    /// All delegations are explained, including the ones following the decisive one.
+    fn explain_record<'a>(
+        &self,
+        namespace: &str,
+        namespace_labels: &BTreeMap<String, String>,
+        entry: &ZoneEntry,
+        admitted: impl IntoIterator<Item = &'a ZoneEntry>,
+    ) -> Explanation {
+        explain_record(self, namespace, namespace_labels, entry, admitted)
+    }
+}
+
+/// Find the indices of the delegation and record rule deciding the
+/// verdict for a record of type `record_type` at `record_fqdn`.
+pub(super) fn find_record_rule<Z: ZoneExt + ?Sized>(
+    zone: &Z,
+    namespace: &str,
+    namespace_labels: &BTreeMap<String, String>,
+    record_type: Type,
+    record_fqdn: &FullyQualifiedDomainName,
+) -> Option<(usize, usize)> {
+    let Some(parent_fqdn) = zone.fqdn() else {
+        trace!("parent zone {zone} has no fqdn, and can therefore not validate record");
+        return None;
+    };
+
+    if record_fqdn != parent_fqdn && !record_fqdn.is_subdomain_of(parent_fqdn) {
+        trace!("record {record_fqdn} is not a subdomain of {parent_fqdn}");
+        return None;
+    }
+
+    zone.zone_spec()
+        .find_rule(namespace, namespace_labels, |delegation| {
+            delegation.match_record(parent_fqdn, record_type, record_fqdn)
         })
+}
+
+impl ZoneSpec {
+    fn record_verdict(&self, delegation_index: usize, record_index: usize) -> Verdict {
+        Verdict {
+            effect: self.delegations[delegation_index].effect,
+            rule: Some(format!(
+                "delegations[{delegation_index}].records[{record_index}]"
+            )),
+            violation: None,
+        }
+    }
+
+    /// Find the first delegation covering `namespace`, for which `matching_rule`
+    /// produces the index of a matching rule within it.
+    fn find_rule(
+        &self,
+        namespace: &str,
+        namespace_labels: &BTreeMap<String, String>,
+        matching_rule: impl Fn(&Delegation) -> Option<usize>,
+    ) -> Option<(usize, usize)> {
+        self.delegations
+            .iter()
+            .enumerate()
+            .filter(|(_, delegation)| delegation.covers_namespace(namespace, namespace_labels))
+            .find_map(|(index, delegation)| matching_rule(delegation).map(|rule| (index, rule)))
+    }
+}
+
+impl ZoneExt for Zone {
+    fn zone_spec(&self) -> &ZoneSpec {
+        &self.spec
+    }
+
+    fn zone_status(&self) -> Option<&ZoneStatus> {
+        self.status.as_ref()
+    }
+
+    fn zone_ref(&self) -> ZoneRef {
+        ZoneRef {
+            kind: ZoneKind::Zone,
+            name: self.name_any(),
+            namespace: self.namespace(),
+        }
     }
 }
 
@@ -254,11 +484,7 @@ impl DomainExt for Zone {
     }
 
     fn parent(&self) -> Option<ZoneRef> {
-        self.meta()
-            .labels
-            .as_ref()?
-            .get(PARENT_ZONE_LABEL)
-            .map(ZoneRef::from)
+        ZoneRef::parent_of(self.meta())
     }
 }
 
@@ -301,6 +527,54 @@ pub struct ZoneStatus {
     /// [RFC 1912](https://datatracker.ietf.org/doc/html/rfc1912#section-2.2)
     #[serde(default)]
     pub serial: Option<u32>,
+
+    /// Delegation verdicts for records which claim this zone as their parent,
+    /// either explicitly, or by being most immediately within it, listing
+    /// the rule which decided whether each record was allowed or denied.
+    ///
+    /// Limited to 100 records, with denied records listed before allowed
+    /// ones. See `allowedRecords` and `deniedRecords` for the totals.
+    #[serde(default)]
+    pub verdicts: Vec<RecordVerdict>,
+
+    /// Number of records claiming this zone as their parent, which were allowed.
+    #[serde(default)]
+    pub allowed_records: u32,
+
+    /// Number of records claiming this zone as their parent, which were denied.
+    #[serde(default)]
+    pub denied_records: u32,
+
+    /// Latest observations of the zone's state, such as whether another
+    /// zone has already claimed the same fully qualified domain name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+}
+
+/// Records which delegation rule decided whether a record is part of a zone.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordVerdict {
+    /// Kind of the resource the verdict applies to, either `Record` or `RecordSet`.
+    #[serde(default = "defaults::record_kind")]
+    pub kind: String,
+    /// Record the verdict applies to, in `namespace/name` form.
+    pub record: String,
+    pub fqdn: FullyQualifiedDomainName,
+    #[serde(rename = "type")]
+    pub type_: Type,
+    pub effect: DelegationEffect,
+
+    /// Path of the rule which decided the verdict, such as `delegations[1].records[0]`.
+    ///
+    /// Absent if no rule matched the record, in which case it is denied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+
+    /// Why the record was denied, despite the rule allowing it, such as
+    /// its TTL being out of the bounds set by the rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema, Hash)]
@@ -325,16 +599,36 @@ impl From<&ZoneEntry> for RecordIdent {
 }
 
 #[derive(
-    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+    Serialize, Deserialize, Clone, Debug, Default, KubeSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
 )]
+#[x_kube(validation = rules::min_ttl_below_max_ttl())]
 #[serde(rename_all = "camelCase")]
 pub struct RecordDelegation {
-    /// Pattern which delegated records must match.
+    /// Pattern which delegated records must match, relative to the zone.
+    ///
+    /// Supports wildcards (`*`), recursive wildcards (`**`), character classes
+    /// (`[a-z]`), and anchored regular expressions enclosed in slashes (`/api-[0-9]+/`).
     pub pattern: Pattern,
 
     /// Type of record to allow. Empty list implies *any*.
     #[serde(default)]
     pub types: Vec<Type>,
+
+    /// Lowest Time-to-Live delegated records may have.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_ttl: Option<u32>,
+
+    /// Highest Time-to-Live delegated records may have.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ttl: Option<u32>,
+
+    /// Patterns which the domain names pointed to by delegated records
+    /// must match, such as `*.internal.example.org.`. Empty list implies *any*.
+    ///
+    /// Only applies to record types pointing at other domain names,
+    /// namely `CNAME`, `DNAME`, `NS`, `PTR`, `MX` and `SRV`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<Pattern>,
 }
 
 impl RecordDelegation {
@@ -347,90 +641,347 @@ impl RecordDelegation {
         self.pattern.with_origin(zone_fqdn).matches(domain)
             && (self.types.is_empty() || self.types.contains(&record_type))
     }
+
+    /// Verify that the TTL and target of a zone entry satisfy the
+    /// constraints of this delegation.
+    pub fn check_constraints(&self, entry: &ZoneEntry) -> Result<(), ConstraintViolation> {
+        if let Some(min_ttl) = self.min_ttl.filter(|min_ttl| entry.ttl < *min_ttl) {
+            return Err(ConstraintViolation::TtlTooLow(entry.ttl, min_ttl));
+        }
+
+        if let Some(max_ttl) = self.max_ttl.filter(|max_ttl| entry.ttl > *max_ttl) {
+            return Err(ConstraintViolation::TtlTooHigh(entry.ttl, max_ttl));
+        }
+
+        if self.targets.is_empty() {
+            return Ok(());
+        }
+
+        let Some(target) = rdata_target(entry.type_, &entry.rdata) else {
+            return Ok(());
+        };
+
+        if FullyQualifiedDomainName::try_from(target)
+            .is_ok_and(|target| self.targets.iter().any(|pattern| pattern.matches(&target)))
+        {
+            Ok(())
+        } else {
+            Err(ConstraintViolation::TargetNotAllowed(target.to_string()))
+        }
+    }
+}
+
+/// Extract the domain name pointed to by the rdata of record types
+/// which point at other domains, which is always the last field.
+fn rdata_target(type_: Type, rdata: &str) -> Option<&str> {
+    match type_ {
+        Type::CNAME | Type::DNAME | Type::NS | Type::PTR | Type::MX | Type::SRV => {
+            rdata.split_whitespace().last()
+        }
+        _ => None,
+    }
+}
+
+/// Produced when a record matched by a delegation violates its constraints.
+#[derive(Error, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConstraintViolation {
+    /// Record TTL is below the delegation's `minTtl`.
+    #[error("ttl {0} is below the minimum of {1}")]
+    TtlTooLow(u32, u32),
+    /// Record TTL is above the delegation's `maxTtl`.
+    #[error("ttl {0} is above the maximum of {1}")]
+    TtlTooHigh(u32, u32),
+    /// Record points at a domain not matching any of the delegation's `targets`.
+    #[error("target {0} is not allowed")]
+    TargetNotAllowed(String),
+    /// Namespace already has the delegation's `maxRecords` number of records.
+    #[error("namespace exceeds the maximum of {0} records")]
+    QuotaExceeded(u32),
 }
 
+/// Whether a [`Delegation`] allows or denies the records and zones it matches.
 #[derive(
-    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    JsonSchema,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
 )]
+pub enum DelegationEffect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+impl Display for DelegationEffect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DelegationEffect::Allow => f.write_str("allows"),
+            DelegationEffect::Deny => f.write_str("denies"),
+        }
+    }
+}
+
+/// Outcome of evaluating the delegations of a zone against a record or zone.
+///
+/// Defaults to denying, since that is the verdict when no rule matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verdict {
+    pub effect: DelegationEffect,
+
+    /// Path of the rule which decided the verdict, such as `delegations[1].records[0]`,
+    /// or `None` if no rule matched.
+    pub rule: Option<String>,
+
+    /// Constraint of the rule violated by the record, causing it to be denied
+    /// despite the rule allowing it.
+    pub violation: Option<ConstraintViolation>,
+}
+
+impl Verdict {
+    /// Returns true if the verdict allows the record or zone.
+    pub fn is_allowed(&self) -> bool {
+        self.effect == DelegationEffect::Allow
+    }
+}
+
+impl Default for Verdict {
+    fn default() -> Self {
+        Verdict {
+            effect: DelegationEffect::Deny,
+            rule: None,
+            violation: None,
+        }
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.rule, &self.violation) {
+            (Some(rule), Some(violation)) => write!(f, "{} (by {rule}: {violation})", self.effect),
+            (Some(rule), None) => write!(f, "{} (by {rule})", self.effect),
+            (None, _) => write!(f, "{} (no matching rule)", self.effect),
+        }
+    }
+}
+
+/// Rule granting (or denying) namespaces the ability to insert
+/// records and zones into a zone.
+///
+/// A zone's delegations are evaluated in order, and the first delegation
+/// covering the namespace with a rule matching the record or zone decides
+/// whether it is allowed, according to its `effect`. Anything not matched
+/// by any delegation is denied.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct Delegation {
+    /// Whether records and zones matching this delegation are allowed or denied.
+    ///
+    /// Defaults to `Allow`.
+    #[serde(default, skip_serializing_if = "is_allow")]
+    pub effect: DelegationEffect,
+
+    /// Names of namespaces covered by this delegation.
+    ///
+    /// Names may contain wildcards (`*`) and character classes, such as `team-*`
+    /// or `*-prod-[0-9]`, to cover all namespaces matching the pattern.
     #[serde(default)]
     pub namespaces: Vec<String>,
+
+    /// Label selector matching namespaces covered by this delegation,
+    /// in addition to the ones listed in `namespaces`.
+    ///
+    /// If neither `namespaces` nor `namespaceSelector` are specified,
+    /// the delegation covers all namespaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace_selector: Option<LabelSelector>,
+
+    /// Maximum number of records each covered namespace may insert into
+    /// the zone through this delegation.
+    ///
+    /// Records exceeding the limit are rejected, in order of kind (Records before
+    /// RecordSets) and name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_records: Option<u32>,
     #[serde(default)]
     pub zones: Vec<Pattern>,
     #[serde(default)]
     pub records: Vec<RecordDelegation>,
 }
 
+// LabelSelector does not implement Hash, so its fields are hashed instead.
+impl Hash for Delegation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.effect.hash(state);
+        self.namespaces.hash(state);
+        self.namespace_selector
+            .as_ref()
+            .map(|selector| {
+                (
+                    &selector.match_labels,
+                    selector.match_expressions.as_ref().map(|expressions| {
+                        expressions
+                            .iter()
+                            .map(|expression| {
+                                (&expression.key, &expression.operator, &expression.values)
+                            })
+                            .collect::<Vec<_>>()
+                    }),
+                )
+            })
+            .hash(state);
+        self.max_records.hash(state);
+        self.zones.hash(state);
+        self.records.hash(state);
+    }
+}
+
 impl Delegation {
-    /// Check if the given namespace is covered by this Delegation.
-    pub fn covers_namespace(&self, namespace: &str) -> bool {
-        if self.namespaces.is_empty() {
-            return true;
+    /// Check if the given namespace is covered by this Delegation, either
+    /// by name, or because its `labels` match the namespace selector.
+    pub fn covers_namespace(&self, namespace: &str, labels: &BTreeMap<String, String>) -> bool {
+        self.namespace_coverage(namespace, labels).is_covered()
+    }
+
+    /// Determine whether, and how, the given namespace is covered by this Delegation.
+    pub fn namespace_coverage(
+        &self,
+        namespace: &str,
+        labels: &BTreeMap<String, String>,
+    ) -> NamespaceCoverage {
+        if self.namespaces.is_empty() && self.namespace_selector.is_none() {
+            return NamespaceCoverage::All;
         }
 
-        if self
-            .namespaces
-            .iter()
-            .any(|delegated_namespace| delegated_namespace == namespace)
-        {
-            return true;
+        if let Some(delegated_namespace) = self.namespaces.iter().find(|delegated_namespace| {
+            *delegated_namespace == namespace
+                || PatternSegment::try_from(delegated_namespace.as_str())
+                    .is_ok_and(|pattern| pattern.matches_str(namespace))
+        }) {
+            return NamespaceCoverage::Name(delegated_namespace.clone());
+        }
+
+        // Selectors with invalid operators cannot be evaluated, and cover no namespaces.
+        if self.namespace_selector.as_ref().is_some_and(|selector| {
+            Selector::try_from(selector.clone()).is_ok_and(|selector| selector.matches(labels))
+        }) {
+            return NamespaceCoverage::Selector;
         }
 
         trace!("delegation {self:?} does not cover {namespace}");
-        false
+        NamespaceCoverage::NotCovered
+    }
+
+    /// Find the index of the first record rule of this delegation
+    /// matching the (record type, domain) pair.
+    pub fn match_record(
+        &self,
+        zone_fqdn: &FullyQualifiedDomainName,
+        record_type: Type,
+        domain: &FullyQualifiedDomainName,
+    ) -> Option<usize> {
+        self.records.iter().position(|record_delegation| {
+            let matches = record_delegation.validate(zone_fqdn, record_type, domain);
+            trace!("{record_delegation:?} {record_type} matches {domain} ? {matches}");
+            matches
+        })
     }
 
     /// Verify that a (record type, domain) pair matches the delegation
     /// rules of this delegation.
+    ///
+    /// This only checks whether the rules match, and not the delegation's `effect`.
     pub fn validate_record(
         &self,
         zone_fqdn: &FullyQualifiedDomainName,
         record_type: Type,
         domain: &FullyQualifiedDomainName,
     ) -> bool {
-        for record_delegation in &self.records {
-            trace!(
-                "{:?} {} matches {} ? {}",
-                record_delegation,
-                record_type,
-                domain,
-                record_delegation.validate(zone_fqdn, record_type, domain)
-            );
-            if record_delegation.validate(zone_fqdn, record_type, domain) {
-                return true;
-            }
-        }
-
         // If no record delegations exist, deny.
-        false
+        self.match_record(zone_fqdn, record_type, domain).is_some()
+    }
+
+    /// Find the index of the first zone rule of this delegation matching the domain.
+    pub fn match_zone(
+        &self,
+        parent_fqdn: &FullyQualifiedDomainName,
+        domain: &FullyQualifiedDomainName,
+    ) -> Option<usize> {
+        self.zones
+            .iter()
+            .position(|zone_delegation| zone_delegation.with_origin(parent_fqdn).matches(domain))
     }
 
     /// Verify that a domain matches the zone delegation
     /// rules of this delegation.
+    ///
+    /// This only checks whether the rules match, and not the delegation's `effect`.
     pub fn validate_zone(
         &self,
         parent_fqdn: &FullyQualifiedDomainName,
         domain: &FullyQualifiedDomainName,
     ) -> bool {
-        for zone_delegation in &self.zones {
-            if zone_delegation.with_origin(parent_fqdn).matches(domain) {
-                return true;
-            }
-        }
-
         // If no zone delegations exist, deny.
-        false
+        self.match_zone(parent_fqdn, domain).is_some()
+    }
+}
+
+/// Describes how a [`Delegation`] covers a namespace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NamespaceCoverage {
+    /// The delegation does not restrict namespaces, and therefore covers all of them.
+    All,
+    /// The namespace matches the contained entry of the delegation's `namespaces`.
+    Name(String),
+    /// The namespace's labels match the delegation's `namespaceSelector`.
+    Selector,
+    /// The delegation does not cover the namespace.
+    NotCovered,
+}
+
+impl NamespaceCoverage {
+    /// Returns true if the namespace is covered.
+    pub fn is_covered(&self) -> bool {
+        *self != NamespaceCoverage::NotCovered
+    }
+}
+
+impl Display for NamespaceCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NamespaceCoverage::All => f.write_str("covers all namespaces"),
+            NamespaceCoverage::Name(name) => write!(f, "covers namespace by name ({name})"),
+            NamespaceCoverage::Selector => f.write_str("covers namespace by label selector"),
+            NamespaceCoverage::NotCovered => f.write_str("does not cover namespace"),
+        }
     }
 }
 
+fn is_allow(effect: &DelegationEffect) -> bool {
+    *effect == DelegationEffect::Allow
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
     use kube::core::ObjectMeta;
     use kubizone_common::{Class, DomainName, FullyQualifiedDomainName, Pattern, Type};
 
     use crate::v1alpha1::{Record, RecordSpec, RecordStatus, ZoneStatus};
 
-    use super::{Delegation, RecordDelegation, Zone, ZoneSpec};
+    use super::{
+        ConstraintViolation, Delegation, DelegationEffect, RecordDelegation, Zone, ZoneEntry,
+        ZoneExt as _, ZoneSpec,
+    };
 
     #[test]
     fn test_record_delegation() {
@@ -446,7 +997,9 @@ mod tests {
                     records: vec![RecordDelegation {
                         pattern: Pattern::try_from("*").unwrap(),
                         types: vec![],
+                        ..Default::default()
                     }],
+                    ..Default::default()
                 }],
                 ..Default::default()
             },
@@ -458,57 +1011,67 @@ mod tests {
         };
 
         // Record in delegated namespace should be allowed.
-        assert!(zone.validate_record(&Record {
-            metadata: ObjectMeta {
-                namespace: Some(String::from("default")),
-                ..Default::default()
+        assert!(zone.validate_record(
+            &Record {
+                metadata: ObjectMeta {
+                    namespace: Some(String::from("default")),
+                    ..Default::default()
+                },
+                spec: RecordSpec {
+                    domain_name: DomainName::try_from("www.example.org.").unwrap(),
+                    zone_ref: None,
+                    type_: Type::A,
+                    class: Class::IN,
+                    ttl: None,
+                    rdata: String::from("192.168.0.1")
+                },
+                status: Some(RecordStatus {
+                    fqdn: Some(FullyQualifiedDomainName::try_from("www.example.org.").unwrap()),
+                    ..Default::default()
+                })
             },
-            spec: RecordSpec {
-                domain_name: DomainName::try_from("www.example.org.").unwrap(),
-                zone_ref: None,
-                type_: Type::A,
-                class: Class::IN,
-                ttl: None,
-                rdata: String::from("192.168.0.1")
-            },
-            status: Some(RecordStatus {
-                fqdn: Some(FullyQualifiedDomainName::try_from("www.example.org.").unwrap())
-            })
-        }));
+            &BTreeMap::new()
+        ));
 
         // Record in non-delegated namespace should fail.
-        assert!(!zone.validate_record(&Record {
-            metadata: ObjectMeta {
-                namespace: Some(String::from("not-default")),
-                ..Default::default()
-            },
-            spec: RecordSpec {
-                domain_name: DomainName::try_from("www.example.org.").unwrap(),
-                zone_ref: None,
-                type_: Type::A,
-                class: Class::IN,
-                ttl: None,
-                rdata: String::from("192.168.0.1")
+        assert!(!zone.validate_record(
+            &Record {
+                metadata: ObjectMeta {
+                    namespace: Some(String::from("not-default")),
+                    ..Default::default()
+                },
+                spec: RecordSpec {
+                    domain_name: DomainName::try_from("www.example.org.").unwrap(),
+                    zone_ref: None,
+                    type_: Type::A,
+                    class: Class::IN,
+                    ttl: None,
+                    rdata: String::from("192.168.0.1")
+                },
+                status: None
             },
-            status: None
-        }));
+            &BTreeMap::new()
+        ));
 
         // Record in delegated namespace, with invalid super-domain should fail.
-        assert!(!zone.validate_record(&Record {
-            metadata: ObjectMeta {
-                namespace: Some(String::from("default")),
-                ..Default::default()
+        assert!(!zone.validate_record(
+            &Record {
+                metadata: ObjectMeta {
+                    namespace: Some(String::from("default")),
+                    ..Default::default()
+                },
+                spec: RecordSpec {
+                    domain_name: DomainName::try_from("www.test.com.").unwrap(),
+                    zone_ref: None,
+                    type_: Type::A,
+                    class: Class::IN,
+                    ttl: None,
+                    rdata: String::from("192.168.0.1")
+                },
+                status: None
             },
-            spec: RecordSpec {
-                domain_name: DomainName::try_from("www.test.com.").unwrap(),
-                zone_ref: None,
-                type_: Type::A,
-                class: Class::IN,
-                ttl: None,
-                rdata: String::from("192.168.0.1")
-            },
-            status: None
-        }))
+            &BTreeMap::new()
+        ))
     }
 
     #[test]
@@ -523,7 +1086,9 @@ mod tests {
                     records: vec![RecordDelegation {
                         pattern: Pattern::origin(),
                         types: vec![Type::MX],
+                        ..Default::default()
                     }],
+                    ..Default::default()
                 }],
                 ..Default::default()
             },
@@ -538,40 +1103,351 @@ mod tests {
 
         // Record in delegated namespace with delegated record type
         // (MX) should be allowed.
-        assert!(zone.validate_record(&Record {
-            metadata: ObjectMeta {
-                namespace: Some(String::from("default")),
-                ..Default::default()
+        assert!(zone.validate_record(
+            &Record {
+                metadata: ObjectMeta {
+                    namespace: Some(String::from("default")),
+                    ..Default::default()
+                },
+                spec: RecordSpec {
+                    domain_name: DomainName::try_from("example.org.").unwrap(),
+                    zone_ref: None,
+                    type_: Type::MX,
+                    class: Class::IN,
+                    ttl: None,
+                    rdata: String::from("10 mail1.example.org.")
+                },
+                status: Some(RecordStatus {
+                    fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap()),
+                    ..Default::default()
+                })
             },
-            spec: RecordSpec {
-                domain_name: DomainName::try_from("example.org.").unwrap(),
-                zone_ref: None,
-                type_: Type::MX,
-                class: Class::IN,
-                ttl: None,
-                rdata: String::from("10 mail1.example.org.")
-            },
-            status: Some(RecordStatus {
-                fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap())
-            })
-        }));
+            &BTreeMap::new()
+        ));
 
         // Record in delegated namespace with non-delegated record type
         // (A) should not be allowed.
-        assert!(!zone.validate_record(&Record {
-            metadata: ObjectMeta {
-                namespace: Some(String::from("default")),
+        assert!(!zone.validate_record(
+            &Record {
+                metadata: ObjectMeta {
+                    namespace: Some(String::from("default")),
+                    ..Default::default()
+                },
+                spec: RecordSpec {
+                    domain_name: DomainName::try_from("example.org.").unwrap(),
+                    zone_ref: None,
+                    type_: Type::A,
+                    class: Class::IN,
+                    ttl: None,
+                    rdata: String::from("192.168.0.1")
+                },
+                status: None
+            },
+            &BTreeMap::new()
+        ));
+    }
+
+    #[test]
+    fn test_namespace_pattern_delegation() {
+        let delegation = Delegation {
+            namespaces: vec![String::from("team-*"), String::from("*-prod")],
+            zones: vec![],
+            records: vec![],
+            ..Default::default()
+        };
+
+        assert!(delegation.covers_namespace("team-payments", &BTreeMap::new()));
+        assert!(delegation.covers_namespace("billing-prod", &BTreeMap::new()));
+        assert!(!delegation.covers_namespace("billing-dev", &BTreeMap::new()));
+        assert!(!delegation.covers_namespace("team", &BTreeMap::new()));
+    }
+
+    #[test]
+    fn test_namespace_selector_delegation() {
+        let delegation = Delegation {
+            namespaces: vec![String::from("infra")],
+            namespace_selector: Some(LabelSelector {
+                match_labels: Some(BTreeMap::from([(
+                    String::from("team"),
+                    String::from("payments"),
+                )])),
+                ..Default::default()
+            }),
+            zones: vec![],
+            records: vec![],
+            ..Default::default()
+        };
+
+        // Namespaces listed by name are covered regardless of labels.
+        assert!(delegation.covers_namespace("infra", &BTreeMap::new()));
+
+        // Namespaces with matching labels are covered.
+        assert!(delegation.covers_namespace(
+            "payments-prod",
+            &BTreeMap::from([(String::from("team"), String::from("payments"))])
+        ));
+
+        // Namespaces with neither a listed name nor matching labels are not.
+        assert!(!delegation.covers_namespace(
+            "billing-prod",
+            &BTreeMap::from([(String::from("team"), String::from("billing"))])
+        ));
+    }
+
+    #[test]
+    fn test_deny_precedence() {
+        let zone = Zone {
+            spec: ZoneSpec {
+                domain_name: DomainName::try_from("example.org.").unwrap(),
+                zone_ref: None,
+                delegations: vec![
+                    // Only infra may create ACME challenge records.
+                    Delegation {
+                        namespaces: vec![String::from("infra")],
+                        zones: vec![],
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("_acme-challenge.*").unwrap(),
+                            types: vec![Type::TXT],
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                    // Nobody else may create ACME challenge records, nor NS records at the apex.
+                    Delegation {
+                        effect: DelegationEffect::Deny,
+                        namespaces: vec![],
+                        zones: vec![],
+                        records: vec![
+                            RecordDelegation {
+                                pattern: Pattern::try_from("_acme-challenge.*").unwrap(),
+                                types: vec![],
+                                ..Default::default()
+                            },
+                            RecordDelegation {
+                                pattern: Pattern::origin(),
+                                types: vec![Type::NS],
+                                ..Default::default()
+                            },
+                        ],
+                        ..Default::default()
+                    },
+                    // Anything else goes.
+                    Delegation {
+                        namespaces: vec![],
+                        zones: vec![],
+                        records: vec![
+                            RecordDelegation {
+                                pattern: Pattern::origin(),
+                                types: vec![],
+                                ..Default::default()
+                            },
+                            RecordDelegation {
+                                pattern: Pattern::try_from("*").unwrap(),
+                                types: vec![],
+                                ..Default::default()
+                            },
+                        ],
+                        ..Default::default()
+                    },
+                ],
                 ..Default::default()
             },
-            spec: RecordSpec {
+            status: Some(ZoneStatus {
+                fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap()),
+                ..Default::default()
+            }),
+            metadata: kube::core::ObjectMeta::default(),
+        };
+
+        let evaluate = |namespace: &str, type_: Type, fqdn: &str| {
+            let verdict = zone.evaluate_record(
+                namespace,
+                &BTreeMap::new(),
+                type_,
+                &FullyQualifiedDomainName::try_from(fqdn).unwrap(),
+            );
+
+            (verdict.effect, verdict.rule.unwrap_or_default())
+        };
+
+        assert_eq!(
+            evaluate("infra", Type::TXT, "_acme-challenge.www.example.org."),
+            (
+                DelegationEffect::Allow,
+                String::from("delegations[0].records[0]")
+            )
+        );
+
+        assert_eq!(
+            evaluate("default", Type::TXT, "_acme-challenge.www.example.org."),
+            (
+                DelegationEffect::Deny,
+                String::from("delegations[1].records[0]")
+            )
+        );
+
+        assert_eq!(
+            evaluate("infra", Type::NS, "example.org."),
+            (
+                DelegationEffect::Deny,
+                String::from("delegations[1].records[1]")
+            )
+        );
+
+        assert_eq!(
+            evaluate("default", Type::MX, "example.org."),
+            (
+                DelegationEffect::Allow,
+                String::from("delegations[2].records[0]")
+            )
+        );
+
+        assert_eq!(
+            evaluate("default", Type::A, "www.example.org."),
+            (
+                DelegationEffect::Allow,
+                String::from("delegations[2].records[1]")
+            )
+        );
+
+        // Records outside of the zone match no rules at all.
+        assert_eq!(
+            evaluate("default", Type::A, "www.example.com."),
+            (DelegationEffect::Deny, String::new())
+        );
+    }
+
+    #[test]
+    fn test_delegation_constraints() {
+        let zone = Zone {
+            spec: ZoneSpec {
                 domain_name: DomainName::try_from("example.org.").unwrap(),
                 zone_ref: None,
-                type_: Type::A,
-                class: Class::IN,
-                ttl: None,
-                rdata: String::from("192.168.0.1")
+                delegations: vec![Delegation {
+                    namespaces: vec![],
+                    max_records: Some(2),
+                    zones: vec![],
+                    records: vec![RecordDelegation {
+                        pattern: Pattern::try_from("*").unwrap(),
+                        types: vec![],
+                        min_ttl: Some(60),
+                        max_ttl: Some(3600),
+                        targets: vec![Pattern::try_from("*.internal.example.org.").unwrap()],
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
             },
-            status: None
-        }));
+            status: Some(ZoneStatus {
+                fqdn: Some(FullyQualifiedDomainName::try_from("example.org.").unwrap()),
+                ..Default::default()
+            }),
+            metadata: kube::core::ObjectMeta::default(),
+        };
+
+        let entry = |name: &str, type_: Type, ttl: u32, rdata: &str| ZoneEntry {
+            fqdn: FullyQualifiedDomainName::try_from(name).unwrap(),
+            type_,
+            class: Class::IN,
+            ttl,
+            rdata: rdata.to_string(),
+        };
+
+        let labels = BTreeMap::new();
+        let entries = [
+            ("team-a", entry("ok.example.org.", Type::A, 360, "10.0.0.1")),
+            ("team-a", entry("low.example.org.", Type::A, 1, "10.0.0.1")),
+            (
+                "team-a",
+                entry("high.example.org.", Type::A, 86400, "10.0.0.1"),
+            ),
+            (
+                "team-a",
+                entry(
+                    "in.example.org.",
+                    Type::CNAME,
+                    360,
+                    "db.internal.example.org.",
+                ),
+            ),
+            (
+                "team-a",
+                entry("over.example.org.", Type::A, 360, "10.0.0.1"),
+            ),
+            (
+                "team-b",
+                entry("out.example.org.", Type::CNAME, 360, "example.com."),
+            ),
+            (
+                "team-b",
+                entry(
+                    "mx.example.org.",
+                    Type::MX,
+                    360,
+                    "10 mail.internal.example.org.",
+                ),
+            ),
+        ];
+
+        let violations: Vec<_> = zone
+            .evaluate_entries(
+                entries
+                    .iter()
+                    .map(|(namespace, entry)| (*namespace, &labels, std::slice::from_ref(entry))),
+            )
+            .into_iter()
+            .flatten()
+            .map(|verdict| {
+                assert_eq!(verdict.rule.as_deref(), Some("delegations[0].records[0]"));
+                assert_eq!(verdict.is_allowed(), verdict.violation.is_none());
+                verdict.violation
+            })
+            .collect();
+
+        assert_eq!(
+            violations,
+            vec![
+                None,
+                Some(ConstraintViolation::TtlTooLow(1, 60)),
+                Some(ConstraintViolation::TtlTooHigh(86400, 3600)),
+                None,
+                // Denied records do not count towards the quota, so this is the third.
+                Some(ConstraintViolation::QuotaExceeded(2)),
+                Some(ConstraintViolation::TargetNotAllowed(String::from(
+                    "example.com."
+                ))),
+                None,
+            ]
+        );
+
+        // Record sets are admitted as a whole, so the allowed entries of a partly
+        // denied set do not use up the quota of the records following it.
+        let record_set = [
+            entry(
+                "mx.example.org.",
+                Type::MX,
+                360,
+                "10 mail.internal.example.org.",
+            ),
+            entry("mx.example.org.", Type::MX, 360, "20 mail.example.com."),
+        ];
+        let www = [entry("www.example.org.", Type::A, 360, "10.0.0.1")];
+        let api = [entry("api.example.org.", Type::A, 360, "10.0.0.1")];
+
+        let verdicts = zone.evaluate_entries([
+            ("team-a", &labels, record_set.as_slice()),
+            ("team-a", &labels, www.as_slice()),
+            ("team-a", &labels, api.as_slice()),
+        ]);
+
+        assert!(verdicts[0][0].is_allowed());
+        assert_eq!(
+            verdicts[0][1].violation,
+            Some(ConstraintViolation::TargetNotAllowed(String::from(
+                "mail.example.com."
+            )))
+        );
+        assert!(verdicts[1][0].is_allowed());
+        assert!(verdicts[2][0].is_allowed());
     }
 }
@@ -0,0 +1,47 @@
+mod rdata;
+mod record;
+mod zone;
+
+pub use rdata::*;
+pub use record::*;
+pub use zone::*;
+
+// Types which are unchanged between versions.
+pub use crate::v1alpha1::{
+    Delegation, DelegationEffect, DomainExt, RecordDelegation, RecordVerdict, ZoneEntry, ZoneRef,
+};
+
+pub mod defaults {
+    use kubizone_common::Class;
+
+    pub const CLASS: Class = Class::IN;
+
+    /// Service addresses might change often, so we use a low
+    /// Time-to-Live to increase cache responsiveness.
+    pub const TTL: u32 = 360;
+
+    pub const REFRESH: u32 = 86400;
+    pub const RETRY: u32 = 7200;
+    pub const EXPIRE: u32 = 3600000;
+    pub const NEGATIVE_RESPONSE_CACHE: u32 = 360;
+
+    // The functions below are only there for use with `serde(default)`.
+    pub(super) fn class() -> Class {
+        CLASS
+    }
+    pub(super) const fn ttl() -> u32 {
+        TTL
+    }
+    pub(super) const fn refresh() -> u32 {
+        REFRESH
+    }
+    pub(super) const fn retry() -> u32 {
+        RETRY
+    }
+    pub(super) const fn expire() -> u32 {
+        EXPIRE
+    }
+    pub(super) const fn negative_response_cache() -> u32 {
+        NEGATIVE_RESPONSE_CACHE
+    }
+}
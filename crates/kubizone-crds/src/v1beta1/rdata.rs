@@ -0,0 +1,284 @@
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use kubizone_common::{DomainName, Type};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Typed record data, keyed by the type of the record.
+///
+/// Types without a dedicated representation can be expressed
+/// using [`RecordData::Generic`], in zone file syntax.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(
+    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum RecordData {
+    /// IPv4 address.
+    A(Ipv4Addr),
+    /// IPv6 address.
+    AAAA(Ipv6Addr),
+    /// Canonical name the record is an alias of.
+    CNAME(DomainName),
+    /// Domain name the record's subtree is an alias of.
+    DNAME(DomainName),
+    /// Authoritative name server.
+    NS(DomainName),
+    /// Domain name pointed to, most commonly used for reverse lookups.
+    PTR(DomainName),
+    MX(MailExchange),
+    SRV(Service),
+    /// Character strings of the record.
+    TXT(Vec<String>),
+    CAA(CertificationAuthorityAuthorization),
+    /// Record data of any type, in zone file syntax.
+    #[serde(rename = "generic")]
+    Generic(GenericRecordData),
+}
+
+/// Mail server accepting email for the domain.
+#[derive(
+    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MailExchange {
+    /// Preference of this mail server, compared to others. Lower is preferred.
+    pub preference: u16,
+    pub exchange: DomainName,
+}
+
+/// Location of a service, as described by [RFC 2782](https://datatracker.ietf.org/doc/html/rfc2782).
+#[derive(
+    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Service {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: DomainName,
+}
+
+/// Certificate authority authorization, as described by [RFC 8659](https://datatracker.ietf.org/doc/html/rfc8659).
+#[derive(
+    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificationAuthorityAuthorization {
+    #[serde(default)]
+    pub flags: u8,
+    /// Property tag, such as `issue`, `issuewild` or `iodef`.
+    pub tag: String,
+    pub value: String,
+}
+
+/// Record data of an arbitrary type, in zone file syntax.
+#[derive(
+    Serialize, Deserialize, Clone, Debug, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericRecordData {
+    #[serde(rename = "type")]
+    pub type_: Type,
+    pub rdata: String,
+}
+
+impl RecordData {
+    /// Type of the record this data belongs to.
+    pub fn type_(&self) -> Type {
+        match self {
+            RecordData::A(_) => Type::A,
+            RecordData::AAAA(_) => Type::AAAA,
+            RecordData::CNAME(_) => Type::CNAME,
+            RecordData::DNAME(_) => Type::DNAME,
+            RecordData::NS(_) => Type::NS,
+            RecordData::PTR(_) => Type::PTR,
+            RecordData::MX(_) => Type::MX,
+            RecordData::SRV(_) => Type::SRV,
+            RecordData::TXT(_) => Type::TXT,
+            RecordData::CAA(_) => Type::CAA,
+            RecordData::Generic(generic) => generic.type_,
+        }
+    }
+
+    /// Parse `rdata` in zone file syntax as record data of type `type_`.
+    ///
+    /// Data which does not have a typed representation, or which
+    /// cannot be parsed as such, is kept as [`RecordData::Generic`].
+    pub fn from_rdata(type_: Type, rdata: &str) -> Self {
+        Self::parse(type_, rdata).unwrap_or_else(|| {
+            RecordData::Generic(GenericRecordData {
+                type_,
+                rdata: rdata.to_string(),
+            })
+        })
+    }
+
+    fn parse(type_: Type, rdata: &str) -> Option<Self> {
+        let fields: Vec<&str> = rdata.split_whitespace().collect();
+        let domain_name = |field: &str| DomainName::try_from(field).ok();
+
+        Some(match (type_, fields.as_slice()) {
+            (Type::A, [address]) => RecordData::A(address.parse().ok()?),
+            (Type::AAAA, [address]) => RecordData::AAAA(address.parse().ok()?),
+            (Type::CNAME, [target]) => RecordData::CNAME(domain_name(target)?),
+            (Type::DNAME, [target]) => RecordData::DNAME(domain_name(target)?),
+            (Type::NS, [target]) => RecordData::NS(domain_name(target)?),
+            (Type::PTR, [target]) => RecordData::PTR(domain_name(target)?),
+            (Type::MX, [preference, exchange]) => RecordData::MX(MailExchange {
+                preference: preference.parse().ok()?,
+                exchange: domain_name(exchange)?,
+            }),
+            (Type::SRV, [priority, weight, port, target]) => RecordData::SRV(Service {
+                priority: priority.parse().ok()?,
+                weight: weight.parse().ok()?,
+                port: port.parse().ok()?,
+                target: domain_name(target)?,
+            }),
+            (Type::TXT, _) => RecordData::TXT(character_strings(rdata)?),
+            (Type::CAA, [flags, tag, ..]) => {
+                // The value is everything following the tag, and may itself contain whitespace.
+                let (_, value) = rdata.trim().split_once(char::is_whitespace)?;
+                let (_, value) = value.trim_start().split_once(char::is_whitespace)?;
+                let mut value = character_strings(value)?;
+
+                if value.len() != 1 || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return None;
+                }
+
+                RecordData::CAA(CertificationAuthorityAuthorization {
+                    flags: flags.parse().ok()?,
+                    tag: tag.to_string(),
+                    value: value.remove(0),
+                })
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Split `rdata` into character strings, which are either quoted, or delimited by whitespace.
+fn character_strings(rdata: &str) -> Option<Vec<String>> {
+    let mut strings = Vec::new();
+    let mut chars = rdata.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut string = String::new();
+
+        if c == '"' {
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => string.push(chars.next()?),
+                    c => string.push(c),
+                }
+            }
+        } else {
+            string.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                string.push(c);
+            }
+        }
+
+        strings.push(string);
+    }
+
+    Some(strings)
+}
+
+/// Write `string` as a quoted character string.
+fn write_quoted(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for c in string.chars() {
+        if c == '"' || c == '\\' {
+            f.write_str("\\")?;
+        }
+        write!(f, "{c}")?;
+    }
+    f.write_str("\"")
+}
+
+impl Display for RecordData {
+    /// Format the record data in zone file syntax.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordData::A(address) => write!(f, "{address}"),
+            RecordData::AAAA(address) => write!(f, "{address}"),
+            RecordData::CNAME(target)
+            | RecordData::DNAME(target)
+            | RecordData::NS(target)
+            | RecordData::PTR(target) => write!(f, "{target}"),
+            RecordData::MX(mx) => write!(f, "{} {}", mx.preference, mx.exchange),
+            RecordData::SRV(srv) => write!(
+                f,
+                "{} {} {} {}",
+                srv.priority, srv.weight, srv.port, srv.target
+            ),
+            RecordData::TXT(strings) => {
+                for (i, string) in strings.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write_quoted(f, string)?;
+                }
+                Ok(())
+            }
+            RecordData::CAA(caa) => {
+                write!(f, "{} {} ", caa.flags, caa.tag)?;
+                write_quoted(f, &caa.value)
+            }
+            RecordData::Generic(generic) => f.write_str(&generic.rdata),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kubizone_common::Type;
+
+    use super::RecordData;
+
+    #[test]
+    fn round_trip() {
+        for (type_, rdata) in [
+            (Type::A, "192.168.0.1"),
+            (Type::AAAA, "2001:db8::1"),
+            (Type::CNAME, "www.example.org."),
+            (Type::NS, "ns1"),
+            (Type::MX, "10 mail.example.org."),
+            (Type::SRV, "10 5 443 api.example.org."),
+            (Type::TXT, "\"v=spf1 -all\" \"quoted \\\"text\\\"\""),
+            (Type::CAA, "0 issue \"letsencrypt.org\""),
+            (Type::SSHFP, "1 1 123456789abcdef67890123456789abcdef67890"),
+        ] {
+            let data = RecordData::from_rdata(type_, rdata);
+            assert_eq!(data.type_(), type_);
+            assert_eq!(data.to_string(), rdata);
+        }
+    }
+
+    #[test]
+    fn typed_representation() {
+        assert!(matches!(
+            RecordData::from_rdata(Type::MX, "10 mail.example.org."),
+            RecordData::MX(mx) if mx.preference == 10
+        ));
+
+        assert_eq!(
+            RecordData::from_rdata(Type::TXT, "unquoted \"quoted text\""),
+            RecordData::TXT(vec![String::from("unquoted"), String::from("quoted text")])
+        );
+
+        // Unparseable data is retained as-is.
+        let data = RecordData::from_rdata(Type::A, "192.168.0");
+        assert!(matches!(data, RecordData::Generic(_)));
+        assert_eq!(data.type_(), Type::A);
+        assert_eq!(data.to_string(), "192.168.0");
+    }
+}
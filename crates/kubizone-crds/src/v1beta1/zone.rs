@@ -0,0 +1,183 @@
+use std::fmt::Display;
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kube::{CustomResource, KubeSchema, Resource as _, ResourceExt};
+use kubizone_common::{DomainName, FullyQualifiedDomainName};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::v1alpha1::rules;
+
+use super::{Delegation, DomainExt, RecordVerdict, ZoneEntry, ZoneRef, defaults};
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, KubeSchema, Hash, PartialEq)]
+// The 'dev' feature flag puts the resource in a separate dev.kubi.zone group,
+// instead of the real one. This way you can have the production and dev versions
+// of kubizone resources running side by side, without interfering with each other.
+#[cfg_attr(
+    feature = "dev",
+    kube(
+        group = "dev.kubi.zone",
+        version = "v1beta1",
+        kind = "Zone",
+        namespaced
+    )
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(group = "kubi.zone", version = "v1beta1", kind = "Zone", namespaced)
+)]
+#[kube(status = "ZoneStatus")]
+#[kube(printcolumn = r#"{"name":"domain name", "jsonPath": ".spec.domainName", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"fqdn", "jsonPath": ".status.fqdn", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"hash", "jsonPath": ".status.hash", "type": "string"}"#)]
+#[kube(printcolumn = r#"{"name":"serial", "jsonPath": ".status.serial", "type": "string"}"#)]
+#[cfg_attr(
+    feature = "dev",
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.dev\\.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+#[cfg_attr(
+    not(feature = "dev"),
+    kube(
+        printcolumn = r#"{"name":"parent", "jsonPath": ".metadata.annotations.kubi\\.zone/parent-zone", "type": "string"}"#
+    )
+)]
+#[x_kube(validation = rules::domain_name_or_zone_ref())]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneSpec {
+    pub domain_name: DomainName,
+
+    /// Optional reference to a parent zone which this zone is a sub-zone of.
+    ///
+    /// Zones must have *either* a zoneRef, or end in a '.', making it a fully
+    /// qualified domain name. It cannot have both.
+    pub zone_ref: Option<ZoneRef>,
+
+    /// List of namespaced records and zones which are allowed to "insert"
+    /// themselves into this zone. See the [`Delegation`] type for more information.
+    pub delegations: Vec<Delegation>,
+
+    /// Time-to-Live. Represents how long (in seconds) recursive resolvers should
+    /// keep this record in their cache.
+    #[serde(default = "defaults::ttl")]
+    #[x_kube(validation = rules::ttl())]
+    pub ttl: u32,
+
+    /// Timers of the zone's SOA record.
+    #[serde(default)]
+    pub soa: StartOfAuthority,
+}
+
+/// Timers of a zone's SOA record, as described by [RFC 1035](https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.13).
+///
+/// See [RIPE-203](https://www.ripe.net/publications/docs/ripe-203) for recommended values.
+#[derive(
+    Serialize, Deserialize, Clone, Debug, KubeSchema, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct StartOfAuthority {
+    /// Number of seconds after which secondary name servers should
+    /// query the master for the SOA record, to detect zone changes.
+    #[serde(default = "defaults::refresh")]
+    #[x_kube(validation = rules::ttl())]
+    pub refresh: u32,
+
+    /// Number of seconds after which secondary name servers should
+    /// retry to request the serial number from the master if the
+    /// master does not respond. It must be less than `refresh`.
+    #[serde(default = "defaults::retry")]
+    #[x_kube(validation = rules::ttl())]
+    pub retry: u32,
+
+    /// Number of seconds after which secondary name servers should
+    /// stop answering request for this zone if the master does not respond.
+    /// It must be greater than the sum of `refresh` and `retry`.
+    #[serde(default = "defaults::expire")]
+    #[x_kube(validation = rules::ttl())]
+    pub expire: u32,
+
+    /// Time-to-Live of negative responses, such as for non-existent domains.
+    #[serde(default = "defaults::negative_response_cache")]
+    #[x_kube(validation = rules::ttl())]
+    pub negative_response_cache: u32,
+}
+
+impl Default for StartOfAuthority {
+    fn default() -> Self {
+        StartOfAuthority {
+            refresh: defaults::REFRESH,
+            retry: defaults::RETRY,
+            expire: defaults::EXPIRE,
+            negative_response_cache: defaults::NEGATIVE_RESPONSE_CACHE,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneStatus {
+    #[serde(default)]
+    pub entries: Vec<ZoneEntry>,
+
+    /// Zones fully qualified domain name.
+    ///
+    /// If the `.spec.domainName` is already fully qualified, these are identical.
+    ///
+    /// If instead the Zone uses a `.spec.zoneRef` to indicate its parent,
+    /// this will be the concatenated version of this zone's `.spec.domainName`
+    /// and the parent's `.status.fqdn`
+    #[serde(default)]
+    pub fqdn: Option<FullyQualifiedDomainName>,
+
+    /// Hash value of all relevant zone entries.
+    #[serde(default)]
+    pub hash: Option<String>,
+
+    /// Serial of the latest generated zonefile.
+    #[serde(default)]
+    pub serial: Option<u32>,
+
+    /// Delegation verdicts for records which claim this zone as their parent,
+    /// listing the rule which decided whether each record was allowed or denied.
+    ///
+    /// Limited to 100 records, with denied records listed before allowed
+    /// ones. See `allowedRecords` and `deniedRecords` for the totals.
+    #[serde(default)]
+    pub verdicts: Vec<RecordVerdict>,
+
+    /// Number of records claiming this zone as their parent, which were allowed.
+    #[serde(default)]
+    pub allowed_records: u32,
+
+    /// Number of records claiming this zone as their parent, which were denied.
+    #[serde(default)]
+    pub denied_records: u32,
+
+    /// Latest observations of the zone's state.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+}
+
+impl DomainExt for Zone {
+    fn fqdn(&self) -> Option<&FullyQualifiedDomainName> {
+        self.status.as_ref().and_then(|status| status.fqdn.as_ref())
+    }
+
+    fn parent(&self) -> Option<ZoneRef> {
+        ZoneRef::parent_of(self.meta())
+    }
+}
+
+impl Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Unwrap safety: Zones are namespaced and therefore always have a name.
+        write!(
+            f,
+            "{}/{}",
+            self.metadata.namespace.as_ref().unwrap(),
+            self.name_any()
+        )
+    }
+}
@@ -45,19 +45,12 @@ pub struct ZoneFileSpec {
 
 impl ZoneFile {
     /// Retrieve the [`ZoneFile`]'s `zoneRef`, but populate the `namespace` variable,
-    /// if not specified by the zoneref itself.
+    /// if not specified by the zoneref itself, and not referencing a ClusterZone.
     pub fn zone_ref(&self) -> Vec<ZoneRef> {
         self.spec
             .zone_refs
             .iter()
-            .map(|zone_ref| ZoneRef {
-                name: zone_ref.name.clone(),
-                namespace: zone_ref
-                    .namespace
-                    .as_ref()
-                    .or(self.namespace().as_ref())
-                    .cloned(),
-            })
+            .map(|zone_ref| zone_ref.in_namespace(self.namespace().as_deref()))
             .collect()
     }
 }